@@ -1,18 +1,41 @@
 use std::{
     any::{Any, TypeId},
+    collections::VecDeque,
+    path::PathBuf,
     sync::Arc,
 };
 
+use crate::bus::journal::{self, Journals, LogOptions, Replay, Retention, SharedJournal, Stamped};
+use crate::bus::topic::{validate_topic, TopicMessage, TopicPattern};
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
 use exception::typed::common::MessageBusError;
+use exception::GlobalResult;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use tokio::sync::broadcast;
 use tokio::time::{timeout, Duration};
 
 const DEFAULT_CHANNEL_SIZE: usize = 64;
 
+type Payload = Arc<dyn Any + Send + Sync>;
+
+/// Broadcast bus routing by message type and, within a type, optionally by topic.
+///
+/// The type-only API publishes on the type's default topic, which topic and pattern
+/// subscriptions do not see.
 #[derive(Clone)]
 pub struct TypedMessageBus {
-    channels: Arc<DashMap<TypeId, broadcast::Sender<Arc<dyn Any + Send + Sync>>>>,
+    channels: Arc<DashMap<TypeId, broadcast::Sender<Payload>>>,
+    topics: Arc<DashMap<(TypeId, String), broadcast::Sender<Payload>>>,
+    patterns: Arc<DashMap<TypeId, Vec<PatternChannel>>>,
+    capacities: Arc<DashMap<(TypeId, String), usize>>,
+    journals: Arc<Journals>,
+}
+
+struct PatternChannel {
+    pattern: TopicPattern,
+    tx: broadcast::Sender<Payload>,
 }
 
 impl Default for TypedMessageBus {
@@ -25,28 +48,64 @@ impl TypedMessageBus {
     pub fn new() -> Self {
         Self {
             channels: Arc::new(DashMap::new()),
+            topics: Arc::new(DashMap::new()),
+            patterns: Arc::new(DashMap::new()),
+            capacities: Arc::new(DashMap::new()),
+            journals: Arc::new(Journals::default()),
         }
     }
 
-    pub fn publish<T>(
-        &self,
-        msg: T,
-    ) -> Result<usize, broadcast::error::SendError<Arc<dyn Any + Send + Sync>>>
+    /// Keeps `T`'s recent messages so new subscribers get them first and lagging ones catch up.
+    pub fn retain<T>(&self, retention: Retention)
     where
         T: Send + Sync + 'static + Clone,
     {
-        let type_id = TypeId::of::<T>();
-        let entry = self.channels.entry(type_id).or_insert_with(|| {
-            let (tx, _) = broadcast::channel(DEFAULT_CHANNEL_SIZE);
-            tx
-        });
-        entry.send(Arc::new(msg))
+        self.journals.retain::<T>(retention);
     }
 
-    pub fn publish_ref<T>(
+    /// Appends `T`'s messages to the log at `path` and continues its offsets, so subscribers
+    /// can resume with [`sub_type_channel_from`](Self::sub_type_channel_from) after a restart.
+    ///
+    /// Records still within `retention` are replayed to new subscribers as with
+    /// [`retain`](Self::retain). Must be called before `T` is first published.
+    pub fn persist<T>(&self, path: impl Into<PathBuf>, retention: Retention) -> GlobalResult<()>
+    where
+        T: Send + Sync + 'static + Clone + Serialize + DeserializeOwned,
+    {
+        self.persist_with::<T>(path, retention, LogOptions::default())
+    }
+
+    /// [`persist`](Self::persist) with the log's sync policy and segment size set explicitly.
+    pub fn persist_with<T>(
         &self,
-        msg: &T,
-    ) -> Result<usize, broadcast::error::SendError<Arc<dyn Any + Send + Sync>>>
+        path: impl Into<PathBuf>,
+        retention: Retention,
+        options: LogOptions,
+    ) -> GlobalResult<()>
+    where
+        T: Send + Sync + 'static + Clone + Serialize + DeserializeOwned,
+    {
+        self.journals.persist::<T>(path.into(), retention, options)
+    }
+
+    pub fn publish<T>(&self, msg: T) -> Result<usize, broadcast::error::SendError<Payload>>
+    where
+        T: Send + Sync + 'static + Clone,
+    {
+        let tx = self.sender::<T>();
+        match self.journals.get::<T>() {
+            None => tx.send(Arc::new(msg)),
+            Some(journal) => {
+                // Recording and sending under the journal lock keeps history and channel in
+                // the same order for subscribers that join in between.
+                let mut journal = journal::lock(&journal);
+                let offset = journal.record(&msg);
+                tx.send(Arc::new(Stamped { offset, msg }))
+            }
+        }
+    }
+
+    pub fn publish_ref<T>(&self, msg: &T) -> Result<usize, broadcast::error::SendError<Payload>>
     where
         T: Send + Sync + 'static + Clone,
     {
@@ -57,24 +116,190 @@ impl TypedMessageBus {
     where
         T: Send + Sync + 'static + Clone,
     {
+        let tx = self.sender::<T>();
+        let Some(journal) = self.journals.get::<T>() else {
+            return TypedReceiver::new(tx.subscribe(), None, Replay::default());
+        };
+        let mut locked = journal::lock(&journal);
+        let inner = tx.subscribe();
+        let (backlog, _) = locked.since(0);
+        let replay = Replay::new(backlog, locked.next_offset());
+        drop(locked);
+        TypedReceiver::new(inner, Some(journal), replay)
+    }
+
+    /// Subscribes to a persisted `T` starting with the logged message at `offset`.
+    ///
+    /// Fails with [`MessageBusError::Lagged`] when the log has already rotated `offset` away.
+    pub fn sub_type_channel_from<T>(&self, offset: u64) -> GlobalResult<TypedReceiver<T>>
+    where
+        T: Send + Sync + 'static + Clone + DeserializeOwned,
+    {
+        let tx = self.sender::<T>();
+        let journal = self.journals.persisted::<T>()?;
+        let locked = journal::lock(&journal);
+        let inner = tx.subscribe();
+        let live_from = locked.next_offset();
+        let reader = locked.log_reader();
+        drop(locked);
+        // The log is read without the journal lock so publishers are not held up by it.
+        let backlog = match reader {
+            Some(reader) => reader.read(offset, live_from)?,
+            None => VecDeque::new(),
+        };
+        Ok(TypedReceiver::new(
+            inner,
+            Some(journal),
+            Replay::new(backlog, live_from),
+        ))
+    }
+
+    /// Sets the channel capacity of a topic or pattern of `T`, used when its channel is created.
+    ///
+    /// Fails with `InvalidCapacity` for zero and with `AlreadyExists` while the channel has
+    /// subscribers.
+    pub fn set_topic_capacity<T>(&self, topic: &str, capacity: usize) -> Result<(), MessageBusError>
+    where
+        T: Send + Sync + 'static + Clone,
+    {
+        if capacity == 0 {
+            return Err(MessageBusError::InvalidCapacity(capacity));
+        }
+        let pattern = TopicPattern::parse(topic)?;
+        let type_id = TypeId::of::<T>();
+        let key = (type_id, topic.to_string());
+        // The channel entry stays locked until the capacity is stored, so a concurrent
+        // subscriber either sees the old channel here or creates one with the new capacity.
+        if pattern.is_exact() {
+            match self.topics.entry(key.clone()) {
+                Entry::Occupied(entry) => {
+                    if entry.get().receiver_count() > 0 {
+                        return Err(MessageBusError::AlreadyExists);
+                    }
+                    self.capacities.insert(key, capacity);
+                    entry.remove();
+                }
+                Entry::Vacant(_) => {
+                    self.capacities.insert(key, capacity);
+                }
+            }
+        } else {
+            let mut channels = self.patterns.entry(type_id).or_default();
+            if channels
+                .iter()
+                .any(|channel| channel.pattern == pattern && channel.tx.receiver_count() > 0)
+            {
+                return Err(MessageBusError::AlreadyExists);
+            }
+            channels.retain(|channel| channel.pattern != pattern);
+            self.capacities.insert(key, capacity);
+        }
+        Ok(())
+    }
+
+    /// Publishes to `topic` of `T`, e.g. `device/42/status`, and to every pattern matching it.
+    ///
+    /// Returns how many subscribers it reached. Topics are not journaled.
+    pub fn publish_to<T>(&self, topic: &str, msg: T) -> Result<usize, MessageBusError>
+    where
+        T: Send + Sync + 'static + Clone,
+    {
+        validate_topic(topic)?;
         let type_id = TypeId::of::<T>();
-        let tx = self
-            .channels
-            .entry(type_id)
+        let matching: Vec<_> = self
+            .patterns
+            .get(&type_id)
+            .map(|channels| {
+                channels
+                    .iter()
+                    .filter(|channel| channel.pattern.matches(topic))
+                    .map(|channel| channel.tx.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let exact = self
+            .topics
+            .get(&(type_id, topic.to_string()))
+            .map(|entry| entry.clone());
+
+        let tagged = (!matching.is_empty()).then(|| -> Payload {
+            Arc::new(TopicMessage {
+                topic: Arc::from(topic),
+                msg: msg.clone(),
+            })
+        });
+        let mut delivered = 0;
+        if let Some(tx) = exact {
+            delivered += tx.send(Arc::new(msg)).unwrap_or(0);
+        }
+        if let Some(payload) = tagged {
+            for tx in matching {
+                delivered += tx.send(payload.clone()).unwrap_or(0);
+            }
+        }
+        Ok(delivered)
+    }
+
+    /// Subscribes to exactly `topic` of `T`.
+    pub fn sub_topic_channel<T>(&self, topic: &str) -> Result<TypedReceiver<T>, MessageBusError>
+    where
+        T: Send + Sync + 'static + Clone,
+    {
+        validate_topic(topic)?;
+        let key = (TypeId::of::<T>(), topic.to_string());
+        let tx = match self.topics.entry(key) {
+            Entry::Occupied(entry) => entry.get().clone(),
+            Entry::Vacant(entry) => {
+                let capacity = self.capacity(entry.key());
+                entry.insert(broadcast::channel(capacity).0).clone()
+            }
+        };
+        Ok(TypedReceiver::new(tx.subscribe(), None, Replay::default()))
+    }
+
+    /// Subscribes to the topics of `T` matching `pattern`, where `+` stands for one level and a
+    /// trailing `#` for any remaining levels.
+    pub fn sub_topic_pattern<T>(
+        &self,
+        pattern: &str,
+    ) -> Result<TypedReceiver<TopicMessage<T>>, MessageBusError>
+    where
+        T: Send + Sync + 'static + Clone,
+    {
+        let pattern = TopicPattern::parse(pattern)?;
+        let key = (TypeId::of::<T>(), pattern.as_str().to_string());
+        let mut channels = self.patterns.entry(key.0).or_default();
+        let inner = match channels.iter().find(|channel| channel.pattern == pattern) {
+            Some(channel) => channel.tx.subscribe(),
+            None => {
+                let (tx, rx) = broadcast::channel(self.capacity(&key));
+                channels.push(PatternChannel { pattern, tx });
+                rx
+            }
+        };
+        Ok(TypedReceiver::new(inner, None, Replay::default()))
+    }
+
+    fn capacity(&self, key: &(TypeId, String)) -> usize {
+        self.capacities
+            .get(key)
+            .map_or(DEFAULT_CHANNEL_SIZE, |capacity| *capacity)
+    }
+
+    fn sender<T: 'static>(&self) -> broadcast::Sender<Payload> {
+        self.channels
+            .entry(TypeId::of::<T>())
             .or_insert_with(|| {
                 let (tx, _) = broadcast::channel(DEFAULT_CHANNEL_SIZE);
                 tx
             })
-            .clone();
-
-        TypedReceiver {
-            inner: tx.subscribe(),
-            _marker: std::marker::PhantomData,
-        }
+            .clone()
     }
 
     pub fn spawn_cleanup_task(&self) {
         let map = self.channels.clone();
+        let topics = self.topics.clone();
+        let patterns = self.patterns.clone();
         tokio::spawn(async move {
             loop {
                 tokio::time::sleep(Duration::from_secs(60)).await;
@@ -86,6 +311,11 @@ impl TypedMessageBus {
                 for id in to_remove {
                     map.remove(&id);
                 }
+                topics.retain(|_, tx| tx.receiver_count() > 0);
+                patterns.retain(|_, channels| {
+                    channels.retain(|channel| channel.tx.receiver_count() > 0);
+                    !channels.is_empty()
+                });
             }
         });
     }
@@ -95,7 +325,9 @@ pub struct TypedReceiver<T>
 where
     T: Send + Sync + 'static + Clone,
 {
-    inner: broadcast::Receiver<Arc<dyn Any + Send + Sync>>,
+    inner: broadcast::Receiver<Payload>,
+    journal: Option<SharedJournal<T>>,
+    replay: Replay<T>,
     _marker: std::marker::PhantomData<T>,
 }
 
@@ -103,35 +335,94 @@ impl<T> TypedReceiver<T>
 where
     T: Send + Sync + 'static + Clone,
 {
+    fn new(
+        inner: broadcast::Receiver<Payload>,
+        journal: Option<SharedJournal<T>>,
+        replay: Replay<T>,
+    ) -> Self {
+        Self {
+            inner,
+            journal,
+            replay,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Offset of the last journaled message received; resume from the one after it.
+    pub fn offset(&self) -> Option<u64> {
+        self.replay.last_offset()
+    }
+
+    /// With retention, `Lagged` is returned only when the missed messages were no longer
+    /// retained; the ones still retained follow on the next calls.
     pub fn try_recv(&mut self) -> Result<T, MessageBusError> {
-        match self.inner.try_recv() {
-            Ok(arc) => Self::try_cast(arc),
-            Err(broadcast::error::TryRecvError::Closed) => Err(MessageBusError::ChannelClosed),
-            Err(broadcast::error::TryRecvError::Empty) => Err(MessageBusError::Empty),
-            Err(broadcast::error::TryRecvError::Lagged(_)) => Err(MessageBusError::Lagged),
+        loop {
+            if let Some(msg) = self.replay.pop() {
+                return Ok(msg);
+            }
+            match self.inner.try_recv() {
+                Ok(arc) => {
+                    if let Some(msg) = self.accept(arc)? {
+                        return Ok(msg);
+                    }
+                }
+                Err(broadcast::error::TryRecvError::Closed) => {
+                    return Err(MessageBusError::ChannelClosed)
+                }
+                Err(broadcast::error::TryRecvError::Empty) => return Err(MessageBusError::Empty),
+                Err(broadcast::error::TryRecvError::Lagged(_)) => self.catch_up()?,
+            }
         }
     }
 
     pub async fn recv(&mut self) -> Result<T, MessageBusError> {
-        match self.inner.recv().await {
-            Ok(arc) => Self::try_cast(arc),
-            Err(broadcast::error::RecvError::Closed) => Err(MessageBusError::ChannelClosed),
-            Err(broadcast::error::RecvError::Lagged(_)) => Err(MessageBusError::Lagged),
+        loop {
+            if let Some(msg) = self.replay.pop() {
+                return Ok(msg);
+            }
+            match self.inner.recv().await {
+                Ok(arc) => {
+                    if let Some(msg) = self.accept(arc)? {
+                        return Ok(msg);
+                    }
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    return Err(MessageBusError::ChannelClosed)
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => self.catch_up()?,
+            }
         }
     }
 
     pub async fn recv_with_timeout(&mut self, dur: Duration) -> Result<T, MessageBusError> {
-        match timeout(dur, self.inner.recv()).await {
-            Ok(Ok(arc)) => Self::try_cast(arc),
-            Ok(Err(broadcast::error::RecvError::Closed)) => Err(MessageBusError::ChannelClosed),
-            Ok(Err(broadcast::error::RecvError::Lagged(_))) => Err(MessageBusError::Lagged),
-            Err(_) => Err(MessageBusError::Timeout),
+        timeout(dur, self.recv())
+            .await
+            .unwrap_or(Err(MessageBusError::Timeout))
+    }
+
+    /// Skips to the channel tail and queues the retained messages that were overrun.
+    fn catch_up(&mut self) -> Result<(), MessageBusError> {
+        let Some(journal) = &self.journal else {
+            return Err(MessageBusError::Lagged);
+        };
+        let mut journal = journal::lock(journal);
+        self.inner = self.inner.resubscribe();
+        let (missed, gap) = journal.since(self.replay.expected());
+        self.replay.resume(missed, journal.next_offset());
+        if gap {
+            Err(MessageBusError::Lagged)
+        } else {
+            Ok(())
         }
     }
 
-    fn try_cast(arc: Arc<dyn Any + Send + Sync>) -> Result<T, MessageBusError> {
-        match Arc::downcast::<T>(arc) {
-            Ok(val) => Ok((*val).clone()),
+    fn accept(&mut self, arc: Payload) -> Result<Option<T>, MessageBusError> {
+        let arc = match Arc::downcast::<T>(arc) {
+            Ok(val) => return Ok(Some((*val).clone())),
+            Err(arc) => arc,
+        };
+        match Arc::downcast::<Stamped<T>>(arc) {
+            Ok(stamped) => Ok(self.replay.accept(stamped.offset, stamped.msg.clone())),
             Err(_) => Err(MessageBusError::TypeMismatch),
         }
     }
@@ -1,2 +1,5 @@
 pub mod broadcast;
+pub mod journal;
 pub mod mpsc;
+mod queue;
+pub mod topic;
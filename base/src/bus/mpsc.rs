@@ -1,18 +1,44 @@
 use std::{
     any::{Any, TypeId},
+    collections::VecDeque,
+    path::PathBuf,
     sync::Arc,
 };
 
+use crate::bus::journal::{self, Journals, LogOptions, Replay, Retention, Stamped};
+use crate::bus::queue::{Boxed, TypeQueue};
+pub use crate::bus::queue::{OverflowPolicy, QueueStats};
 use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
 use exception::typed::common::MessageBusError;
-use tokio::sync::mpsc;
+use exception::{GlobalResult, GlobalResultExt};
+use log::error;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::future::Future;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
 use tokio::time::{timeout, Duration};
 
 const DEFAULT_CHANNEL_SIZE: usize = 64;
 
 pub struct TypedMessageBus {
-    channels: Arc<DashMap<TypeId, mpsc::Sender<Box<dyn Any + Send + Sync>>>>,
+    channels: Arc<DashMap<TypeId, Arc<TypeQueue>>>,
+    policies: Arc<DashMap<TypeId, OverflowPolicy>>,
+    journals: Arc<Journals>,
+}
+
+/// A request routed to the responder of `Req`; answer it with [`reply`](Self::reply).
+pub struct Request<Req, Resp> {
+    pub msg: Req,
+    reply: oneshot::Sender<Resp>,
+}
+
+impl<Req, Resp> Request<Req, Resp> {
+    /// Sends the response; `false` if the requester stopped waiting.
+    pub fn reply(self, resp: Resp) -> bool {
+        self.reply.send(resp).is_ok()
+    }
 }
 
 impl Default for TypedMessageBus {
@@ -25,20 +51,186 @@ impl TypedMessageBus {
     pub fn new() -> Self {
         Self {
             channels: Arc::new(DashMap::new()),
+            policies: Arc::new(DashMap::new()),
+            journals: Arc::new(Journals::default()),
+        }
+    }
+
+    /// Sets what publishing `T` does when its queue is full; takes effect immediately.
+    pub fn set_overflow_policy<T>(&self, policy: OverflowPolicy)
+    where
+        T: Send + Sync + 'static,
+    {
+        let type_id = TypeId::of::<T>();
+        self.policies.insert(type_id, policy);
+        if let Some(queue) = self.channels.get(&type_id) {
+            queue.set_policy(policy);
         }
     }
 
+    /// Queue counters of `T`, once it has a subscriber.
+    pub fn stats<T>(&self) -> Option<QueueStats>
+    where
+        T: Send + Sync + 'static,
+    {
+        self.channels
+            .get(&TypeId::of::<T>())
+            .map(|queue| queue.stats())
+    }
+
+    /// Queue counters of every subscribed type.
+    pub fn all_stats(&self) -> Vec<QueueStats> {
+        self.channels.iter().map(|queue| queue.stats()).collect()
+    }
+
+    /// Registers the receiver of `Req` requests; each must be answered with
+    /// [`Request::reply`].
+    pub fn sub_request_channel<Req, Resp>(
+        &self,
+    ) -> Result<TypedReceiver<Request<Req, Resp>>, MessageBusError>
+    where
+        Req: Send + Sync + 'static,
+        Resp: Send + Sync + 'static,
+    {
+        self.sub_type_channel::<Request<Req, Resp>>()
+    }
+
+    /// Answers `Req` requests with `handler`, one at a time, on a spawned task that ends
+    /// when the bus is dropped.
+    pub fn respond<Req, Resp, F, Fut>(&self, handler: F) -> Result<JoinHandle<()>, MessageBusError>
+    where
+        Req: Send + Sync + 'static,
+        Resp: Send + Sync + 'static,
+        F: Fn(Req) -> Fut + Send + 'static,
+        Fut: Future<Output = Resp> + Send,
+    {
+        let mut requests = self.sub_request_channel::<Req, Resp>()?;
+        Ok(tokio::spawn(async move {
+            while let Ok(request) = requests.recv().await {
+                let resp = handler(request.msg).await;
+                let _ = request.reply.send(resp);
+            }
+        }))
+    }
+
+    /// Sends `msg` to the responder of `Req` and waits up to `wait` for its answer, including
+    /// the time spent waiting for queue room under [`OverflowPolicy::Block`].
+    ///
+    /// Fails with `NotFound` without a responder and `ChannelClosed` if the request was
+    /// dropped unanswered.
+    pub async fn request<Req, Resp>(
+        &self,
+        msg: Req,
+        wait: Duration,
+    ) -> Result<Resp, MessageBusError>
+    where
+        Req: Send + Sync + 'static,
+        Resp: Send + Sync + 'static,
+    {
+        let (reply, response) = oneshot::channel();
+        let exchange = async {
+            self.publish(Request { msg, reply }).await?;
+            response.await.map_err(|_| MessageBusError::ChannelClosed)
+        };
+        timeout(wait, exchange)
+            .await
+            .unwrap_or(Err(MessageBusError::Timeout))
+    }
+
+    /// Keeps `T`'s recent messages, including those published before anyone subscribed, and
+    /// hands them to the subscriber first.
+    pub fn retain<T>(&self, retention: Retention)
+    where
+        T: Send + Sync + 'static + Clone,
+    {
+        self.journals.retain::<T>(retention);
+    }
+
+    /// Appends `T`'s messages to the log at `path` and continues its offsets, so the
+    /// subscriber can resume with [`sub_type_channel_from`](Self::sub_type_channel_from)
+    /// after a restart. Must be called before `T` is first published.
+    pub fn persist<T>(&self, path: impl Into<PathBuf>, retention: Retention) -> GlobalResult<()>
+    where
+        T: Send + Sync + 'static + Clone + Serialize + DeserializeOwned,
+    {
+        self.persist_with::<T>(path, retention, LogOptions::default())
+    }
+
+    /// [`persist`](Self::persist) with the log's sync policy and segment size set explicitly.
+    pub fn persist_with<T>(
+        &self,
+        path: impl Into<PathBuf>,
+        retention: Retention,
+        options: LogOptions,
+    ) -> GlobalResult<()>
+    where
+        T: Send + Sync + 'static + Clone + Serialize + DeserializeOwned,
+    {
+        self.journals.persist::<T>(path.into(), retention, options)
+    }
+
     pub fn sub_type_channel<T>(&self) -> Result<TypedReceiver<T>, MessageBusError>
     where
         T: Send + Sync + 'static,
     {
-        let (tx, rx) = mpsc::channel::<Box<dyn Any + Send + Sync>>(DEFAULT_CHANNEL_SIZE);
+        let Some(journal) = self.journals.get::<T>() else {
+            return self.register(Replay::default);
+        };
+        let mut locked = journal::lock(&journal);
+        self.register(|| {
+            let (backlog, _) = locked.since(0);
+            Replay::new(backlog, locked.next_offset())
+        })
+    }
+
+    /// Subscribes to a persisted `T` starting with the logged message at `offset`.
+    ///
+    /// Fails with [`MessageBusError::Lagged`] when the log has already rotated `offset` away.
+    pub fn sub_type_channel_from<T>(&self, offset: u64) -> GlobalResult<TypedReceiver<T>>
+    where
+        T: Send + Sync + 'static + DeserializeOwned,
+    {
+        let journal = self.journals.persisted::<T>()?;
+        let locked = journal::lock(&journal);
+        let live_from = locked.next_offset();
+        let mut receiver = self
+            .register(|| Replay::new(VecDeque::new(), live_from))
+            .hand_log(|msg| error!("{msg}: type={}", std::any::type_name::<T>()))?;
+        let reader = locked.log_reader();
+        drop(locked);
+        // The log is read without the journal lock so publishers are not held up by it.
+        if let Some(reader) = reader {
+            receiver
+                .replay
+                .resume(reader.read(offset, live_from)?, live_from);
+        }
+        Ok(receiver)
+    }
+
+    /// Installs the only channel for `T`; the journal lock, if any, is held by the caller so
+    /// no message is both replayed and delivered.
+    fn register<T>(
+        &self,
+        replay: impl FnOnce() -> Replay<T>,
+    ) -> Result<TypedReceiver<T>, MessageBusError>
+    where
+        T: Send + Sync + 'static,
+    {
         let type_id = TypeId::of::<T>();
+        let policy = self
+            .policies
+            .get(&type_id)
+            .map_or(OverflowPolicy::default(), |policy| *policy);
         match self.channels.entry(type_id) {
             Entry::Occupied(_) => Err(MessageBusError::AlreadyExists),
             Entry::Vacant(val) => {
-                val.insert(tx);
-                Ok(TypedReceiver::new(rx))
+                let queue = Arc::new(TypeQueue::new(
+                    std::any::type_name::<T>(),
+                    DEFAULT_CHANNEL_SIZE,
+                    policy,
+                ));
+                val.insert(queue.clone());
+                Ok(TypedReceiver::from_source(Source::Queue(queue), replay()))
             }
         }
     }
@@ -47,15 +239,15 @@ impl TypedMessageBus {
     where
         T: Send + Sync + 'static,
     {
-        let type_id = TypeId::of::<T>();
-        let entry = self
-            .channels
-            .get(&type_id)
-            .ok_or(MessageBusError::NotFound)?;
-        entry.try_send(Box::new(msg)).map_err(|err| match err {
-            mpsc::error::TrySendError::Full(_) => MessageBusError::Full,
-            mpsc::error::TrySendError::Closed(_) => MessageBusError::ChannelClosed,
-        })
+        let Some(journal) = self.journals.get::<T>() else {
+            return self.try_send::<T>(Box::new(msg));
+        };
+        let mut journal = journal::lock(&journal);
+        let offset = journal.record(&msg);
+        match self.try_send::<T>(Box::new(Stamped { offset, msg })) {
+            Err(MessageBusError::NotFound) => Ok(()),
+            sent => sent,
+        }
     }
 
     pub async fn publish<T>(&self, msg: T) -> Result<(), MessageBusError>
@@ -63,22 +255,54 @@ impl TypedMessageBus {
         T: Send + Sync + 'static,
     {
         let type_id = TypeId::of::<T>();
-        let entry = self
-            .channels
+        let Some(journal) = self.journals.get::<T>() else {
+            return self.queue(type_id)?.send(Box::new(msg)).await;
+        };
+        // The channel is looked up under the journal lock so a subscriber registering
+        // concurrently gets the message either replayed or delivered, never both.
+        let (offset, queue) = {
+            let mut journal = journal::lock(&journal);
+            (journal.record(&msg), self.queue(type_id))
+        };
+        match queue {
+            Err(MessageBusError::NotFound) => Ok(()),
+            Err(err) => Err(err),
+            Ok(queue) => queue.send(Box::new(Stamped { offset, msg })).await,
+        }
+    }
+
+    fn queue(&self, type_id: TypeId) -> Result<Arc<TypeQueue>, MessageBusError> {
+        self.channels
             .get(&type_id)
-            .ok_or(MessageBusError::NotFound)?;
-        entry
-            .send(Box::new(msg))
-            .await
-            .map_err(|_| MessageBusError::ChannelClosed)
+            .map(|entry| entry.clone())
+            .ok_or(MessageBusError::NotFound)
+    }
+
+    fn try_send<T: 'static>(&self, msg: Boxed) -> Result<(), MessageBusError> {
+        self.queue(TypeId::of::<T>())?.try_send(msg)
     }
 }
 
+impl Drop for TypedMessageBus {
+    fn drop(&mut self) {
+        for queue in self.channels.iter() {
+            queue.disconnect();
+        }
+    }
+}
+
+enum Source {
+    Queue(Arc<TypeQueue>),
+    /// A bare channel handed to the deprecated [`TypedReceiver::new`].
+    Channel(mpsc::Receiver<Boxed>),
+}
+
 pub struct TypedReceiver<T>
 where
     T: Send + Sync + 'static,
 {
-    inner: mpsc::Receiver<Box<dyn Any + Send + Sync>>,
+    source: Source,
+    replay: Replay<T>,
     _marker: std::marker::PhantomData<T>,
 }
 
@@ -86,40 +310,213 @@ impl<T> TypedReceiver<T>
 where
     T: Send + Sync + 'static,
 {
+    /// Reads `T` from a channel the caller feeds; it has no queue stats or overflow policy.
+    #[deprecated(note = "subscribe with `TypedMessageBus::sub_type_channel` instead")]
     pub fn new(inner: mpsc::Receiver<Box<dyn Any + Send + Sync>>) -> Self {
+        Self::from_source(Source::Channel(inner), Replay::default())
+    }
+
+    fn from_source(source: Source, replay: Replay<T>) -> Self {
         Self {
-            inner,
+            source,
+            replay,
             _marker: std::marker::PhantomData,
         }
     }
+
+    /// Highest offset of the journaled messages received; resume from the one after it.
+    ///
+    /// Messages published concurrently may arrive out of order, so a lower offset can still
+    /// follow while publishers are racing.
+    pub fn offset(&self) -> Option<u64> {
+        self.replay.last_offset()
+    }
+
     pub fn try_recv(&mut self) -> Result<T, MessageBusError> {
-        match self.inner.try_recv() {
-            Ok(bo) => Self::try_cast(bo),
-            Err(e) => match e {
-                mpsc::error::TryRecvError::Empty => Err(MessageBusError::Empty),
-                mpsc::error::TryRecvError::Disconnected => Err(MessageBusError::ChannelClosed),
-            },
+        loop {
+            if let Some(msg) = self.replay.pop() {
+                return Ok(msg);
+            }
+            let bo = match &mut self.source {
+                Source::Queue(queue) => queue.try_take()?,
+                Source::Channel(inner) => inner.try_recv().map_err(|err| match err {
+                    mpsc::error::TryRecvError::Empty => MessageBusError::Empty,
+                    mpsc::error::TryRecvError::Disconnected => MessageBusError::ChannelClosed,
+                })?,
+            };
+            if let Some(msg) = self.accept(bo)? {
+                return Ok(msg);
+            }
         }
     }
 
     pub async fn recv(&mut self) -> Result<T, MessageBusError> {
-        match self.inner.recv().await {
-            Some(bo) => Self::try_cast(bo),
-            None => Err(MessageBusError::ChannelClosed),
+        loop {
+            if let Some(msg) = self.replay.pop() {
+                return Ok(msg);
+            }
+            let bo = match &mut self.source {
+                Source::Queue(queue) => queue.take().await,
+                Source::Channel(inner) => inner.recv().await,
+            };
+            let Some(bo) = bo else {
+                return Err(MessageBusError::ChannelClosed);
+            };
+            if let Some(msg) = self.accept(bo)? {
+                return Ok(msg);
+            }
         }
     }
 
     pub async fn recv_with_timeout(&mut self, dur: Duration) -> Result<T, MessageBusError> {
-        match timeout(dur, self.inner.recv()).await {
-            Ok(Some(bo)) => Self::try_cast(bo),
-            Ok(None) => Err(MessageBusError::ChannelClosed),
-            Err(_) => Err(MessageBusError::Timeout),
+        timeout(dur, self.recv())
+            .await
+            .unwrap_or(Err(MessageBusError::Timeout))
+    }
+
+    fn accept(&mut self, bo: Boxed) -> Result<Option<T>, MessageBusError> {
+        let bo = match bo.downcast::<T>() {
+            Ok(boxed) => return Ok(Some(*boxed)),
+            Err(bo) => bo,
+        };
+        match bo.downcast::<Stamped<T>>() {
+            Ok(stamped) => Ok(self.replay.accept(stamped.offset, stamped.msg)),
+            Err(_) => Err(MessageBusError::TypeMismatch),
+        }
+    }
+}
+
+impl<T> Drop for TypedReceiver<T>
+where
+    T: Send + Sync + 'static,
+{
+    fn drop(&mut self) {
+        if let Source::Queue(queue) = &self.source {
+            queue.close();
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Job(usize);
+
+    #[tokio::test]
+    async fn test_request_reply() {
+        let bus = TypedMessageBus::new();
+        let wait = Duration::from_secs(1);
+        assert!(matches!(
+            bus.request::<u32, u64>(1, wait).await,
+            Err(MessageBusError::NotFound)
+        ));
+        bus.respond(|n: u32| async move { u64::from(n) * 2 })
+            .unwrap();
+        assert_eq!(bus.request::<u32, u64>(21, wait).await.unwrap(), 42);
+        assert!(matches!(
+            bus.respond(|n: u32| async move { u64::from(n) }),
+            Err(MessageBusError::AlreadyExists)
+        ));
+
+        let mut requests = bus.sub_request_channel::<String, usize>().unwrap();
+        let unanswered = bus.request::<String, usize>("drop".to_string(), wait);
+        let (result, ()) = tokio::join!(unanswered, async {
+            let request = requests.recv().await.unwrap();
+            assert_eq!(request.msg, "drop");
+            drop(request);
+        });
+        assert!(matches!(result, Err(MessageBusError::ChannelClosed)));
+        // Nobody takes this one off the queue.
+        assert!(matches!(
+            bus.request::<String, usize>("slow".to_string(), Duration::from_millis(20))
+                .await,
+            Err(MessageBusError::Timeout)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_overflow_policies_and_stats() {
+        let bus = TypedMessageBus::new();
+        bus.set_overflow_policy::<Job>(OverflowPolicy::Error);
+        let mut jobs = bus.sub_type_channel::<Job>().unwrap();
+        for i in 0..DEFAULT_CHANNEL_SIZE {
+            bus.publish(Job(i)).await.unwrap();
+        }
+        assert!(matches!(
+            bus.publish(Job(99)).await,
+            Err(MessageBusError::Full)
+        ));
+
+        bus.set_overflow_policy::<Job>(OverflowPolicy::DropNewest);
+        bus.publish(Job(99)).await.unwrap();
+        bus.set_overflow_policy::<Job>(OverflowPolicy::DropOldest);
+        for i in DEFAULT_CHANNEL_SIZE..DEFAULT_CHANNEL_SIZE + 3 {
+            bus.try_publish(Job(i)).unwrap();
+        }
+        let stats = bus.stats::<Job>().unwrap();
+        assert_eq!(
+            (
+                stats.depth,
+                stats.high_watermark,
+                stats.dropped,
+                stats.rejected
+            ),
+            (DEFAULT_CHANNEL_SIZE, DEFAULT_CHANNEL_SIZE, 4, 1)
+        );
+        for i in 3..DEFAULT_CHANNEL_SIZE + 3 {
+            assert_eq!(jobs.recv().await.unwrap(), Job(i));
+        }
+        let stats = bus.stats::<Job>().unwrap();
+        assert_eq!(stats.depth, 0);
+        assert_eq!(stats.delivered as usize, DEFAULT_CHANNEL_SIZE);
+
+        bus.set_overflow_policy::<Job>(OverflowPolicy::Block);
+        for i in 0..DEFAULT_CHANNEL_SIZE {
+            bus.publish(Job(i)).await.unwrap();
+        }
+        assert!(matches!(
+            bus.try_publish(Job(0)),
+            Err(MessageBusError::Full)
+        ));
+        let blocked = bus.publish(Job(DEFAULT_CHANNEL_SIZE));
+        let (published, first) = tokio::join!(blocked, async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            jobs.recv().await.unwrap()
+        });
+        published.unwrap();
+        assert_eq!(first, Job(0));
+        assert_eq!(bus.all_stats()[0].type_name, std::any::type_name::<Job>());
+
+        drop(jobs);
+        assert!(matches!(
+            bus.publish(Job(0)).await,
+            Err(MessageBusError::ChannelClosed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_receiver_ends_with_the_bus_and_bare_channels_still_work() {
+        let bus = TypedMessageBus::new();
+        let mut jobs = bus.sub_type_channel::<Job>().unwrap();
+        bus.publish(Job(1)).await.unwrap();
+        drop(bus);
+        assert_eq!(jobs.recv().await.unwrap(), Job(1));
+        assert!(matches!(
+            jobs.recv().await,
+            Err(MessageBusError::ChannelClosed)
+        ));
 
-    fn try_cast(bo: Box<dyn Any + Send + Sync>) -> Result<T, MessageBusError> {
-        bo.downcast::<T>()
-            .map(|boxed| *boxed)
-            .map_err(|_| MessageBusError::TypeMismatch)
+        let (tx, rx) = mpsc::channel::<Boxed>(1);
+        #[allow(deprecated)]
+        let mut bare = TypedReceiver::<Job>::new(rx);
+        tx.send(Box::new(Job(2))).await.unwrap();
+        assert_eq!(bare.try_recv().unwrap(), Job(2));
+        drop(tx);
+        assert!(matches!(
+            bare.recv().await,
+            Err(MessageBusError::ChannelClosed)
+        ));
     }
 }
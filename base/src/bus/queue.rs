@@ -0,0 +1,301 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use exception::typed::common::MessageBusError;
+use tokio::sync::Notify;
+
+pub(crate) type Boxed = Box<dyn Any + Send + Sync>;
+
+/// What publishing does when a type's queue is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// `publish` waits for room; `try_publish` fails with `Full`.
+    #[default]
+    Block,
+    /// The new message is discarded and publishing succeeds.
+    DropNewest,
+    /// The oldest queued message is discarded to make room for the new one.
+    DropOldest,
+    /// Publishing fails with `Full`.
+    Error,
+}
+
+impl OverflowPolicy {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::DropNewest,
+            2 => Self::DropOldest,
+            3 => Self::Error,
+            _ => Self::Block,
+        }
+    }
+
+    fn as_u8(self) -> u8 {
+        match self {
+            Self::Block => 0,
+            Self::DropNewest => 1,
+            Self::DropOldest => 2,
+            Self::Error => 3,
+        }
+    }
+}
+
+/// Counters of one message type's queue.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueueStats {
+    pub type_name: &'static str,
+    pub policy: OverflowPolicy,
+    pub capacity: usize,
+    /// Messages waiting for the receiver.
+    pub depth: usize,
+    pub high_watermark: usize,
+    pub published: u64,
+    pub delivered: u64,
+    /// Messages discarded by `DropNewest` or `DropOldest`.
+    pub dropped: u64,
+    /// Publishes that failed with `Full`.
+    pub rejected: u64,
+}
+
+/// Bounded queue of one message type, drained by its single receiver.
+///
+/// The buffer is a `VecDeque` under a lock, so `DropOldest` pops the front on overflow and the
+/// queue never holds more than `capacity` messages.
+pub(crate) struct TypeQueue {
+    type_name: &'static str,
+    capacity: usize,
+    buf: Mutex<VecDeque<Boxed>>,
+    /// Wakes the receiver once a message is queued or the bus goes away.
+    ready: Notify,
+    /// Wakes `Block` publishers once the receiver frees a slot or goes away.
+    space: Notify,
+    /// Set when the receiver is dropped; publishing then fails.
+    closed: AtomicBool,
+    /// Set when the bus is dropped; the receiver drains what is left and then ends.
+    disconnected: AtomicBool,
+    policy: AtomicU8,
+    high_watermark: AtomicUsize,
+    published: AtomicU64,
+    delivered: AtomicU64,
+    dropped: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl TypeQueue {
+    pub(crate) fn new(type_name: &'static str, capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            type_name,
+            capacity,
+            buf: Mutex::new(VecDeque::with_capacity(capacity)),
+            ready: Notify::new(),
+            space: Notify::new(),
+            closed: AtomicBool::new(false),
+            disconnected: AtomicBool::new(false),
+            policy: AtomicU8::new(policy.as_u8()),
+            high_watermark: AtomicUsize::new(0),
+            published: AtomicU64::new(0),
+            delivered: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn policy(&self) -> OverflowPolicy {
+        OverflowPolicy::from_u8(self.policy.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn set_policy(&self, policy: OverflowPolicy) {
+        self.policy.store(policy.as_u8(), Ordering::Relaxed);
+    }
+
+    pub(crate) async fn send(&self, mut msg: Boxed) -> Result<(), MessageBusError> {
+        loop {
+            if self.policy() != OverflowPolicy::Block {
+                return self.try_send(msg);
+            }
+            let space = self.space.notified();
+            tokio::pin!(space);
+            // Registered before the check so a slot freed in between still wakes us.
+            space.as_mut().enable();
+            match self.offer(msg, OverflowPolicy::Block)? {
+                None => return Ok(()),
+                Some(back) => msg = back,
+            }
+            space.await;
+        }
+    }
+
+    pub(crate) fn try_send(&self, msg: Boxed) -> Result<(), MessageBusError> {
+        match self.offer(msg, self.policy())? {
+            None => Ok(()),
+            Some(_) => {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                Err(MessageBusError::Full)
+            }
+        }
+    }
+
+    /// Queues or drops `msg` as `policy` says; hands it back when `Block` has to wait.
+    fn offer(&self, msg: Boxed, policy: OverflowPolicy) -> Result<Option<Boxed>, MessageBusError> {
+        let mut buf = self.lock();
+        if self.closed.load(Ordering::Acquire) {
+            return Err(MessageBusError::ChannelClosed);
+        }
+        if buf.len() >= self.capacity {
+            match policy {
+                OverflowPolicy::Block => return Ok(Some(msg)),
+                OverflowPolicy::Error => {
+                    self.rejected.fetch_add(1, Ordering::Relaxed);
+                    return Err(MessageBusError::Full);
+                }
+                OverflowPolicy::DropNewest => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return Ok(None);
+                }
+                OverflowPolicy::DropOldest => {
+                    buf.pop_front();
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+        buf.push_back(msg);
+        self.high_watermark.fetch_max(buf.len(), Ordering::Relaxed);
+        drop(buf);
+        self.published.fetch_add(1, Ordering::Relaxed);
+        self.ready.notify_one();
+        Ok(None)
+    }
+
+    /// Next queued message; `Empty` if there is none yet, `ChannelClosed` once the bus is gone
+    /// and the queue is drained.
+    pub(crate) fn try_take(&self) -> Result<Boxed, MessageBusError> {
+        let Some(msg) = self.lock().pop_front() else {
+            return Err(if self.disconnected.load(Ordering::Acquire) {
+                MessageBusError::ChannelClosed
+            } else {
+                MessageBusError::Empty
+            });
+        };
+        self.delivered.fetch_add(1, Ordering::Relaxed);
+        self.space.notify_one();
+        Ok(msg)
+    }
+
+    /// Waits for the next message; `None` once the bus is gone and the queue is drained.
+    pub(crate) async fn take(&self) -> Option<Boxed> {
+        loop {
+            let ready = self.ready.notified();
+            tokio::pin!(ready);
+            ready.as_mut().enable();
+            match self.try_take() {
+                Ok(msg) => return Some(msg),
+                Err(MessageBusError::Empty) => ready.await,
+                Err(_) => return None,
+            }
+        }
+    }
+
+    /// Called when the receiver is dropped: fails publishers, including blocked ones, and
+    /// frees the queued messages.
+    pub(crate) fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.lock().clear();
+        self.space.notify_waiters();
+    }
+
+    /// Called when the bus is dropped: the receiver ends once it has drained the queue.
+    pub(crate) fn disconnect(&self) {
+        self.disconnected.store(true, Ordering::Release);
+        self.ready.notify_one();
+    }
+
+    pub(crate) fn stats(&self) -> QueueStats {
+        QueueStats {
+            type_name: self.type_name,
+            policy: self.policy(),
+            capacity: self.capacity,
+            depth: self.lock().len(),
+            high_watermark: self.high_watermark.load(Ordering::Relaxed),
+            published: self.published.load(Ordering::Relaxed),
+            delivered: self.delivered.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<Boxed>> {
+        self.buf.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn drop_oldest_never_evicts_the_newest_message() {
+        const BURSTS: usize = 5_000;
+        const BURST: usize = 3;
+        let queue = Arc::new(TypeQueue::new("usize", 1, OverflowPolicy::DropOldest));
+        let (seen_tx, mut seen_rx) = tokio::sync::watch::channel(None);
+        let receiver_queue = queue.clone();
+        let receiver = tokio::spawn(async move {
+            let mut received = Vec::new();
+            while let Some(msg) = receiver_queue.take().await {
+                let msg = *msg.downcast::<usize>().unwrap();
+                received.push(msg);
+                seen_tx.send_replace(Some(msg));
+                if msg == BURSTS * BURST - 1 {
+                    break;
+                }
+            }
+            received
+        });
+        for burst in 0..BURSTS {
+            let newest = burst * BURST + BURST - 1;
+            for msg in burst * BURST..=newest {
+                queue.try_send(Box::new(msg)).unwrap();
+            }
+            tokio::time::timeout(
+                Duration::from_secs(5),
+                seen_rx.wait_for(|seen| *seen == Some(newest)),
+            )
+            .await
+            .expect("newest message was evicted")
+            .unwrap();
+        }
+        let received = receiver.await.unwrap();
+        let stats = queue.stats();
+        assert!(received.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(
+            received.len() as u64 + stats.dropped,
+            (BURSTS * BURST) as u64
+        );
+        assert_eq!(stats.depth, 0);
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_holds_at_most_capacity_for_a_stalled_receiver() {
+        let queue = TypeQueue::new("usize", 4, OverflowPolicy::DropOldest);
+        for msg in 0..1000usize {
+            queue.try_send(Box::new(msg)).unwrap();
+        }
+        let stats = queue.stats();
+        assert_eq!((stats.depth, stats.dropped), (4, 996));
+        let kept: Vec<usize> = std::iter::from_fn(|| queue.try_take().ok())
+            .map(|msg| *msg.downcast::<usize>().unwrap())
+            .collect();
+        assert_eq!(kept, [996, 997, 998, 999]);
+
+        queue.disconnect();
+        assert!(matches!(
+            queue.try_take(),
+            Err(MessageBusError::ChannelClosed)
+        ));
+        assert!(queue.take().await.is_none());
+    }
+}
@@ -0,0 +1,172 @@
+use std::sync::Arc;
+
+use exception::typed::common::MessageBusError;
+
+/// A message received through a wildcard subscription, with the topic it was published to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicMessage<T> {
+    pub topic: Arc<str>,
+    pub msg: T,
+}
+
+/// Checks a publish topic: `/`-separated non-empty levels without wildcards.
+pub(crate) fn validate_topic(topic: &str) -> Result<(), MessageBusError> {
+    if topic.is_empty() || topic.split('/').any(|level| level.is_empty()) {
+        return Err(MessageBusError::InvalidTopic(topic.to_string()));
+    }
+    if topic.contains(['+', '#']) {
+        return Err(MessageBusError::InvalidTopic(topic.to_string()));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Level {
+    Exact(String),
+    /// `+`: exactly one level.
+    Single,
+    /// `#`: any number of trailing levels, including none.
+    Rest,
+}
+
+/// Subscription pattern in MQTT style, e.g. `device/+/status` or `tenant/7/#`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicPattern {
+    raw: String,
+    levels: Vec<Level>,
+}
+
+impl TopicPattern {
+    pub fn parse(pattern: &str) -> Result<Self, MessageBusError> {
+        let invalid = || MessageBusError::InvalidTopic(pattern.to_string());
+        if pattern.is_empty() {
+            return Err(invalid());
+        }
+        let parts: Vec<&str> = pattern.split('/').collect();
+        let mut levels = Vec::with_capacity(parts.len());
+        for (index, part) in parts.iter().enumerate() {
+            let level = match *part {
+                "+" => Level::Single,
+                "#" if index == parts.len() - 1 => Level::Rest,
+                part if part.is_empty() || part.contains(['+', '#']) => return Err(invalid()),
+                part => Level::Exact(part.to_string()),
+            };
+            levels.push(level);
+        }
+        Ok(Self {
+            raw: pattern.to_string(),
+            levels,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// Whether the pattern has no wildcard and so names a single topic.
+    pub fn is_exact(&self) -> bool {
+        self.levels
+            .iter()
+            .all(|level| matches!(level, Level::Exact(_)))
+    }
+
+    pub fn matches(&self, topic: &str) -> bool {
+        let mut parts = topic.split('/');
+        for level in &self.levels {
+            match level {
+                Level::Rest => return true,
+                Level::Single => {
+                    if parts.next().is_none() {
+                        return false;
+                    }
+                }
+                Level::Exact(expected) => {
+                    if parts.next() != Some(expected.as_str()) {
+                        return false;
+                    }
+                }
+            }
+        }
+        parts.next().is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::broadcast::TypedMessageBus;
+
+    #[test]
+    fn test_pattern_matching() {
+        let single = TopicPattern::parse("device/+/status").unwrap();
+        assert!(single.matches("device/42/status"));
+        assert!(!single.matches("device/42/status/extra"));
+        assert!(!single.matches("device/status"));
+
+        let rest = TopicPattern::parse("tenant/7/#").unwrap();
+        assert!(rest.matches("tenant/7"));
+        assert!(rest.matches("tenant/7/device/1"));
+        assert!(!rest.matches("tenant/8/device/1"));
+        assert!(TopicPattern::parse("#").unwrap().matches("a/b/c"));
+        assert!(TopicPattern::parse("a/b").unwrap().is_exact());
+
+        for bad in ["", "a/#/b", "a//b", "a/b+", "a/#x"] {
+            assert!(TopicPattern::parse(bad).is_err(), "{bad}");
+        }
+        for bad in ["", "a/+", "a/#", "/a", "a/"] {
+            assert!(validate_topic(bad).is_err(), "{bad}");
+        }
+        assert!(validate_topic("device/42/status").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_bus_routes_topics_and_wildcards() {
+        let bus = TypedMessageBus::new();
+        let mut default = bus.sub_type_channel::<u32>();
+        let mut exact = bus.sub_topic_channel::<u32>("device/42/status").unwrap();
+        let mut status = bus.sub_topic_pattern::<u32>("device/+/status").unwrap();
+        let mut tenant = bus.sub_topic_pattern::<u32>("device/#").unwrap();
+
+        assert_eq!(bus.publish_to("device/42/status", 1u32).unwrap(), 3);
+        assert_eq!(bus.publish_to("device/7/status", 2u32).unwrap(), 2);
+        assert_eq!(bus.publish_to("device/7/config", 3u32).unwrap(), 1);
+        assert_eq!(bus.publish_to("sensor/1", 4u32).unwrap(), 0);
+        assert_eq!(bus.publish_to("device/42/status", "other type").unwrap(), 0);
+        assert!(bus.publish_to("device/+/status", 5u32).is_err());
+
+        assert_eq!(exact.recv().await.unwrap(), 1);
+        assert!(matches!(exact.try_recv(), Err(MessageBusError::Empty)));
+        let seen: Vec<_> = (0..2).map(|_| status.try_recv().unwrap()).collect();
+        assert_eq!(
+            seen,
+            vec![
+                TopicMessage {
+                    topic: Arc::from("device/42/status"),
+                    msg: 1
+                },
+                TopicMessage {
+                    topic: Arc::from("device/7/status"),
+                    msg: 2
+                },
+            ]
+        );
+        let topics: Vec<_> = (0..3).map(|_| tenant.try_recv().unwrap().msg).collect();
+        assert_eq!(topics, vec![1, 2, 3]);
+        assert!(matches!(default.try_recv(), Err(MessageBusError::Empty)));
+
+        assert!(matches!(
+            bus.set_topic_capacity::<u32>("device/42/status", 1),
+            Err(MessageBusError::AlreadyExists)
+        ));
+        assert!(matches!(
+            bus.set_topic_capacity::<u32>("alarm/1", 0),
+            Err(MessageBusError::InvalidCapacity(0))
+        ));
+        bus.set_topic_capacity::<u32>("alarm/1", 1).unwrap();
+        let mut alarm = bus.sub_topic_channel::<u32>("alarm/1").unwrap();
+        bus.publish_to("alarm/1", 1u32).unwrap();
+        bus.publish_to("alarm/1", 2u32).unwrap();
+        assert!(matches!(alarm.recv().await, Err(MessageBusError::Lagged)));
+        assert_eq!(alarm.recv().await.unwrap(), 2);
+    }
+}
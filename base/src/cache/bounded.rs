@@ -0,0 +1,444 @@
+/*
+容量上限与淘汰策略：按条目数或权重函数限制 TtlCache。
+LRU 链表与 TinyLFU 频率草图由一把策略锁保护：写入在锁内完成准入与淘汰，
+读取只 try_lock 记录访问，锁竞争时丢弃这次记录（与 Caffeine 的有损读缓冲同理），不阻塞读路径。
+*/
+
+use ahash::{AHashMap, RandomState};
+use std::hash::Hash;
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+
+//
+// ================= CONFIG =================
+//
+
+const NIL: u32 = u32::MAX;
+const SKETCH_DEPTH: usize = 4;
+const SKETCH_MIN_WIDTH: usize = 64;
+const SKETCH_MAX_WIDTH: usize = 1 << 20;
+const COUNTER_MAX: u8 = 15;
+const RESET_FACTOR: u64 = 10;
+
+//
+// ================= OPTIONS =================
+//
+
+/// Which entry makes room when a bounded cache is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EvictionPolicy {
+    /// Evicts the least recently used entry.
+    #[default]
+    Lru,
+    /// LRU eviction behind a TinyLFU admission filter: a new key is only admitted when it has
+    /// been seen more often recently than the entry it would evict, so one-off keys cannot
+    /// flush a hot working set.
+    TinyLfu,
+}
+
+pub type Weigher<K, V> = Arc<dyn Fn(&K, &V) -> u64 + Send + Sync>;
+
+/// Capacity bound of a [`TtlCache`](crate::cache::ttl::TtlCache).
+#[derive(Clone)]
+pub struct CacheBound<K, V> {
+    max_weight: u64,
+    weigher: Option<Weigher<K, V>>,
+    policy: EvictionPolicy,
+}
+
+impl<K, V> CacheBound<K, V> {
+    /// Bounds the number of entries.
+    pub fn max_entries(max_entries: u64) -> Self {
+        Self {
+            max_weight: max_entries,
+            weigher: None,
+            policy: EvictionPolicy::default(),
+        }
+    }
+
+    /// Bounds the summed weight of all entries; an entry heavier than `max_weight` is never kept.
+    pub fn max_weight<F>(max_weight: u64, weigher: F) -> Self
+    where
+        F: Fn(&K, &V) -> u64 + Send + Sync + 'static,
+    {
+        Self {
+            max_weight,
+            weigher: Some(Arc::new(weigher)),
+            policy: EvictionPolicy::default(),
+        }
+    }
+
+    pub fn with_policy(mut self, policy: EvictionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+//
+// ================= BOUNDED =================
+//
+
+pub(crate) struct Bounded<K, V> {
+    weigher: Option<Weigher<K, V>>,
+    policy: Mutex<Policy<K>>,
+}
+
+impl<K: Hash + Eq + Clone, V> Bounded<K, V> {
+    pub(crate) fn new(bound: CacheBound<K, V>) -> Self {
+        let tiny_lfu = bound.policy == EvictionPolicy::TinyLfu;
+        Self {
+            policy: Mutex::new(Policy::new(bound.max_weight, tiny_lfu)),
+            weigher: bound.weigher,
+        }
+    }
+
+    pub(crate) fn weigh(&self, key: &K, value: &V) -> u64 {
+        self.weigher
+            .as_ref()
+            .map_or(1, |weigher| weigher(key, value))
+    }
+
+    pub(crate) fn lock(&self) -> MutexGuard<'_, Policy<K>> {
+        self.policy
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Records a read unless a writer holds the policy lock.
+    pub(crate) fn record_read(&self, key: &K) {
+        let mut policy = match self.policy.try_lock() {
+            Ok(policy) => policy,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => return,
+        };
+        policy.record(key);
+    }
+}
+
+//
+// ================= POLICY =================
+//
+
+/// Outcome of [`Policy::admit`].
+pub(crate) struct Admission<K> {
+    /// False when TinyLFU or the weight limit turned the candidate away.
+    pub(crate) admitted: bool,
+    pub(crate) victims: Vec<K>,
+}
+
+struct Node<K> {
+    key: K,
+    weight: u64,
+    prev: u32,
+    next: u32,
+}
+
+/// Recency list plus frequency sketch; `head` is the most recently used entry.
+pub(crate) struct Policy<K> {
+    nodes: Vec<Option<Node<K>>>,
+    free: Vec<u32>,
+    index: AHashMap<K, u32>,
+    head: u32,
+    tail: u32,
+    weight: u64,
+    max_weight: u64,
+    sketch: Option<FrequencySketch>,
+    hasher: RandomState,
+}
+
+impl<K: Hash + Eq + Clone> Policy<K> {
+    fn new(max_weight: u64, tiny_lfu: bool) -> Self {
+        Self {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            index: AHashMap::new(),
+            head: NIL,
+            tail: NIL,
+            weight: 0,
+            max_weight,
+            sketch: tiny_lfu.then(|| FrequencySketch::new(max_weight)),
+            hasher: RandomState::new(),
+        }
+    }
+
+    pub(crate) fn weight(&self) -> u64 {
+        self.weight
+    }
+
+    pub(crate) fn record(&mut self, key: &K) {
+        let hash = self.hasher.hash_one(key);
+        if let Some(sketch) = self.sketch.as_mut() {
+            sketch.increment(hash);
+        }
+        if let Some(&idx) = self.index.get(key) {
+            self.move_to_head(idx);
+        }
+    }
+
+    /// Adds or re-weighs `key` and evicts victims until the bound holds again.
+    ///
+    /// Victims are chosen before any is evicted, so a rejected candidate leaves every other
+    /// entry in place.
+    pub(crate) fn admit(&mut self, key: &K, weight: u64) -> Admission<K> {
+        let hash = self.hasher.hash_one(key);
+        if let Some(sketch) = self.sketch.as_mut() {
+            sketch.increment(hash);
+        }
+        let rejected = Admission {
+            admitted: false,
+            victims: Vec::new(),
+        };
+        let is_new = match self.index.get(key).copied() {
+            Some(idx) => {
+                let node = self.node_mut(idx);
+                let previous = std::mem::replace(&mut node.weight, weight);
+                self.weight = self.weight - previous + weight;
+                self.move_to_head(idx);
+                false
+            }
+            None => {
+                if weight > self.max_weight {
+                    return rejected;
+                }
+                self.push_head(key.clone(), weight);
+                true
+            }
+        };
+        let mut excess = self.weight.saturating_sub(self.max_weight);
+        let mut victims = Vec::new();
+        let mut idx = self.tail;
+        while excess > 0 {
+            // Reaching the candidate means only a re-weighed entry heavier than the bound is
+            // left; it is dropped like one TinyLFU turns away.
+            if self.node(idx).key == *key || (is_new && !self.prefers(hash, idx)) {
+                self.remove(key);
+                return rejected;
+            }
+            let node = self.node(idx);
+            excess = excess.saturating_sub(node.weight);
+            victims.push(node.key.clone());
+            idx = node.prev;
+        }
+        for victim in &victims {
+            self.remove(victim);
+        }
+        Admission {
+            admitted: true,
+            victims,
+        }
+    }
+
+    /// TinyLFU admission: the candidate must be strictly more frequent than the victim.
+    fn prefers(&self, candidate_hash: u64, victim: u32) -> bool {
+        let Some(sketch) = self.sketch.as_ref() else {
+            return true;
+        };
+        let victim_hash = self.hasher.hash_one(&self.node(victim).key);
+        sketch.frequency(candidate_hash) > sketch.frequency(victim_hash)
+    }
+
+    pub(crate) fn remove(&mut self, key: &K) {
+        let Some(idx) = self.index.remove(key) else {
+            return;
+        };
+        self.unlink(idx);
+        if let Some(node) = self.nodes[idx as usize].take() {
+            self.weight -= node.weight;
+        }
+        self.free.push(idx);
+    }
+
+    fn push_head(&mut self, key: K, weight: u64) {
+        let node = Node {
+            key: key.clone(),
+            weight,
+            prev: NIL,
+            next: NIL,
+        };
+        let idx = match self.free.pop() {
+            Some(idx) => {
+                self.nodes[idx as usize] = Some(node);
+                idx
+            }
+            None => {
+                self.nodes.push(Some(node));
+                (self.nodes.len() - 1) as u32
+            }
+        };
+        self.index.insert(key, idx);
+        self.weight += weight;
+        self.link_head(idx);
+    }
+
+    fn move_to_head(&mut self, idx: u32) {
+        if self.head != idx {
+            self.unlink(idx);
+            self.link_head(idx);
+        }
+    }
+
+    fn link_head(&mut self, idx: u32) {
+        let head = self.head;
+        {
+            let node = self.node_mut(idx);
+            node.prev = NIL;
+            node.next = head;
+        }
+        if head != NIL {
+            self.node_mut(head).prev = idx;
+        } else {
+            self.tail = idx;
+        }
+        self.head = idx;
+    }
+
+    fn unlink(&mut self, idx: u32) {
+        let (prev, next) = {
+            let node = self.node(idx);
+            (node.prev, node.next)
+        };
+        if prev != NIL {
+            self.node_mut(prev).next = next;
+        } else {
+            self.head = next;
+        }
+        if next != NIL {
+            self.node_mut(next).prev = prev;
+        } else {
+            self.tail = prev;
+        }
+    }
+
+    fn node(&self, idx: u32) -> &Node<K> {
+        self.nodes[idx as usize].as_ref().expect("linked node")
+    }
+
+    fn node_mut(&mut self, idx: u32) -> &mut Node<K> {
+        self.nodes[idx as usize].as_mut().expect("linked node")
+    }
+}
+
+//
+// ================= SKETCH =================
+//
+
+/// Count-min sketch of saturating 4-bit counters, halved every `10 * width` increments so that
+/// frequencies reflect recent traffic.
+struct FrequencySketch {
+    table: Vec<u8>,
+    mask: usize,
+    additions: u64,
+    reset_at: u64,
+}
+
+impl FrequencySketch {
+    fn new(max_weight: u64) -> Self {
+        let width = usize::try_from(max_weight)
+            .unwrap_or(SKETCH_MAX_WIDTH)
+            .clamp(SKETCH_MIN_WIDTH, SKETCH_MAX_WIDTH)
+            .next_power_of_two();
+        Self {
+            table: vec![0; width * SKETCH_DEPTH],
+            mask: width - 1,
+            additions: 0,
+            reset_at: width as u64 * RESET_FACTOR,
+        }
+    }
+
+    fn slots(&self, hash: u64) -> [usize; SKETCH_DEPTH] {
+        let (low, high) = (hash as u32 as usize, (hash >> 32) as usize);
+        let width = self.mask + 1;
+        std::array::from_fn(|row| {
+            row * width + (low.wrapping_add(row.wrapping_mul(high)) & self.mask)
+        })
+    }
+
+    fn increment(&mut self, hash: u64) {
+        let mut added = false;
+        for slot in self.slots(hash) {
+            if self.table[slot] < COUNTER_MAX {
+                self.table[slot] += 1;
+                added = true;
+            }
+        }
+        if added {
+            self.additions += 1;
+            if self.additions >= self.reset_at {
+                self.table.iter_mut().for_each(|counter| *counter >>= 1);
+                self.additions /= 2;
+            }
+        }
+    }
+
+    fn frequency(&self, hash: u64) -> u8 {
+        self.slots(hash)
+            .into_iter()
+            .map(|slot| self.table[slot])
+            .min()
+            .unwrap_or(0)
+    }
+}
+
+// ================= TEST =================
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_evicts_least_recent_and_rejects_oversized() {
+        let mut policy = Policy::new(3, false);
+        for key in ["a", "b", "c"] {
+            assert!(policy.admit(&key, 1).victims.is_empty());
+        }
+        policy.record(&"a");
+        assert_eq!(policy.admit(&"d", 1).victims, vec!["b"]);
+        assert_eq!(policy.admit(&"e", 2).victims, vec!["c", "a"]);
+        assert_eq!((policy.index.len(), policy.weight()), (2, 3));
+        assert!(!policy.admit(&"huge", 4).admitted);
+
+        policy.remove(&"d");
+        assert_eq!((policy.index.len(), policy.weight()), (1, 2));
+    }
+
+    #[test]
+    fn test_tiny_lfu_keeps_hot_entries() {
+        let mut policy = Policy::new(2, true);
+        policy.admit(&"hot", 1);
+        policy.admit(&"warm", 1);
+        for _ in 0..5 {
+            policy.record(&"hot");
+            policy.record(&"warm");
+        }
+        let cold = policy.admit(&"cold", 1);
+        assert!(!cold.admitted && cold.victims.is_empty());
+        assert_eq!(policy.index.len(), 2);
+
+        for _ in 0..8 {
+            policy.record(&"rising");
+        }
+        let rising = policy.admit(&"rising", 1);
+        assert!(rising.admitted);
+        assert_eq!(rising.victims, vec!["hot"]);
+    }
+
+    #[test]
+    fn test_rejected_candidate_evicts_nothing() {
+        let mut policy = Policy::new(3, true);
+        for key in ["a", "b", "c"] {
+            policy.admit(&key, 1);
+        }
+        for _ in 0..5 {
+            policy.record(&"b");
+        }
+        policy.record(&"c");
+        policy.record(&"x");
+        policy.record(&"x");
+        // "x" outranks "a" but not "b", and needs both gone.
+        let x = policy.admit(&"x", 2);
+        assert!(!x.admitted && x.victims.is_empty());
+        assert_eq!((policy.index.len(), policy.weight()), (3, 3));
+
+        let a = policy.admit(&"a", 4);
+        assert!(!a.admitted && a.victims.is_empty());
+        assert_eq!((policy.index.len(), policy.weight()), (2, 2));
+    }
+}
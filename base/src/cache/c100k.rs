@@ -6,6 +6,9 @@ L1 = 512 tick
 L2 = 128 * 512 = 65536 tick
 L3 = 128 * 65536 = 8,388,608 tick
 8,388,608 * 200ms ≈ 1,677,721 秒 ≈ 19.4 天
+以上为默认几何；tick、分片数、各层槽数、通道容量与批大小可经 CacheBuilder 调整。
+分片槽位在发送 insert 命令前同步预留：分片已满时 insert 直接返回错误，而不是在分片内静默丢弃。
+超出最高层范围的 TTL 暂存于溢出层，最高层每转一圈重新调度一次，不会被截断或提前到期。
 2核:
 稳定 250k~400k
 安全 150k~300k
@@ -20,24 +23,34 @@ L3 = 128 * 65536 = 8,388,608 tick
 极限 5M
 */
 
+use crate::cache::snapshot::{save_on_cancel, CacheSnapshot, SnapshotEntry};
+use crate::utils::rt::GlobalRuntime;
 use ahash::{AHashMap, RandomState};
-use exception::{GlobalResult, GlobalResultExt};
+use exception::{GlobalError, GlobalResult, GlobalResultExt};
 use log::error;
 use once_cell::sync::Lazy;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use smallvec::SmallVec;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
 use std::{hash::Hash, mem::MaybeUninit, time::Duration};
 use tokio::sync::mpsc::error::TryRecvError;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
-use tokio::sync::Mutex;
+use tokio::sync::{oneshot, Mutex};
+use tokio::task::JoinHandle;
 use tokio::time::{interval_at, Instant};
 //
 // ================= CONFIG =================
 //
 
 const SHARDS: usize = 4;
-const WHEEL_L1: usize = 512;
-const WHEEL_L2: usize = 128;
-const WHEEL_L3: usize = 128;
+const WHEEL_LEVELS: [usize; 3] = [512, 128, 128];
 const MAX_CATCHUP: u64 = 1024;
 
 const TICK_MS: u64 = 200;
@@ -46,6 +59,185 @@ const BATCH: usize = 64;
 const COMPACT_THRESHOLD: usize = 1024;
 const DEFAULT_CAPACITY_PER_SHARD: usize = 128_000;
 
+/// 各分片共用的时间轮几何。
+#[derive(Clone, Debug)]
+struct WheelConfig {
+    tick_ms: u64,
+    /// 每层槽数，第 0 层最细。
+    levels: Vec<usize>,
+    /// 每层一个槽覆盖的 tick 数。
+    spans: Vec<u64>,
+    /// 所有层合计覆盖的 tick 数，超出者进入溢出层。
+    horizon: u64,
+    channel: usize,
+    batch: usize,
+}
+
+impl WheelConfig {
+    fn new(tick_ms: u64, levels: Vec<usize>, channel: usize, batch: usize) -> Option<Self> {
+        let mut spans = Vec::with_capacity(levels.len());
+        let mut span = 1u64;
+        for &slots in &levels {
+            spans.push(span);
+            span = span.checked_mul(slots as u64)?;
+        }
+        Some(Self {
+            tick_ms,
+            levels,
+            spans,
+            horizon: span,
+            channel,
+            batch,
+        })
+    }
+
+    fn tick(&self) -> Duration {
+        Duration::from_millis(self.tick_ms)
+    }
+
+    fn ttl_to_tick(&self, ttl: Duration) -> u64 {
+        let ms = u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX);
+        if ms == 0 {
+            1
+        } else {
+            ms.div_ceil(self.tick_ms)
+        }
+    }
+
+    fn ticks_to_ttl(&self, ticks: u64) -> Duration {
+        Duration::from_millis(ticks.saturating_mul(self.tick_ms))
+    }
+}
+
+impl Default for WheelConfig {
+    fn default() -> Self {
+        Self::new(TICK_MS, WHEEL_LEVELS.to_vec(), CHANNEL, BATCH).expect("default wheel geometry")
+    }
+}
+
+//
+// ================= BUILDER =================
+//
+
+/// Geometry of a [`Cache`] keyed by `K`; defaults match [`Cache::default`].
+///
+/// The wheel covers the product of all level sizes in ticks. Longer TTLs wait in an overflow
+/// level that is re-scheduled once per rotation of the top level, so they still expire on time.
+#[derive(Clone)]
+pub struct CacheBuilder<K> {
+    tick: Duration,
+    shards: usize,
+    capacity_per_shard: usize,
+    wheel_levels: Vec<usize>,
+    channel_capacity: usize,
+    batch_size: usize,
+    _key: PhantomData<fn() -> K>,
+}
+
+impl<K> Default for CacheBuilder<K> {
+    fn default() -> Self {
+        Self {
+            tick: Duration::from_millis(TICK_MS),
+            shards: SHARDS,
+            capacity_per_shard: DEFAULT_CAPACITY_PER_SHARD,
+            wheel_levels: WHEEL_LEVELS.to_vec(),
+            channel_capacity: CHANNEL,
+            batch_size: BATCH,
+            _key: PhantomData,
+        }
+    }
+}
+
+impl<K> fmt::Debug for CacheBuilder<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CacheBuilder")
+            .field("tick", &self.tick)
+            .field("shards", &self.shards)
+            .field("capacity_per_shard", &self.capacity_per_shard)
+            .field("wheel_levels", &self.wheel_levels)
+            .field("channel_capacity", &self.channel_capacity)
+            .field("batch_size", &self.batch_size)
+            .finish()
+    }
+}
+
+impl<K: CacheKey> CacheBuilder<K> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Timer resolution, at least 1ms and rounded down to whole milliseconds; TTLs round up to
+    /// whole ticks.
+    pub fn tick(mut self, tick: Duration) -> Self {
+        self.tick = tick;
+        self
+    }
+
+    /// Number of shard actors; must be a power of two.
+    pub fn shards(mut self, shards: usize) -> Self {
+        self.shards = shards;
+        self
+    }
+
+    pub fn capacity_per_shard(mut self, capacity: usize) -> Self {
+        self.capacity_per_shard = capacity;
+        self
+    }
+
+    /// Slot count of each wheel level, finest first; each level needs at least two slots.
+    pub fn wheel_levels(mut self, levels: impl Into<Vec<usize>>) -> Self {
+        self.wheel_levels = levels.into();
+        self
+    }
+
+    /// Queued commands per shard before `insert`/`refresh`/`delete` start dropping.
+    pub fn channel_capacity(mut self, capacity: usize) -> Self {
+        self.channel_capacity = capacity;
+        self
+    }
+
+    /// Expired keys per event batch.
+    pub fn batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size;
+        self
+    }
+
+    /// Validates the geometry and starts the shard actors; must be called inside a tokio runtime.
+    pub fn build(self) -> GlobalResult<Cache<K>> {
+        let tick_ms = self.tick.as_millis() as u64;
+        let invalid = |reason: &str| {
+            GlobalError::new_sys_error(reason, |msg| {
+                error!("invalid cache geometry: {msg}: {self:?}")
+            })
+        };
+        if tick_ms == 0 {
+            return Err(invalid("tick must be at least 1ms"));
+        }
+        if !self.shards.is_power_of_two() {
+            return Err(invalid("shard count must be a power of two"));
+        }
+        if self.capacity_per_shard == 0 || self.capacity_per_shard >= u32::MAX as usize {
+            return Err(invalid("capacity per shard must be within 1..u32::MAX"));
+        }
+        if self.wheel_levels.is_empty() || self.wheel_levels.iter().any(|&slots| slots < 2) {
+            return Err(invalid(
+                "wheel needs at least one level of two or more slots",
+            ));
+        }
+        if self.channel_capacity == 0 || self.batch_size == 0 {
+            return Err(invalid("channel capacity and batch size must be positive"));
+        }
+        let config = WheelConfig::new(
+            tick_ms,
+            self.wheel_levels.clone(),
+            self.channel_capacity,
+            self.batch_size,
+        )
+        .ok_or_else(|| invalid("wheel span overflows u64 ticks"))?;
+        Ok(Cache::start(config, self.capacity_per_shard, self.shards))
+    }
+}
+
 //
 // ================= TRAIT =================
 //
@@ -75,14 +267,111 @@ pub struct CacheEvent<K> {
     pub version: u32,
 }
 
+//
+// ================= STATS =================
+//
+
+/// 单个分片的计数快照；计数自分片启动起累计。
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShardStats {
+    pub live_entries: usize,
+    pub free_slots: usize,
+    /// 各层时间轮中挂着的槽位引用数，与 `CacheBuilder::wheel_levels` 逐层对应（第 0 层最细），
+    /// 含已失效（被刷新/删除）尚未清理的引用。
+    pub wheel_levels: Vec<usize>,
+    /// 超出时间轮范围、暂存于溢出层的引用数。
+    pub overflow: usize,
+    pub inserts: u64,
+    /// 容量已满而丢弃的插入。
+    pub insert_full: u64,
+    pub refreshes: u64,
+    pub refresh_misses: u64,
+    pub deletes: u64,
+    pub delete_misses: u64,
+    pub contains_hits: u64,
+    pub contains_misses: u64,
+    pub expirations: u64,
+    /// 落后超过 `MAX_CATCHUP` 个 tick、本轮只追赶了一部分的次数。
+    pub catchup_limited: u64,
+    /// 分片通道已满或已关闭，`try_send` 失败而丢弃的命令。
+    pub dropped_commands: u64,
+}
+
+impl ShardStats {
+    /// 按 key 操作（contains/refresh/delete）命中的比例；没有操作时为 0。
+    pub fn hit_ratio(&self) -> f64 {
+        let hits = self.contains_hits + self.refreshes + self.deletes;
+        let misses = self.contains_misses + self.refresh_misses + self.delete_misses;
+        if hits + misses == 0 {
+            0.0
+        } else {
+            hits as f64 / (hits + misses) as f64
+        }
+    }
+
+    fn merge(&mut self, other: &ShardStats) {
+        self.live_entries += other.live_entries;
+        self.free_slots += other.free_slots;
+        if self.wheel_levels.len() < other.wheel_levels.len() {
+            self.wheel_levels.resize(other.wheel_levels.len(), 0);
+        }
+        for (total, pending) in self.wheel_levels.iter_mut().zip(&other.wheel_levels) {
+            *total += pending;
+        }
+        self.overflow += other.overflow;
+        self.inserts += other.inserts;
+        self.insert_full += other.insert_full;
+        self.refreshes += other.refreshes;
+        self.refresh_misses += other.refresh_misses;
+        self.deletes += other.deletes;
+        self.delete_misses += other.delete_misses;
+        self.contains_hits += other.contains_hits;
+        self.contains_misses += other.contains_misses;
+        self.expirations += other.expirations;
+        self.catchup_limited += other.catchup_limited;
+        self.dropped_commands += other.dropped_commands;
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub shards: Vec<ShardStats>,
+    pub total: ShardStats,
+}
+
 //
 // ================= COMMAND =================
 //
 
 enum Command<K> {
-    Insert { key: K, ttl: u64 },
-    Refresh { key: K },
-    Delete { key: K },
+    Insert {
+        key: K,
+        ttl: u64,
+    },
+    Restore {
+        key: K,
+        ttl: u64,
+        remaining: u64,
+    },
+    Refresh {
+        key: K,
+    },
+    Delete {
+        key: K,
+    },
+    Contains {
+        key: K,
+        reply: oneshot::Sender<bool>,
+    },
+    Len {
+        reply: oneshot::Sender<usize>,
+    },
+    Stats {
+        reply: oneshot::Sender<ShardStats>,
+    },
+    Snapshot {
+        reply: oneshot::Sender<Vec<SnapshotEntry<K, ()>>>,
+    },
 }
 
 //
@@ -113,12 +402,12 @@ impl<K> Entry<K> {
         }
     }
 
-    fn write(&mut self, key: K, hash: u64, ttl_tick: u64, now_tick: u64) {
+    fn write(&mut self, key: K, hash: u64, ttl_tick: u64, expire_tick: u64) {
         self.key.write(key);
         self.hash = hash;
         self.version = 1;
         self.ttl_tick = ttl_tick;
-        self.expire_tick = now_tick + ttl_tick;
+        self.expire_tick = expire_tick;
         self.in_use = true;
     }
 
@@ -154,24 +443,29 @@ struct Shard<K: CacheKey> {
 
     index_map: AHashMap<u64, SmallVec<[u32; 2]>>,
 
-    wheel_l1: Vec<Vec<(u32, u32)>>,
-    wheel_l2: Vec<Vec<(u32, u32)>>,
-    wheel_l3: Vec<Vec<(u32, u32)>>,
+    config: WheelConfig,
+    wheel: Vec<Vec<Vec<(u32, u32)>>>,
+    overflow: Vec<(u32, u32)>,
 
     tick: u64,
     start: Instant,
 
     cmd_rx: Receiver<Command<K>>,
     event_tx: Sender<Vec<CacheEvent<K>>>,
+    /// 已占用及已预留的槽位数，与 `Cache` 前端共享；槽位释放时递减。
+    occupied: Arc<AtomicUsize>,
 
     batch: Vec<CacheEvent<K>>,
+    stats: ShardStats,
 }
 
 impl<K: CacheKey> Shard<K> {
     fn new(
+        config: WheelConfig,
         capacity: usize,
         cmd_rx: Receiver<Command<K>>,
         event_tx: Sender<Vec<CacheEvent<K>>>,
+        occupied: Arc<AtomicUsize>,
     ) -> Self {
         let mut entries = Vec::with_capacity(capacity);
 
@@ -184,16 +478,22 @@ impl<K: CacheKey> Shard<K> {
             free_head: 0,
             index_map: AHashMap::with_capacity(capacity),
 
-            wheel_l1: vec![Vec::new(); WHEEL_L1],
-            wheel_l2: vec![Vec::new(); WHEEL_L2],
-            wheel_l3: vec![Vec::new(); WHEEL_L3],
+            wheel: config
+                .levels
+                .iter()
+                .map(|&slots| vec![Vec::new(); slots])
+                .collect(),
+            overflow: Vec::new(),
 
             tick: 0,
             start: Instant::now(),
 
             cmd_rx,
             event_tx,
-            batch: Vec::with_capacity(BATCH),
+            occupied,
+            batch: Vec::with_capacity(config.batch),
+            config,
+            stats: ShardStats::default(),
         }
     }
 
@@ -214,6 +514,7 @@ impl<K: CacheKey> Shard<K> {
         e.in_use = false;
         e.next_free = self.free_head;
         self.free_head = idx;
+        self.occupied.fetch_sub(1, Ordering::AcqRel);
     }
 
     fn insert_index(&mut self, hash: u64, idx: u32) {
@@ -250,37 +551,36 @@ impl<K: CacheKey> Shard<K> {
         let e = &self.entries[idx as usize];
         if e.expire_tick <= self.tick {
             // 已过期，放入下一个槽位，保证尽快过期
-            let next_slot = (self.tick as usize + 1) % WHEEL_L1;
-            self.wheel_l1[next_slot].push((idx, e.version));
+            let next_slot = (self.tick as usize + 1) % self.config.levels[0];
+            self.wheel[0][next_slot].push((idx, e.version));
             return;
         }
         let diff = e.expire_tick - self.tick;
-        if diff < WHEEL_L1 as u64 {
-            self.wheel_l1[e.expire_tick as usize % WHEEL_L1].push((idx, e.version));
-        } else if diff < (WHEEL_L1 * WHEEL_L2) as u64 {
-            self.wheel_l2[(e.expire_tick / WHEEL_L1 as u64) as usize % WHEEL_L2]
-                .push((idx, e.version));
-        } else {
-            self.wheel_l3
-                [(e.expire_tick / (WHEEL_L1 as u64 * WHEEL_L2 as u64)) as usize % WHEEL_L3]
-                .push((idx, e.version));
+        for (level, &span) in self.config.spans.iter().enumerate() {
+            let slots = self.config.levels[level];
+            if diff < span.saturating_mul(slots as u64) {
+                let slot = (e.expire_tick / span) as usize % slots;
+                self.wheel[level][slot].push((idx, e.version));
+                return;
+            }
         }
+        self.overflow.push((idx, e.version));
     }
 
-    fn cascade_l2(&mut self) {
-        let slot = (self.tick / WHEEL_L1 as u64) as usize % WHEEL_L2;
-        let bucket = std::mem::take(&mut self.wheel_l2[slot]);
-        for (idx, ver) in bucket {
-            let e = &self.entries[idx as usize];
-            if e.in_use && e.version == ver {
-                self.schedule(idx);
-            }
-        }
+    /// 把第 `level` 层当前槽中的条目按剩余时间重新挂到更低层。
+    fn cascade(&mut self, level: usize) {
+        let slot = (self.tick / self.config.spans[level]) as usize % self.config.levels[level];
+        let bucket = std::mem::take(&mut self.wheel[level][slot]);
+        self.reschedule(bucket);
+    }
+
+    /// 最高层转满一圈时，溢出层中进入范围的条目挂回时间轮，其余仍留在溢出层。
+    fn drain_overflow(&mut self) {
+        let parked = std::mem::take(&mut self.overflow);
+        self.reschedule(parked);
     }
 
-    fn cascade_l3(&mut self) {
-        let slot = (self.tick / (WHEEL_L1 as u64 * WHEEL_L2 as u64)) as usize % WHEEL_L3;
-        let bucket = std::mem::take(&mut self.wheel_l3[slot]);
+    fn reschedule(&mut self, bucket: Vec<(u32, u32)>) {
         for (idx, ver) in bucket {
             let e = &self.entries[idx as usize];
             if e.in_use && e.version == ver {
@@ -300,6 +600,7 @@ impl<K: CacheKey> Shard<K> {
                 continue;
             }
             // 已过期
+            self.stats.expirations += 1;
             let hash = e.hash;
             let version = e.version;
             let key = unsafe { self.entries[idx as usize].take_key() };
@@ -307,8 +608,9 @@ impl<K: CacheKey> Shard<K> {
             let e = &mut self.entries[idx as usize];
             e.next_free = self.free_head;
             self.free_head = idx;
+            self.occupied.fetch_sub(1, Ordering::AcqRel);
             self.batch.push(CacheEvent { key, hash, version });
-            if self.batch.len() >= BATCH {
+            if self.batch.len() >= self.config.batch {
                 let _ = self
                     .event_tx
                     .try_send(std::mem::take(&mut self.batch))
@@ -319,22 +621,27 @@ impl<K: CacheKey> Shard<K> {
 
     fn on_tick(&mut self) {
         let now = Instant::now();
-        let expected_tick = (now - self.start).as_millis() as u64 / TICK_MS;
+        let expected_tick = (now - self.start).as_millis() as u64 / self.config.tick_ms;
 
         let target = expected_tick.min(self.tick + MAX_CATCHUP);
+        if target < expected_tick {
+            self.stats.catchup_limited += 1;
+        }
 
         while self.tick < target {
             self.tick += 1;
 
-            if self.tick.is_multiple_of(WHEEL_L1 as u64) {
-                self.cascade_l2();
-                if (self.tick / WHEEL_L1 as u64).is_multiple_of(WHEEL_L2 as u64) {
-                    self.cascade_l3();
+            if self.tick.is_multiple_of(self.config.horizon) {
+                self.drain_overflow();
+            }
+            for level in (1..self.config.levels.len()).rev() {
+                if self.tick.is_multiple_of(self.config.spans[level]) {
+                    self.cascade(level);
                 }
             }
 
-            let slot = self.tick as usize % WHEEL_L1;
-            let mut bucket = std::mem::take(&mut self.wheel_l1[slot]);
+            let slot = self.tick as usize % self.config.levels[0];
+            let mut bucket = std::mem::take(&mut self.wheel[0][slot]);
 
             if bucket.len() > COMPACT_THRESHOLD {
                 bucket.retain(|(idx, ver)| {
@@ -356,13 +663,70 @@ impl<K: CacheKey> Shard<K> {
 
     fn handle(&mut self, cmd: Command<K>) {
         match cmd {
-            Command::Insert { key, ttl } => self.insert(key, ttl),
+            Command::Insert { key, ttl } => self.insert(key, ttl, ttl),
+            Command::Restore {
+                key,
+                ttl,
+                remaining,
+            } => self.insert(key, ttl, remaining),
             Command::Refresh { key } => self.refresh(key),
             Command::Delete { key } => self.delete(key),
+            Command::Contains { key, reply } => {
+                let found = self.find_entry(&key, hash(&key)).is_some();
+                if found {
+                    self.stats.contains_hits += 1;
+                } else {
+                    self.stats.contains_misses += 1;
+                }
+                let _ = reply.send(found);
+            }
+            Command::Len { reply } => {
+                let _ = reply.send(self.live_entries());
+            }
+            Command::Stats { reply } => {
+                let _ = reply.send(self.collect_stats());
+            }
+            Command::Snapshot { reply } => {
+                let _ = reply.send(self.live_keys());
+            }
+        }
+    }
+
+    fn live_entries(&self) -> usize {
+        self.index_map.values().map(|list| list.len()).sum()
+    }
+
+    fn live_keys(&self) -> Vec<SnapshotEntry<K, ()>> {
+        let ticks = |ticks: u64| self.config.ticks_to_ttl(ticks);
+        self.entries
+            .iter()
+            .filter(|e| e.in_use && e.expire_tick > self.tick)
+            .map(|e| SnapshotEntry {
+                // SAFETY: in_use 的条目 key 已初始化
+                key: unsafe { e.key() }.clone(),
+                value: (),
+                ttl: ticks(e.ttl_tick),
+                remaining: ticks(e.expire_tick - self.tick),
+            })
+            .collect()
+    }
+
+    fn collect_stats(&self) -> ShardStats {
+        let live_entries = self.live_entries();
+        ShardStats {
+            live_entries,
+            free_slots: self.entries.len() - live_entries,
+            wheel_levels: self
+                .wheel
+                .iter()
+                .map(|level| level.iter().map(Vec::len).sum())
+                .collect(),
+            overflow: self.overflow.len(),
+            ..self.stats.clone()
         }
     }
 
-    fn insert(&mut self, key: K, ttl_tick: u64) {
+    fn insert(&mut self, key: K, ttl_tick: u64, remaining_tick: u64) {
         let h = hash(&key);
 
         if let Some(old_idx) = self.find_entry(&key, h) {
@@ -372,11 +736,15 @@ impl<K: CacheKey> Shard<K> {
 
         if let Some(idx) = self.alloc() {
             let e = &mut self.entries[idx as usize];
-            e.write(key, h, ttl_tick, self.tick);
+            e.write(key, h, ttl_tick, self.tick.saturating_add(remaining_tick));
 
             self.insert_index(h, idx);
             self.schedule(idx);
+            self.stats.inserts += 1;
         } else {
+            // 前端预留保证有空槽；走到这里说明计数失准，归还预留以免永久占位。
+            self.occupied.fetch_sub(1, Ordering::AcqRel);
+            self.stats.insert_full += 1;
             error!("Cache capacity full");
         }
     }
@@ -386,8 +754,11 @@ impl<K: CacheKey> Shard<K> {
         if let Some(idx) = self.find_entry(&key, h) {
             let e = &mut self.entries[idx as usize];
             e.version += 1;
-            e.expire_tick = self.tick + e.ttl_tick;
+            e.expire_tick = self.tick.saturating_add(e.ttl_tick);
             self.schedule(idx);
+            self.stats.refreshes += 1;
+        } else {
+            self.stats.refresh_misses += 1;
         }
     }
 
@@ -396,14 +767,14 @@ impl<K: CacheKey> Shard<K> {
         if let Some(idx) = self.find_entry(&key, h) {
             self.remove_index(h, idx);
             self.free(idx);
+            self.stats.deletes += 1;
+        } else {
+            self.stats.delete_misses += 1;
         }
     }
 
     async fn run(mut self) {
-        let mut ticker = interval_at(
-            Instant::now() + Duration::from_millis(TICK_MS),
-            Duration::from_millis(TICK_MS),
-        );
+        let mut ticker = interval_at(Instant::now() + self.config.tick(), self.config.tick());
         loop {
             loop {
                 match self.cmd_rx.try_recv() {
@@ -428,7 +799,12 @@ impl<K: CacheKey> Shard<K> {
 //
 
 pub struct Cache<K: CacheKey> {
+    config: WheelConfig,
     shards: Vec<Sender<Command<K>>>,
+    dropped: Vec<AtomicU64>,
+    capacity_per_shard: usize,
+    occupied: Vec<Arc<AtomicUsize>>,
+    insert_full: Vec<AtomicU64>,
     event_rx: Mutex<Receiver<Vec<CacheEvent<K>>>>,
 }
 impl<K: CacheKey> Default for Cache<K> {
@@ -437,54 +813,192 @@ impl<K: CacheKey> Default for Cache<K> {
     }
 }
 impl<K: CacheKey> Cache<K> {
+    pub fn builder() -> CacheBuilder<K> {
+        CacheBuilder::new()
+    }
+
+    /// 默认几何；`shard_len` 向上取整为 2 的幂。
     pub fn with_capacity(capacity_per_shard: usize, shard_len: usize) -> Self {
-        let (event_tx, event_rx) = channel(CHANNEL);
+        Self::start(
+            WheelConfig::default(),
+            capacity_per_shard,
+            shard_len.max(1).next_power_of_two(),
+        )
+    }
+
+    fn start(config: WheelConfig, capacity_per_shard: usize, shard_len: usize) -> Self {
+        let (event_tx, event_rx) = channel(config.channel);
 
         let mut shards = Vec::new();
+        let mut occupied = Vec::new();
 
         for _ in 0..shard_len {
-            let (tx, rx) = channel(CHANNEL);
-            let shard = Shard::new(capacity_per_shard, rx, event_tx.clone());
+            let (tx, rx) = channel(config.channel);
+            let used = Arc::new(AtomicUsize::new(0));
+            let shard = Shard::new(
+                config.clone(),
+                capacity_per_shard,
+                rx,
+                event_tx.clone(),
+                used.clone(),
+            );
             tokio::spawn(shard.run());
             shards.push(tx);
+            occupied.push(used);
         }
 
         Self {
+            config,
+            dropped: shards.iter().map(|_| AtomicU64::new(0)).collect(),
+            insert_full: shards.iter().map(|_| AtomicU64::new(0)).collect(),
+            capacity_per_shard,
+            occupied,
             shards,
             event_rx: Mutex::new(event_rx),
         }
     }
 
-    fn shard(&self, hash: u64) -> &Sender<Command<K>> {
-        &self.shards[(hash as usize) & (self.shards.len() - 1)]
+    fn shard_index(&self, hash: u64) -> usize {
+        (hash as usize) & (self.shards.len() - 1)
     }
 
-    fn ttl_to_tick(ttl: Duration) -> u64 {
-        let ms = ttl.as_millis() as u64;
-        if ms == 0 {
-            1
-        } else {
-            ms.div_ceil(TICK_MS)
+    fn send(&self, key_hash: u64, cmd: Command<K>) -> GlobalResult<()> {
+        let index = self.shard_index(key_hash);
+        self.shards[index].try_send(cmd).hand_log(|msg| {
+            self.dropped[index].fetch_add(1, Ordering::Relaxed);
+            error!("{msg}")
+        })
+    }
+
+    /// 为新条目预留分片槽位；分片已满时返回错误，调用方不应再发送 insert。
+    fn reserve(&self, index: usize) -> GlobalResult<()> {
+        let reserved =
+            self.occupied[index].fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                (used < self.capacity_per_shard).then_some(used + 1)
+            });
+        if reserved.is_err() {
+            self.insert_full[index].fetch_add(1, Ordering::Relaxed);
+            return Err(GlobalError::new_sys_error("Cache capacity full", |msg| {
+                error!("{msg}: shard={index}")
+            }));
         }
+        Ok(())
     }
+
+    fn release(&self, index: usize) {
+        self.occupied[index].fetch_sub(1, Ordering::AcqRel);
+    }
+
+    /// 查询命令排在该分片已提交的命令之后，在分片下一个 tick 前处理，最长等待约一个 tick。
+    async fn query<T>(
+        &self,
+        index: usize,
+        cmd: impl FnOnce(oneshot::Sender<T>) -> Command<K>,
+    ) -> GlobalResult<T> {
+        let (reply, rx) = oneshot::channel();
+        self.shards[index]
+            .send(cmd(reply))
+            .await
+            .hand_log(|msg| error!("{msg}"))?;
+        rx.await.hand_log(|msg| error!("{msg}"))
+    }
+
+    /// 分片已满或命令通道已满时返回错误，key 不会进入时间轮。
+    ///
+    /// 已在轮中的 key 也要预留槽位（分片处理时归还旧槽），所以分片满时覆盖同样失败。
     pub fn insert(&self, key: K, ttl: Duration) -> GlobalResult<()> {
         let h = hash(&key);
-        let ttl_tick = Self::ttl_to_tick(ttl);
-        self.shard(h)
-            .try_send(Command::Insert { key, ttl: ttl_tick })
-            .hand_log(|msg| error!("{msg}"))
+        let index = self.shard_index(h);
+        self.reserve(index)?;
+        let ttl_tick = self.config.ttl_to_tick(ttl);
+        self.send(h, Command::Insert { key, ttl: ttl_tick })
+            .inspect_err(|_| self.release(index))
     }
     pub fn refresh(&self, key: K) -> GlobalResult<()> {
         let h = hash(&key);
-        self.shard(h)
-            .try_send(Command::Refresh { key })
-            .hand_log(|msg| error!("{msg}"))
+        self.send(h, Command::Refresh { key })
     }
     pub fn delete(&self, key: K) -> GlobalResult<()> {
         let h = hash(&key);
-        self.shard(h)
-            .try_send(Command::Delete { key })
+        self.send(h, Command::Delete { key })
+    }
+
+    /// key 是否仍在时间轮中（未过期、未删除）。
+    pub async fn contains(&self, key: K) -> GlobalResult<bool> {
+        let index = self.shard_index(hash(&key));
+        self.query(index, |reply| Command::Contains { key, reply })
+            .await
+    }
+
+    /// 各分片存活条目数之和。
+    pub async fn len(&self) -> GlobalResult<usize> {
+        let mut len = 0;
+        for index in 0..self.shards.len() {
+            len += self.query(index, |reply| Command::Len { reply }).await?;
+        }
+        Ok(len)
+    }
+
+    pub async fn is_empty(&self) -> GlobalResult<bool> {
+        Ok(self.len().await? == 0)
+    }
+
+    /// 每个分片的统计快照及其汇总。
+    pub async fn stats(&self) -> GlobalResult<CacheStats> {
+        let mut stats = CacheStats::default();
+        for index in 0..self.shards.len() {
+            let mut shard = self.query(index, |reply| Command::Stats { reply }).await?;
+            shard.dropped_commands = self.dropped[index].load(Ordering::Relaxed);
+            shard.insert_full += self.insert_full[index].load(Ordering::Relaxed);
+            stats.total.merge(&shard);
+            stats.shards.push(shard);
+        }
+        Ok(stats)
+    }
+
+    /// 各分片存活 key 及其剩余 TTL（按 tick 取整）。
+    pub async fn snapshot(&self) -> GlobalResult<CacheSnapshot<K, ()>> {
+        let mut entries = Vec::new();
+        for index in 0..self.shards.len() {
+            entries.extend(
+                self.query(index, |reply| Command::Snapshot { reply })
+                    .await?,
+            );
+        }
+        Ok(CacheSnapshot::new(entries))
+    }
+
+    /// 装回快照中仍存活的 key，剩余 TTL 扣除快照以来的墙钟时间；返回装回的条目数。
+    ///
+    /// 与 `insert` 不同，这里等待通道空位，不会因快照过大而丢命令。
+    pub async fn restore<V>(&self, snapshot: CacheSnapshot<K, V>) -> GlobalResult<usize> {
+        let entries = snapshot.into_live(SystemTime::now());
+        let restored = entries.len();
+        for entry in entries {
+            self.restore_key(entry.key, entry.ttl, entry.remaining)
+                .await?;
+        }
+        Ok(restored)
+    }
+
+    pub(crate) async fn restore_key(
+        &self,
+        key: K,
+        ttl: Duration,
+        remaining: Duration,
+    ) -> GlobalResult<()> {
+        let index = self.shard_index(hash(&key));
+        self.reserve(index)?;
+        let cmd = Command::Restore {
+            key,
+            ttl: self.config.ttl_to_tick(ttl),
+            remaining: self.config.ttl_to_tick(remaining),
+        };
+        self.shards[index]
+            .send(cmd)
+            .await
             .hand_log(|msg| error!("{msg}"))
+            .inspect_err(|_| self.release(index))
     }
 
     pub async fn next_batch(&self) -> Option<Vec<CacheEvent<K>>> {
@@ -493,6 +1007,32 @@ impl<K: CacheKey> Cache<K> {
     }
 }
 
+impl<K: CacheKey + Serialize> Cache<K> {
+    /// `runtime` 停机取消时把存活 key 写入 `path`；任务持有 `cache` 直至停机。
+    pub fn save_on_shutdown<C>(
+        cache: C,
+        runtime: &GlobalRuntime,
+        path: impl Into<PathBuf>,
+    ) -> GlobalResult<JoinHandle<()>>
+    where
+        C: Deref<Target = Self> + Send + Sync + 'static,
+    {
+        save_on_cancel(runtime, path.into(), move || async move {
+            cache.snapshot().await
+        })
+    }
+}
+
+impl<K: CacheKey + DeserializeOwned> Cache<K> {
+    /// 启动时装回 `path` 中的快照；文件不存在时返回 0。
+    pub async fn load(&self, path: &Path) -> GlobalResult<usize> {
+        match CacheSnapshot::<K, ()>::load(path).await? {
+            Some(snapshot) => self.restore(snapshot).await,
+            None => Ok(0),
+        }
+    }
+}
+
 // ================= TEST =================
 #[cfg(test)]
 mod tests {
@@ -647,4 +1187,105 @@ mod tests {
         assert_eq!(batch.len(), 1);
         assert_eq!(batch[0].key, "dup");
     }
+
+    #[tokio::test]
+    async fn test_stats_len_contains() {
+        let c = Cache::with_capacity(16, 2);
+        c.insert("a".to_string(), Duration::from_secs(1))
+            .expect("insert a");
+        c.insert("b".to_string(), Duration::from_secs(60))
+            .expect("insert b");
+        c.refresh("b".to_string()).expect("refresh b");
+        c.refresh("missing".to_string()).expect("refresh missing");
+        c.delete("missing".to_string()).expect("delete missing");
+
+        assert_eq!(c.len().await.unwrap(), 2);
+        assert!(c.contains("b".to_string()).await.unwrap());
+        assert!(!c.contains("missing".to_string()).await.unwrap());
+
+        c.next_batch().await.expect("a expires");
+        let stats = c.stats().await.unwrap();
+        assert_eq!(stats.shards.len(), 2);
+        let total = stats.total;
+        assert_eq!(
+            (total.inserts, total.refreshes, total.expirations),
+            (2, 1, 1)
+        );
+        assert_eq!((total.refresh_misses, total.delete_misses), (1, 1));
+        assert_eq!((total.contains_hits, total.contains_misses), (1, 1));
+        assert_eq!((total.live_entries, total.free_slots), (1, 31));
+        assert_eq!(total.wheel_levels.len(), 3);
+        assert!(total.wheel_levels.iter().sum::<usize>() >= 1);
+        assert_eq!(total.dropped_commands, 0);
+        assert!((total.hit_ratio() - 0.4).abs() < f64::EPSILON);
+        assert!(!c.is_empty().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_restore() {
+        let c = Cache::with_capacity(16, 2);
+        c.insert("a".to_string(), Duration::from_secs(60))
+            .expect("insert a");
+        c.insert("b".to_string(), Duration::from_millis(400))
+            .expect("insert b");
+        let snapshot = c.snapshot().await.unwrap();
+        assert_eq!(snapshot.len(), 2);
+        let a = snapshot.entries().iter().find(|e| e.key == "a").unwrap();
+        assert_eq!(a.ttl, Duration::from_secs(60));
+        assert!(a.remaining <= a.ttl);
+
+        let restored = Cache::with_capacity(16, 2);
+        assert_eq!(restored.restore(snapshot).await.unwrap(), 2);
+        assert!(restored.contains("a".to_string()).await.unwrap());
+        let batch = restored.next_batch().await.expect("b expires");
+        assert_eq!(batch[0].key, "b");
+        assert_eq!(restored.len().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_builder_validates_geometry() {
+        assert!(Cache::<u32>::builder().shards(3).build().is_err());
+        assert!(Cache::<u32>::builder()
+            .tick(Duration::from_micros(500))
+            .build()
+            .is_err());
+        assert!(Cache::<u32>::builder()
+            .wheel_levels([512, 1])
+            .build()
+            .is_err());
+        assert!(Cache::<u32>::builder()
+            .wheel_levels([1 << 32, 1 << 32, 1 << 32])
+            .build()
+            .is_err());
+        assert!(Cache::<u32>::builder().batch_size(0).build().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fine_tick_and_overflow_level() {
+        let c = Cache::<String>::builder()
+            .tick(Duration::from_millis(10))
+            .shards(1)
+            .capacity_per_shard(16)
+            .wheel_levels([4, 2])
+            .batch_size(1)
+            .build()
+            .unwrap();
+        let started = Instant::now();
+        c.insert("fast".to_string(), Duration::from_millis(30))
+            .expect("insert fast");
+        // 4 * 2 ticks * 10ms = 80ms 的时间轮放不下 250ms，先进溢出层
+        c.insert("far".to_string(), Duration::from_millis(250))
+            .expect("insert far");
+        let stats = c.stats().await.unwrap();
+        assert_eq!(stats.total.overflow, 1);
+
+        let batch = c.next_batch().await.expect("fast expires");
+        assert_eq!(batch[0].key, "fast");
+        assert!(started.elapsed() < Duration::from_millis(200));
+
+        let batch = c.next_batch().await.expect("far expires");
+        assert_eq!(batch[0].key, "far");
+        assert!(started.elapsed() >= Duration::from_millis(240));
+        assert!(started.elapsed() < Duration::from_millis(600));
+    }
 }
@@ -0,0 +1,627 @@
+/*
+跨副本缓存失效：本地写入或删除后经可插拔传输广播失效消息，其它副本收到后删除对应 key。
+每个 Invalidator 启动时生成随机副本 id，消息带上该 id，收到自己发出的消息（总线回环、组播 loopback）直接忽略。
+传输只保证尽力送达：总线落后（Lagged）或网络丢包时，该副本的旧值保留到 TTL 到期，日志会记录。
+net 特性下提供基于 PacketWriter 的 UDP 组播 / TCP 扇出传输，入站由 InvalidationDispatcher 解码。
+*/
+
+use crate::bus::broadcast::TypedMessageBus;
+use crate::cache::loading::LoadingCache;
+use crate::cache::ttl::TtlCache;
+use crate::cache::{Cacheable, CachedValue, CommonCache};
+use crate::utils::rt::GlobalRuntime;
+use exception::typed::common::MessageBusError;
+use exception::GlobalResult;
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+
+//
+// ================= MESSAGE =================
+//
+
+/// Keys to drop, sent by the replica identified by `origin`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Invalidation {
+    pub origin: u64,
+    pub keys: Vec<String>,
+}
+
+//
+// ================= TARGET =================
+//
+
+/// A local cache that remote invalidations are applied to.
+pub trait Invalidate: Send + Sync + 'static {
+    fn invalidate(&self, key: &str);
+}
+
+impl Invalidate for CommonCache {
+    fn invalidate(&self, key: &str) {
+        CommonCache::remove(key);
+    }
+}
+
+impl<V> Invalidate for TtlCache<String, V>
+where
+    V: Clone + Send + Sync + 'static,
+{
+    fn invalidate(&self, key: &str) {
+        self.remove(&key.to_string());
+    }
+}
+
+impl<V> Invalidate for LoadingCache<String, V>
+where
+    V: Cacheable + Clone,
+{
+    fn invalidate(&self, key: &str) {
+        LoadingCache::invalidate(self, &key.to_string());
+    }
+}
+
+//
+// ================= TRANSPORT =================
+//
+
+/// Delivers invalidations to every replica, the sender included.
+pub trait InvalidationTransport: Send + Sync + 'static {
+    fn publish(&self, msg: Invalidation) -> BoxFuture<'_, GlobalResult<()>>;
+
+    /// Stream of invalidations from all replicas; may be taken once.
+    fn subscribe(&self) -> GlobalResult<BoxStream<'static, Invalidation>>;
+}
+
+/// In-process transport over a [`TypedMessageBus`], for tests and single-process setups.
+#[derive(Clone, Default)]
+pub struct BusTransport {
+    bus: TypedMessageBus,
+}
+
+impl BusTransport {
+    pub fn new(bus: TypedMessageBus) -> Self {
+        Self { bus }
+    }
+}
+
+impl InvalidationTransport for BusTransport {
+    fn publish(&self, msg: Invalidation) -> BoxFuture<'_, GlobalResult<()>> {
+        // Having no subscriber is not an error: there is no replica to tell.
+        let _ = self.bus.publish(msg);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn subscribe(&self) -> GlobalResult<BoxStream<'static, Invalidation>> {
+        let receiver = self.bus.sub_type_channel::<Invalidation>();
+        Ok(
+            futures::stream::unfold(receiver, |mut receiver| async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(msg) => return Some((msg, receiver)),
+                        Err(MessageBusError::Lagged) => {
+                            warn!(
+                                "cache invalidation bus lagged; skipped keys stay until their ttl"
+                            )
+                        }
+                        Err(_) => return None,
+                    }
+                }
+            })
+            .boxed(),
+        )
+    }
+}
+
+//
+// ================= INVALIDATOR =================
+//
+
+/// Publishes local invalidations and applies remote ones to `target`.
+pub struct Invalidator<T: Invalidate> {
+    origin: u64,
+    target: Arc<T>,
+    transport: Arc<dyn InvalidationTransport>,
+}
+
+impl<T: Invalidate> Invalidator<T> {
+    /// Subscribes to `transport` and applies remote invalidations on a task managed by
+    /// `runtime` until it shuts down or the transport stream ends.
+    pub fn start<P>(
+        target: Arc<T>,
+        transport: P,
+        runtime: &GlobalRuntime,
+    ) -> GlobalResult<(Arc<Self>, JoinHandle<()>)>
+    where
+        P: InvalidationTransport,
+    {
+        let invalidator = Arc::new(Self {
+            origin: rand::random(),
+            target,
+            transport: Arc::new(transport),
+        });
+        let mut incoming = invalidator.transport.subscribe()?;
+        let cancel = runtime.cancel.clone();
+        let receiver = invalidator.clone();
+        let task = runtime.spawn(
+            format!("cache-invalidation:{:x}", invalidator.origin),
+            async move {
+                loop {
+                    tokio::select! {
+                        _ = cancel.cancelled() => break,
+                        msg = incoming.next() => match msg {
+                            Some(msg) => receiver.apply(&msg),
+                            None => break,
+                        },
+                    }
+                }
+            },
+        )?;
+        Ok((invalidator, task))
+    }
+
+    pub fn target(&self) -> &Arc<T> {
+        &self.target
+    }
+
+    /// Tells the other replicas to drop `keys`; the local cache is left alone.
+    pub async fn invalidate(&self, keys: Vec<String>) -> GlobalResult<()> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        self.transport
+            .publish(Invalidation {
+                origin: self.origin,
+                keys,
+            })
+            .await
+    }
+
+    fn apply(&self, msg: &Invalidation) {
+        if msg.origin == self.origin {
+            return;
+        }
+        for key in &msg.keys {
+            self.target.invalidate(key);
+        }
+    }
+}
+
+impl Invalidator<CommonCache> {
+    /// Removes `key` locally and on the other replicas.
+    pub async fn remove(&self, key: &str) -> GlobalResult<Option<CachedValue>> {
+        let removed = CommonCache::remove(key);
+        self.invalidate(vec![key.to_string()]).await?;
+        Ok(removed)
+    }
+
+    /// Stores `value` locally and drops the stale copies held by the other replicas.
+    pub async fn insert(&self, key: String, value: CachedValue) -> GlobalResult<()> {
+        CommonCache::insert(key.clone(), value);
+        self.invalidate(vec![key]).await
+    }
+}
+
+impl<V> Invalidator<TtlCache<String, V>>
+where
+    V: Clone + Send + Sync + 'static,
+{
+    /// Removes `key` locally and on the other replicas.
+    pub async fn remove(&self, key: &str) -> GlobalResult<Option<V>> {
+        let removed = self.target.remove(&key.to_string());
+        self.invalidate(vec![key.to_string()]).await?;
+        Ok(removed)
+    }
+
+    /// Stores `value` locally and drops the stale copies held by the other replicas.
+    pub async fn insert(&self, key: String, value: V) -> GlobalResult<Option<V>> {
+        let previous = self.target.insert(key.clone(), value)?;
+        self.invalidate(vec![key]).await?;
+        Ok(previous)
+    }
+}
+
+impl<V> Invalidator<LoadingCache<String, V>>
+where
+    V: Cacheable + Clone,
+{
+    /// Drops `key` locally and on the other replicas, so each loads it again.
+    pub async fn remove(&self, key: &str) -> GlobalResult<()> {
+        self.target.invalidate(&key.to_string());
+        self.invalidate(vec![key.to_string()]).await
+    }
+
+    /// Stores `value` locally and drops the stale copies held by the other replicas.
+    pub async fn insert(&self, key: String, value: V) -> GlobalResult<()> {
+        self.target.insert(key.clone(), value)?;
+        self.invalidate(vec![key]).await
+    }
+}
+
+//
+// ================= NET =================
+//
+
+#[cfg(feature = "net")]
+pub use packet::{InvalidationDispatcher, PacketTransport};
+
+#[cfg(feature = "net")]
+mod packet {
+    use super::{Invalidation, InvalidationTransport};
+    use crate::net::rw::{PacketDispatcher, PacketEncoder, PacketWriter};
+    use crate::net::state::Protocol;
+    use bytes::Bytes;
+    use exception::{GlobalError, GlobalResult};
+    use futures::future::BoxFuture;
+    use futures::stream::BoxStream;
+    use futures::StreamExt;
+    use log::{debug, error};
+    use std::net::SocketAddr;
+    use std::sync::Mutex;
+    use tokio::sync::mpsc;
+
+    const INCOMING_CHANNEL: usize = 1024;
+
+    fn decode(data: &[u8]) -> GlobalResult<Invalidation> {
+        serde_json::from_slice(data).map_err(|err| {
+            GlobalError::new_sys_error("malformed cache invalidation", |msg| {
+                debug!("{msg}: error={err}")
+            })
+        })
+    }
+
+    fn encode(msg: &Invalidation) -> GlobalResult<Bytes> {
+        serde_json::to_vec(msg).map(Bytes::from).map_err(|err| {
+            GlobalError::new_sys_error("encode cache invalidation failed", |msg| {
+                error!("{msg}: error={err}")
+            })
+        })
+    }
+
+    /// Decodes invalidations received by a packet I/O endpoint for a [`PacketTransport`].
+    ///
+    /// A full queue drops the message; the affected keys stay until their TTL.
+    pub struct InvalidationDispatcher {
+        tx: mpsc::Sender<Invalidation>,
+    }
+
+    impl InvalidationDispatcher {
+        /// Dispatcher for the endpoint, and the receiver to hand to [`PacketTransport`].
+        pub fn channel() -> (Self, mpsc::Receiver<Invalidation>) {
+            let (tx, rx) = mpsc::channel(INCOMING_CHANNEL);
+            (Self { tx }, rx)
+        }
+    }
+
+    impl PacketDispatcher for InvalidationDispatcher {
+        fn dispatch_owned(
+            &self,
+            data: Bytes,
+            remote_addr: SocketAddr,
+            protocol: Protocol,
+        ) -> GlobalResult<()> {
+            let msg = decode(&data)?;
+            self.tx.try_send(msg).map_err(|err| {
+                GlobalError::new_sys_error("cache invalidation dropped", |msg| {
+                    error!("{msg}: remote_addr={remote_addr}, protocol={protocol}, error={err}")
+                })
+            })
+        }
+    }
+
+    /// Sends invalidations through a [`PacketWriter`] to a UDP multicast group or to every
+    /// connected TCP peer.
+    ///
+    /// For TCP the endpoint's splitter and encoder must agree on a framing, for example
+    /// [`LengthDelimitedSplitter`](crate::net::codec::LengthDelimitedSplitter) with
+    /// [`LengthPrefixEncoder`](crate::net::codec::LengthPrefixEncoder).
+    pub struct PacketTransport<E: PacketEncoder> {
+        writer: PacketWriter<E>,
+        protocol: Protocol,
+        peers: Vec<SocketAddr>,
+        incoming: Mutex<Option<mpsc::Receiver<Invalidation>>>,
+    }
+
+    impl<E: PacketEncoder> PacketTransport<E> {
+        /// Publishes to `group_addr`; the endpoint must have joined the group, with multicast
+        /// loopback on when replicas share a host.
+        pub fn udp_multicast(
+            writer: PacketWriter<E>,
+            group_addr: SocketAddr,
+            incoming: mpsc::Receiver<Invalidation>,
+        ) -> Self {
+            Self::new(writer, Protocol::UDP, vec![group_addr], incoming)
+        }
+
+        /// Publishes to each peer over its established TCP connection, accepted or connected.
+        pub fn tcp_fanout(
+            writer: PacketWriter<E>,
+            peers: Vec<SocketAddr>,
+            incoming: mpsc::Receiver<Invalidation>,
+        ) -> Self {
+            Self::new(writer, Protocol::TCP, peers, incoming)
+        }
+
+        fn new(
+            writer: PacketWriter<E>,
+            protocol: Protocol,
+            peers: Vec<SocketAddr>,
+            incoming: mpsc::Receiver<Invalidation>,
+        ) -> Self {
+            Self {
+                writer,
+                protocol,
+                peers,
+                incoming: Mutex::new(Some(incoming)),
+            }
+        }
+    }
+
+    impl<E: PacketEncoder> InvalidationTransport for PacketTransport<E> {
+        /// Writes to every peer; the first failure is returned after all peers were tried.
+        fn publish(&self, msg: Invalidation) -> BoxFuture<'_, GlobalResult<()>> {
+            Box::pin(async move {
+                let data = encode(&msg)?;
+                let mut first_error = None;
+                for peer in &self.peers {
+                    if let Err(error) = self
+                        .writer
+                        .write_to(data.clone(), *peer, self.protocol)
+                        .await
+                    {
+                        first_error.get_or_insert(error);
+                    }
+                }
+                first_error.map_or(Ok(()), Err)
+            })
+        }
+
+        fn subscribe(&self) -> GlobalResult<BoxStream<'static, Invalidation>> {
+            let incoming = self
+                .incoming
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .take()
+                .ok_or_else(|| {
+                    GlobalError::new_sys_error(
+                        "packet invalidation transport is already subscribed",
+                        |msg| error!("{msg}"),
+                    )
+                })?;
+            Ok(
+                futures::stream::unfold(incoming, |mut incoming| async move {
+                    incoming.recv().await.map(|msg| (msg, incoming))
+                })
+                .boxed(),
+            )
+        }
+    }
+}
+
+// ================= TEST =================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::time::sleep;
+
+    #[tokio::test]
+    async fn test_bus_replicas_apply_remote_and_ignore_own() {
+        let runtime = GlobalRuntime::get_main_runtime();
+        let bus = TypedMessageBus::new();
+        let replica = |bus: &TypedMessageBus| {
+            let cache = Arc::new(TtlCache::<String, u32>::with_capacity(
+                Duration::from_secs(60),
+                1024,
+                2,
+            ));
+            Invalidator::start(cache, BusTransport::new(bus.clone()), &runtime)
+                .unwrap()
+                .0
+        };
+        let a = replica(&bus);
+        let b = replica(&bus);
+        for invalidator in [&a, &b] {
+            invalidator
+                .target()
+                .insert("user:1".to_string(), 1)
+                .unwrap();
+            invalidator
+                .target()
+                .insert("user:2".to_string(), 2)
+                .unwrap();
+        }
+
+        a.invalidate(vec!["user:1".to_string()]).await.unwrap();
+        sleep(Duration::from_millis(50)).await;
+        assert_eq!(a.target().get(&"user:1".to_string()), Some(1));
+        assert_eq!(b.target().get(&"user:1".to_string()), None);
+        assert_eq!(b.target().get(&"user:2".to_string()), Some(2));
+    }
+
+    #[cfg(feature = "net")]
+    #[tokio::test]
+    async fn test_udp_multicast_replicas() {
+        use crate::net::codec::{LengthDelimitedSplitter, U32Be};
+        use crate::net::multicast::{MulticastGroup, MulticastInterface, UdpSocketOptions};
+        use crate::net::rw::{managed_rw_with_options, PacketIoOptions, RawPacketEncoder};
+        use std::net::{Ipv4Addr, SocketAddr};
+        use tokio_util::sync::CancellationToken;
+
+        let runtime = GlobalRuntime::get_main_runtime();
+        let group_ip = Ipv4Addr::new(239, 255, 77, 9);
+        let udp = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+        let group_addr = SocketAddr::from((group_ip, udp.local_addr().unwrap().port()));
+        let replica = |udp: std::net::UdpSocket, groups: Vec<MulticastGroup>| {
+            let (dispatcher, incoming) = InvalidationDispatcher::channel();
+            let managed = managed_rw_with_options::<_, LengthDelimitedSplitter<U32Be>, _>(
+                &runtime,
+                format!("cache-invalidation-{}", udp.local_addr().unwrap()),
+                (None, Some(udp)),
+                CancellationToken::new(),
+                Arc::new(dispatcher),
+                Arc::new(RawPacketEncoder),
+                PacketIoOptions {
+                    udp: UdpSocketOptions {
+                        multicast_groups: groups,
+                        multicast_loopback: Some(true),
+                        multicast_interface: Some(MulticastInterface::V4(Ipv4Addr::LOCALHOST)),
+                        ..UdpSocketOptions::default()
+                    },
+                    ..PacketIoOptions::default()
+                },
+            )
+            .unwrap();
+            let transport = PacketTransport::udp_multicast(managed.writer(), group_addr, incoming);
+            let cache = Arc::new(TtlCache::<String, u32>::new(Duration::from_secs(60)));
+            let invalidator = Invalidator::start(cache, transport, &runtime).unwrap().0;
+            (invalidator, managed)
+        };
+        let group = MulticastGroup::V4 {
+            group: group_ip,
+            interface: Ipv4Addr::LOCALHOST,
+        };
+        let (member, member_io) = replica(udp, vec![group]);
+        let (sender, sender_io) = replica(std::net::UdpSocket::bind("0.0.0.0:0").unwrap(), vec![]);
+        member.target().insert("order:7".to_string(), 7).unwrap();
+        member.target().insert("order:8".to_string(), 8).unwrap();
+
+        member
+            .invalidate(vec!["order:8".to_string()])
+            .await
+            .unwrap();
+        sender
+            .invalidate(vec!["order:7".to_string()])
+            .await
+            .unwrap();
+        for _ in 0..100 {
+            if !member.target().contains_key(&"order:7".to_string()) {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(member.target().get(&"order:7".to_string()), None);
+        assert_eq!(member.target().get(&"order:8".to_string()), Some(8));
+
+        assert!(member_io.close_and_wait().await.unwrap().is_complete());
+        assert!(sender_io.close_and_wait().await.unwrap().is_complete());
+    }
+
+    #[cfg(feature = "net")]
+    #[tokio::test]
+    async fn test_tcp_fanout_write_through() {
+        use crate::cache::loading::LoadingOptions;
+        use crate::net::codec::{LengthDelimitedSplitter, LengthPrefixEncoder, U32Be};
+        use crate::net::rw::{managed_rw, ManagedTcpConnectOptions};
+        use tokio_util::sync::CancellationToken;
+
+        #[derive(Clone, Debug, PartialEq)]
+        struct Row(u32);
+
+        impl Cacheable for Row {
+            fn expire_ttl(&self) -> Option<Duration> {
+                None
+            }
+        }
+
+        type Splitter = LengthDelimitedSplitter<U32Be>;
+        let runtime = GlobalRuntime::get_main_runtime();
+        let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let server_addr = tcp.local_addr().unwrap();
+        let (server_dispatcher, server_incoming) = InvalidationDispatcher::channel();
+        let server_io = managed_rw::<_, Splitter, _>(
+            &runtime,
+            format!("cache-invalidation-tcp-{server_addr}"),
+            (Some(tcp), None),
+            CancellationToken::new(),
+            Arc::new(server_dispatcher),
+            Arc::new(LengthPrefixEncoder::<U32Be>::new()),
+        )
+        .unwrap();
+        let (client_dispatcher, client_incoming) = InvalidationDispatcher::channel();
+        let client_dispatcher = Arc::new(client_dispatcher);
+        let client_io = managed_rw::<_, Splitter, _>(
+            &runtime,
+            format!("cache-invalidation-tcp-client-{server_addr}"),
+            (None, None),
+            CancellationToken::new(),
+            client_dispatcher.clone(),
+            Arc::new(LengthPrefixEncoder::<U32Be>::new()),
+        )
+        .unwrap();
+        let connection = client_io
+            .connect_tcp::<_, Splitter>(
+                &runtime,
+                format!("cache-invalidation-tcp-peer-{server_addr}"),
+                ManagedTcpConnectOptions {
+                    remote_addr: server_addr,
+                    local_addr: None,
+                    timeout: Duration::from_secs(1),
+                },
+                client_dispatcher,
+            )
+            .await
+            .unwrap();
+        let client_addr = connection.local_addr();
+        server_io
+            .writer()
+            .wait_tcp_sink(client_addr, Duration::from_secs(1))
+            .await
+            .unwrap();
+
+        let ttl_cache = Arc::new(TtlCache::<String, u32>::new(Duration::from_secs(60)));
+        let server = Invalidator::start(
+            ttl_cache,
+            PacketTransport::tcp_fanout(server_io.writer(), vec![client_addr], server_incoming),
+            &runtime,
+        )
+        .unwrap()
+        .0;
+        let loading_cache = Arc::new(LoadingCache::<String, Row>::new(LoadingOptions::default()));
+        let client = Invalidator::start(
+            loading_cache,
+            PacketTransport::tcp_fanout(client_io.writer(), vec![server_addr], client_incoming),
+            &runtime,
+        )
+        .unwrap()
+        .0;
+
+        server.target().insert("user:1".to_string(), 1).unwrap();
+        client.insert("user:1".to_string(), Row(1)).await.unwrap();
+        client
+            .target()
+            .insert("user:2".to_string(), Row(2))
+            .unwrap();
+        assert_eq!(server.insert("user:2".to_string(), 2).await.unwrap(), None);
+        let key = |key: &str| key.to_string();
+        for _ in 0..100 {
+            if !server.target().contains_key(&key("user:1"))
+                && client.target().get(&key("user:2")).is_none()
+            {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(server.target().get(&key("user:1")), None);
+        assert_eq!(server.target().get(&key("user:2")), Some(2));
+        assert_eq!(client.target().get(&key("user:1")), Some(Row(1)));
+        assert_eq!(client.target().get(&key("user:2")), None);
+
+        client.target().insert(key("user:3"), Row(3)).unwrap();
+        assert_eq!(server.remove("user:3").await.unwrap(), None);
+        for _ in 0..100 {
+            if client.target().get(&key("user:3")).is_none() {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(client.target().get(&key("user:3")), None);
+
+        assert!(connection.close_and_wait().await.unwrap().is_complete());
+        assert!(client_io.close_and_wait().await.unwrap().is_complete());
+        assert!(server_io.close_and_wait().await.unwrap().is_complete());
+    }
+}
@@ -0,0 +1,644 @@
+/*
+加载型缓存：未命中时由 get_with 的 loader 回源，同一 key 的并发未命中合并为一次加载（singleflight）。
+存储复用 TtlCache；TTL 取值自身的 Cacheable::expire_ttl。返回 None 时与 CommonCache 一致视为永不过期，
+单独存放直到失效或被覆盖；配置 default_ttl 后改用该 TTL。
+加载失败可按 negative_ttl 缓存，期间同一 key 直接返回该错误，不再打到数据源。
+配置 refresh_ahead 后，命中的值在过期前 refresh_ahead 时间内会在后台重新加载，读者继续拿旧值；
+后台刷新失败不覆盖旧值，旧值照常过期。值过期时与 CommonCache 一样调用 expire_call，同样最多 100 个并发。
+加载期间对同一 key 的 invalidate/insert 会推进该次加载的代数，加载结果仍返回给调用方但不再写入缓存。
+*/
+
+use crate::cache::c100k::{Cache, CacheKey};
+use crate::cache::ttl::{RemovalCause, TtlCache};
+use crate::cache::Cacheable;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use exception::{BizError, GlobalError, GlobalResult, GlobalResultExt};
+use log::{debug, error};
+use std::future::Future;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::sync::{mpsc, OnceCell, Semaphore};
+use tokio::time::Instant;
+
+//
+// ================= OPTIONS =================
+//
+
+/// Wheel default of the inner `TtlCache`; every store passes its own TTL.
+const WHEEL_TTL: Duration = Duration::from_secs(60);
+const EXPIRE_CALL_CHANNEL: usize = 10000;
+const EXPIRE_CALL_PERMITS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LoadingOptions {
+    /// TTL for values whose `expire_ttl` is `None`; `None` keeps them until invalidated, as
+    /// `CommonCache` does.
+    pub default_ttl: Option<Duration>,
+    /// How long a failed load is served from the cache; `None` does not cache failures.
+    pub negative_ttl: Option<Duration>,
+    /// Reloads a value in the background once a hit finds less than this much TTL left.
+    pub refresh_ahead: Option<Duration>,
+}
+
+//
+// ================= SLOT =================
+//
+
+/// `GlobalError` is not `Clone`; a failure keeps what is needed to hand it to every waiter.
+#[derive(Debug, Clone)]
+enum LoadFailure {
+    Biz(BizError),
+    Sys(String),
+}
+
+impl LoadFailure {
+    fn from_error(error: &GlobalError) -> Self {
+        match error {
+            GlobalError::BizErr(biz) => Self::Biz(biz.clone()),
+            GlobalError::SysErr(sys) => Self::Sys(sys.to_string()),
+        }
+    }
+
+    fn to_error(&self) -> GlobalError {
+        match self {
+            Self::Biz(biz) => GlobalError::BizErr(biz.clone()),
+            Self::Sys(msg) => {
+                GlobalError::new_sys_error(msg, |msg| debug!("cached load failure: {msg}"))
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Slot<V> {
+    Ready {
+        value: V,
+        refresh_at: Option<Instant>,
+    },
+    Failed(LoadFailure),
+}
+
+/// One load of a key, shared by every caller that misses it meanwhile.
+struct Flight<V> {
+    result: OnceCell<Result<V, LoadFailure>>,
+    /// Bumped by `invalidate` and `insert`; a load whose generation moved on is not stored.
+    generation: Mutex<u64>,
+}
+
+impl<V> Default for Flight<V> {
+    fn default() -> Self {
+        Self {
+            result: OnceCell::new(),
+            generation: Mutex::new(0),
+        }
+    }
+}
+
+impl<V> Flight<V> {
+    fn generation(&self) -> std::sync::MutexGuard<'_, u64> {
+        self.generation
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+type Flights<K, V> = DashMap<K, Arc<Flight<V>>>;
+
+/// A caller's part in a flight. Leaving, even by cancellation or a panicking loader, removes
+/// the flight once it has a result or no other caller is left to finish it.
+struct FlightGuard<'a, K: CacheKey, V> {
+    inflight: &'a Flights<K, V>,
+    key: &'a K,
+    flight: Option<Arc<Flight<V>>>,
+}
+
+impl<'a, K: CacheKey, V> FlightGuard<'a, K, V> {
+    fn join(inflight: &'a Flights<K, V>, key: &'a K) -> Self {
+        let flight = inflight.entry(key.clone()).or_default().clone();
+        Self {
+            inflight,
+            key,
+            flight: Some(flight),
+        }
+    }
+
+    fn flight(&self) -> &Flight<V> {
+        self.flight.as_deref().expect("flight is held until drop")
+    }
+}
+
+impl<K: CacheKey, V> Drop for FlightGuard<'_, K, V> {
+    fn drop(&mut self) {
+        let Some(flight) = self.flight.take() else {
+            return;
+        };
+        // Callers leave one at a time under the shard lock, so the last one sees itself alone.
+        if let Entry::Occupied(entry) = self.inflight.entry(self.key.clone()) {
+            if Arc::ptr_eq(entry.get(), &flight) {
+                let done = flight.result.initialized();
+                drop(flight);
+                if done || Arc::strong_count(entry.get()) == 1 {
+                    entry.remove();
+                }
+            }
+        }
+    }
+}
+
+//
+// ================= CACHE =================
+//
+
+struct Inner<K: CacheKey, V> {
+    cache: TtlCache<K, Slot<V>>,
+    /// Values without a TTL; they never expire, so they stay out of the wheel.
+    pinned: DashMap<K, V>,
+    inflight: Flights<K, V>,
+    options: LoadingOptions,
+}
+
+/// Read-through cache that loads misses with a caller-supplied async loader.
+pub struct LoadingCache<K: CacheKey, V> {
+    inner: Arc<Inner<K, V>>,
+}
+
+impl<K, V> LoadingCache<K, V>
+where
+    K: CacheKey,
+    V: Cacheable + Clone,
+{
+    /// Creates a cache on a default-sized wheel; must be called inside a tokio runtime.
+    pub fn new(options: LoadingOptions) -> Self {
+        Self::from_wheel(options, Cache::default())
+    }
+
+    pub fn with_capacity(
+        options: LoadingOptions,
+        capacity_per_shard: usize,
+        shard_len: usize,
+    ) -> Self {
+        Self::from_wheel(options, Cache::with_capacity(capacity_per_shard, shard_len))
+    }
+
+    /// Creates a cache on a wheel built with [`Cache::builder`].
+    pub fn from_wheel(options: LoadingOptions, wheel: Cache<K>) -> Self {
+        let cache = TtlCache::from_wheel(WHEEL_TTL, wheel);
+        let (call_tx, call_rx) = mpsc::channel(EXPIRE_CALL_CHANNEL);
+        cache.on_removal(move |_, slot: &Slot<V>, cause| {
+            if let (Slot::Ready { value, .. }, RemovalCause::Expired) = (slot, cause) {
+                let _ = call_tx
+                    .try_send(value.clone())
+                    .hand_log(|msg| error!("loading cache call channel error: {msg}"));
+            }
+        });
+        // Ends once the cache is dropped and the callback holding the sender goes with it.
+        tokio::spawn(expire_calls(call_rx));
+        Self {
+            inner: Arc::new(Inner {
+                cache,
+                pinned: DashMap::new(),
+                inflight: DashMap::new(),
+                options,
+            }),
+        }
+    }
+
+    /// Returns the cached value or runs `loader`, sharing one load among concurrent callers.
+    ///
+    /// Callers that join a load in progress get its result; their own `loader` only runs if the
+    /// leading caller is cancelled first. A failure cached under `negative_ttl` is returned
+    /// without calling `loader`.
+    pub async fn get_with<F, Fut>(&self, key: K, loader: F) -> GlobalResult<V>
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = GlobalResult<V>> + Send + 'static,
+    {
+        match self.inner.lookup(&key) {
+            Some(Slot::Ready { value, refresh_at }) => {
+                if refresh_at.is_some_and(|at| at <= Instant::now()) {
+                    self.refresh_in_background(key, loader);
+                }
+                Ok(value)
+            }
+            Some(Slot::Failed(failure)) => Err(failure.to_error()),
+            None => self
+                .inner
+                .load(key, loader, false)
+                .await
+                .map_err(|failure| failure.to_error()),
+        }
+    }
+
+    /// Cached value without loading; cached failures read as `None`.
+    pub fn get(&self, key: &K) -> Option<V> {
+        match self.inner.lookup(key)? {
+            Slot::Ready { value, .. } => Some(value),
+            Slot::Failed(_) => None,
+        }
+    }
+
+    /// Stores `value` as if `get_with` had loaded it, replacing a cached value or failure.
+    ///
+    /// A load of `key` already in progress is not stored over it.
+    pub fn insert(&self, key: K, value: V) -> GlobalResult<()> {
+        self.inner
+            .supersede(&key.clone(), || self.inner.insert(key, value))
+    }
+
+    /// Drops the cached value or failure so that the next `get_with` loads again.
+    ///
+    /// A load of `key` already in progress still answers its callers but is not stored.
+    pub fn invalidate(&self, key: &K) {
+        self.inner.supersede(key, || {
+            self.inner.cache.remove(key);
+            self.inner.pinned.remove(key);
+        });
+    }
+
+    /// Entries currently stored, including cached failures.
+    pub fn len(&self) -> usize {
+        self.inner.cache.len() + self.inner.pinned.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.cache.is_empty() && self.inner.pinned.is_empty()
+    }
+
+    fn refresh_in_background<F, Fut>(&self, key: K, loader: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = GlobalResult<V>> + Send + 'static,
+    {
+        if self.inner.inflight.contains_key(&key) {
+            return;
+        }
+        let inner = self.inner.clone();
+        tokio::spawn(async move {
+            let _ = inner.load(key, loader, true).await;
+        });
+    }
+}
+
+impl<K, V> Inner<K, V>
+where
+    K: CacheKey,
+    V: Cacheable + Clone,
+{
+    /// Joins or leads the key's flight. The leader stores the result before the flight ends, so
+    /// a caller arriving afterwards finds it in the cache instead of loading again.
+    async fn load<F, Fut>(&self, key: K, loader: F, refresh: bool) -> Result<V, LoadFailure>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = GlobalResult<V>>,
+    {
+        let guard = FlightGuard::join(&self.inflight, &key);
+        let flight = guard.flight();
+        flight
+            .result
+            .get_or_init(|| async {
+                if let Some(cached) = self.settled(&key, refresh) {
+                    return cached;
+                }
+                let generation = *flight.generation();
+                let result = loader()
+                    .await
+                    .map_err(|error| LoadFailure::from_error(&error));
+                // Held while storing so an invalidate either sees the value or stops the store.
+                let current = flight.generation();
+                if *current == generation {
+                    self.store(&key, &result, refresh);
+                }
+                result
+            })
+            .await
+            .clone()
+    }
+
+    /// Runs `change` to the cached entry of `key` so that a load in progress does not store
+    /// over it.
+    fn supersede<R>(&self, key: &K, change: impl FnOnce() -> R) -> R {
+        let Some(flight) = self.inflight.get(key).map(|flight| flight.clone()) else {
+            return change();
+        };
+        let mut generation = flight.generation();
+        *generation += 1;
+        change()
+    }
+
+    /// Result another flight already stored: any entry for a miss, a value that is no longer
+    /// due for refresh for a refresh.
+    fn settled(&self, key: &K, refresh: bool) -> Option<Result<V, LoadFailure>> {
+        match self.lookup(key)? {
+            Slot::Ready { value, refresh_at } => {
+                let due = refresh && refresh_at.is_some_and(|at| at <= Instant::now());
+                (!due).then_some(Ok(value))
+            }
+            Slot::Failed(failure) => (!refresh).then_some(Err(failure)),
+        }
+    }
+
+    /// Looks in the TTL cache first, then among the pinned values.
+    fn lookup(&self, key: &K) -> Option<Slot<V>> {
+        self.cache.get(key).or_else(|| {
+            self.pinned.get(key).map(|value| Slot::Ready {
+                value: value.clone(),
+                refresh_at: None,
+            })
+        })
+    }
+
+    fn insert(&self, key: K, value: V) -> GlobalResult<()> {
+        let Some(ttl) = value.expire_ttl().or(self.options.default_ttl) else {
+            self.pinned.insert(key.clone(), value);
+            self.cache.remove(&key);
+            return Ok(());
+        };
+        let refresh_at = self
+            .options
+            .refresh_ahead
+            .filter(|ahead| *ahead < ttl)
+            .map(|ahead| Instant::now() + (ttl - ahead));
+        let slot = Slot::Ready { value, refresh_at };
+        self.cache.insert_with_ttl(key.clone(), slot, ttl)?;
+        self.pinned.remove(&key);
+        Ok(())
+    }
+
+    fn store(&self, key: &K, result: &Result<V, LoadFailure>, refresh: bool) {
+        // A rejected wheel command is already logged; callers still get the loaded result.
+        let _ = match result {
+            Ok(value) => self.insert(key.clone(), value.clone()),
+            // A failed refresh keeps serving the value it was meant to replace.
+            Err(_) if refresh => return,
+            Err(failure) => match self.options.negative_ttl {
+                Some(ttl) => {
+                    let slot = Slot::Failed(failure.clone());
+                    self.cache.insert_with_ttl(key.clone(), slot, ttl).map(drop)
+                }
+                None => return,
+            },
+        };
+    }
+}
+
+/// Runs `expire_call` for expired values with at most `EXPIRE_CALL_PERMITS` running at once.
+async fn expire_calls<V: Cacheable>(mut call_rx: mpsc::Receiver<V>) {
+    let semaphore = Arc::new(Semaphore::new(EXPIRE_CALL_PERMITS));
+    while let Some(value) = call_rx.recv().await {
+        let Ok(permit) = semaphore.clone().acquire_owned().await else {
+            break;
+        };
+        tokio::spawn(async move {
+            let _permit = permit;
+            value.expire_call().await;
+        });
+    }
+}
+
+// ================= TEST =================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use tokio::time::sleep;
+
+    #[derive(Clone)]
+    struct Row {
+        version: usize,
+        ttl: Option<Duration>,
+        expired: Arc<AtomicBool>,
+    }
+
+    impl Cacheable for Row {
+        fn expire_call(&self) -> futures::future::BoxFuture<'_, ()> {
+            Box::pin(async move { self.expired.store(true, Ordering::SeqCst) })
+        }
+
+        fn expire_ttl(&self) -> Option<Duration> {
+            self.ttl
+        }
+    }
+
+    fn loader(
+        loads: Arc<AtomicUsize>,
+        ttl: Option<Duration>,
+        expired: Arc<AtomicBool>,
+    ) -> impl FnOnce() -> futures::future::BoxFuture<'static, GlobalResult<Row>> + Send + 'static
+    {
+        move || {
+            Box::pin(async move {
+                sleep(Duration::from_millis(50)).await;
+                let version = loads.fetch_add(1, Ordering::SeqCst) + 1;
+                Ok(Row {
+                    version,
+                    ttl,
+                    expired,
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_misses_load_once_and_expire_calls_back() {
+        let cache = Arc::new(LoadingCache::<String, Row>::with_capacity(
+            LoadingOptions::default(),
+            1024,
+            2,
+        ));
+        let loads = Arc::new(AtomicUsize::new(0));
+        let expired = Arc::new(AtomicBool::new(false));
+        let mut tasks = Vec::new();
+        for _ in 0..16 {
+            let cache = cache.clone();
+            let load = loader(
+                loads.clone(),
+                Some(Duration::from_millis(400)),
+                expired.clone(),
+            );
+            tasks.push(tokio::spawn(async move {
+                cache.get_with("user:1".to_string(), load).await
+            }));
+        }
+        for task in tasks {
+            assert_eq!(task.await.unwrap().unwrap().version, 1);
+        }
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert!(cache.inner.inflight.is_empty());
+
+        sleep(Duration::from_millis(1000)).await;
+        assert!(cache.get(&"user:1".to_string()).is_none());
+        assert!(expired.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_failures_are_cached_for_negative_ttl() {
+        let cache = LoadingCache::<String, Row>::with_capacity(
+            LoadingOptions {
+                negative_ttl: Some(Duration::from_millis(300)),
+                ..LoadingOptions::default()
+            },
+            1024,
+            2,
+        );
+        let calls = Arc::new(AtomicUsize::new(0));
+        for _ in 0..3 {
+            let calls = calls.clone();
+            let result = cache
+                .get_with("missing".to_string(), move || async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Err(GlobalError::new_biz_error(1100, "row not found", |_| {}))
+                })
+                .await;
+            assert!(matches!(
+                result,
+                Err(GlobalError::BizErr(BizError { code: 1100, .. }))
+            ));
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        sleep(Duration::from_millis(700)).await;
+        let loads = Arc::new(AtomicUsize::new(0));
+        let row = cache
+            .get_with(
+                "missing".to_string(),
+                loader(loads, None, Arc::new(AtomicBool::new(false))),
+            )
+            .await
+            .unwrap();
+        assert_eq!(row.version, 1);
+    }
+
+    #[tokio::test]
+    async fn test_values_without_ttl_stay_until_invalidated() {
+        let cache = LoadingCache::<String, Row>::with_capacity(LoadingOptions::default(), 1024, 2);
+        let loads = Arc::new(AtomicUsize::new(0));
+        let expired = Arc::new(AtomicBool::new(false));
+        let key = "config".to_string();
+        cache
+            .get_with(key.clone(), loader(loads.clone(), None, expired.clone()))
+            .await
+            .unwrap();
+
+        sleep(Duration::from_millis(600)).await;
+        let row = cache
+            .get_with(key.clone(), loader(loads.clone(), None, expired.clone()))
+            .await
+            .unwrap();
+        assert_eq!(row.version, 1);
+        assert_eq!(cache.len(), 1);
+        assert!(!expired.load(Ordering::SeqCst));
+
+        cache.invalidate(&key);
+        assert!(cache.is_empty());
+        let row = cache
+            .get_with(key, loader(loads.clone(), None, expired.clone()))
+            .await
+            .unwrap();
+        assert_eq!(row.version, 2);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_or_panicked_leader_leaves_no_flight() {
+        let cache = Arc::new(LoadingCache::<String, Row>::with_capacity(
+            LoadingOptions::default(),
+            1024,
+            2,
+        ));
+        let stalled = {
+            let cache = cache.clone();
+            tokio::spawn(async move {
+                cache
+                    .get_with("stalled".to_string(), std::future::pending)
+                    .await
+            })
+        };
+        sleep(Duration::from_millis(50)).await;
+        assert_eq!(cache.inner.inflight.len(), 1);
+        stalled.abort();
+        assert!(stalled.await.is_err_and(|err| err.is_cancelled()));
+        assert!(cache.inner.inflight.is_empty());
+
+        let panicked = {
+            let cache = cache.clone();
+            tokio::spawn(async move {
+                cache
+                    .get_with("panicked".to_string(), || async { panic!("loader failed") })
+                    .await
+            })
+        };
+        assert!(panicked.await.is_err_and(|err| err.is_panic()));
+        assert!(cache.inner.inflight.is_empty());
+
+        let loads = Arc::new(AtomicUsize::new(0));
+        let row = cache
+            .get_with(
+                "stalled".to_string(),
+                loader(loads, None, Arc::new(AtomicBool::new(false))),
+            )
+            .await
+            .unwrap();
+        assert_eq!(row.version, 1);
+    }
+
+    #[tokio::test]
+    async fn test_invalidate_during_load_drops_the_loaded_value() {
+        let cache = Arc::new(LoadingCache::<String, Row>::with_capacity(
+            LoadingOptions::default(),
+            1024,
+            2,
+        ));
+        let loads = Arc::new(AtomicUsize::new(0));
+        let expired = Arc::new(AtomicBool::new(false));
+        let key = "user:2".to_string();
+        let load = {
+            let cache = cache.clone();
+            let load = loader(loads.clone(), None, expired.clone());
+            let key = key.clone();
+            tokio::spawn(async move { cache.get_with(key, load).await })
+        };
+        sleep(Duration::from_millis(10)).await;
+        cache.invalidate(&key);
+        assert_eq!(load.await.unwrap().unwrap().version, 1);
+        assert!(cache.get(&key).is_none());
+        assert!(cache.inner.inflight.is_empty());
+
+        let row = cache
+            .get_with(key.clone(), loader(loads, None, expired))
+            .await
+            .unwrap();
+        assert_eq!(row.version, 2);
+        assert_eq!(cache.get(&key).unwrap().version, 2);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_ahead_reloads_in_background() {
+        let cache = LoadingCache::<String, Row>::with_capacity(
+            LoadingOptions {
+                default_ttl: Some(Duration::from_secs(2)),
+                refresh_ahead: Some(Duration::from_millis(1800)),
+                ..LoadingOptions::default()
+            },
+            1024,
+            2,
+        );
+        let loads = Arc::new(AtomicUsize::new(0));
+        let expired = Arc::new(AtomicBool::new(false));
+        let key = "hot".to_string();
+        let first = cache
+            .get_with(key.clone(), loader(loads.clone(), None, expired.clone()))
+            .await
+            .unwrap();
+        assert_eq!(first.version, 1);
+
+        sleep(Duration::from_millis(300)).await;
+        let stale = cache
+            .get_with(key.clone(), loader(loads.clone(), None, expired.clone()))
+            .await
+            .unwrap();
+        assert_eq!(stale.version, 1);
+        sleep(Duration::from_millis(200)).await;
+        assert_eq!(cache.get(&key).unwrap().version, 2);
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,4 +1,9 @@
+pub mod bounded;
 pub mod c100k;
+pub mod invalidate;
+pub mod loading;
+pub mod snapshot;
+pub mod ttl;
 
 use crate::utils::rt::GlobalRuntime;
 use dashmap::DashMap;
@@ -52,6 +57,15 @@ impl CachedValue {
         (self.0.as_ref() as &dyn Any).downcast_ref::<T>()
     }
 }
+impl Cacheable for CachedValue {
+    fn expire_call(&self) -> BoxFuture<'_, ()> {
+        self.0.expire_call()
+    }
+
+    fn expire_ttl(&self) -> Option<Duration> {
+        self.0.expire_ttl()
+    }
+}
 impl std::ops::Deref for CachedValue {
     type Target = dyn Cacheable;
 
@@ -0,0 +1,194 @@
+/*
+TTL 缓存快照：保存存活条目的 key、值与剩余 TTL，进程重启后按墙钟流逝时间扣减剩余时间再装回。
+快照以 JSON 写入临时文件后 rename，写到一半被杀不会留下残缺文件。
+墙钟回拨时按未流逝处理；在停机期间已到期的条目装载时直接丢弃，不触发过期回调。
+*/
+
+use crate::utils::rt::GlobalRuntime;
+use exception::{GlobalError, GlobalResult, GlobalResultExt};
+use log::{error, info};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+
+//
+// ================= SNAPSHOT =================
+//
+
+/// A live entry as it was when the snapshot was taken.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotEntry<K, V> {
+    pub key: K,
+    pub value: V,
+    /// Full TTL, restored so that later refreshes restart the original period.
+    pub ttl: Duration,
+    pub remaining: Duration,
+}
+
+/// Live entries of a TTL cache stamped with the wall-clock time they were taken at.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheSnapshot<K, V> {
+    saved_at_ms: u64,
+    entries: Vec<SnapshotEntry<K, V>>,
+}
+
+impl<K, V> CacheSnapshot<K, V> {
+    pub fn new(entries: Vec<SnapshotEntry<K, V>>) -> Self {
+        Self {
+            saved_at_ms: unix_millis(SystemTime::now()),
+            entries,
+        }
+    }
+
+    pub fn entries(&self) -> &[SnapshotEntry<K, V>] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries still live at `now`, their remaining TTL reduced by the time since the snapshot.
+    pub fn into_live(self, now: SystemTime) -> Vec<SnapshotEntry<K, V>> {
+        let elapsed = Duration::from_millis(unix_millis(now).saturating_sub(self.saved_at_ms));
+        self.entries
+            .into_iter()
+            .filter_map(|mut entry| {
+                entry.remaining = entry.remaining.checked_sub(elapsed)?;
+                (!entry.remaining.is_zero()).then_some(entry)
+            })
+            .collect()
+    }
+}
+
+impl<K, V> CacheSnapshot<K, V>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    pub async fn save(&self, path: &Path) -> GlobalResult<()> {
+        let bytes =
+            serde_json::to_vec(self).hand_log(|msg| error!("{msg}: path={}", path.display()))?;
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, bytes)
+            .await
+            .hand_log(|msg| error!("{msg}: path={}", tmp.display()))?;
+        tokio::fs::rename(&tmp, path)
+            .await
+            .hand_log(|msg| error!("{msg}: path={}", path.display()))
+    }
+}
+
+impl<K, V> CacheSnapshot<K, V>
+where
+    K: DeserializeOwned,
+    V: DeserializeOwned,
+{
+    /// Reads a snapshot; `None` when the file does not exist.
+    pub async fn load(path: &Path) -> GlobalResult<Option<Self>> {
+        let bytes = match tokio::fs::read(path).await {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(GlobalError::new_sys_error(
+                    "read cache snapshot failed",
+                    |msg| error!("{msg}: path={}, error={err}", path.display()),
+                ))
+            }
+        };
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .hand_log(|msg| error!("{msg}: path={}", path.display()))
+    }
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
+
+/// Spawns a managed task on `runtime` that saves the snapshot built by `take` once the runtime
+/// is cancelled, so the file is written inside the graceful shutdown window.
+pub(crate) fn save_on_cancel<K, V, F, Fut>(
+    runtime: &GlobalRuntime,
+    path: PathBuf,
+    take: F,
+) -> GlobalResult<JoinHandle<()>>
+where
+    K: Serialize + Send + Sync + 'static,
+    V: Serialize + Send + Sync + 'static,
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = GlobalResult<CacheSnapshot<K, V>>> + Send,
+{
+    let cancel = runtime.cancel.clone();
+    runtime.spawn(format!("cache-snapshot:{}", path.display()), async move {
+        cancel.cancelled().await;
+        let Ok(snapshot) = take().await else {
+            return;
+        };
+        if snapshot.save(&path).await.is_ok() {
+            info!(
+                "cache snapshot saved: path={}, entries={}",
+                path.display(),
+                snapshot.len()
+            );
+        }
+    })
+}
+
+// ================= TEST =================
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_save_load_and_age() {
+        let path = std::env::temp_dir().join(format!("cache-snapshot-{}.json", std::process::id()));
+        assert_eq!(
+            CacheSnapshot::<String, u32>::load(&path).await.unwrap(),
+            None
+        );
+
+        let snapshot = CacheSnapshot::new(vec![
+            SnapshotEntry {
+                key: "short".to_string(),
+                value: 1u32,
+                ttl: Duration::from_secs(10),
+                remaining: Duration::from_secs(2),
+            },
+            SnapshotEntry {
+                key: "long".to_string(),
+                value: 2,
+                ttl: Duration::from_secs(60),
+                remaining: Duration::from_secs(30),
+            },
+        ]);
+        snapshot.save(&path).await.unwrap();
+        let loaded = CacheSnapshot::<String, u32>::load(&path)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded, snapshot);
+        tokio::fs::remove_file(&path).await.unwrap();
+
+        let later = UNIX_EPOCH + Duration::from_millis(loaded.saved_at_ms + 5_000);
+        let live = loaded.into_live(later);
+        assert_eq!(live.len(), 1);
+        assert_eq!(
+            (live[0].key.as_str(), live[0].remaining),
+            ("long", Duration::from_secs(25))
+        );
+
+        tokio::fs::write(&path, b"not json").await.unwrap();
+        assert!(CacheSnapshot::<String, u32>::load(&path).await.is_err());
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+}
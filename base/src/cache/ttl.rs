@@ -0,0 +1,774 @@
+/*
+带值的 TTL 缓存：c100k 分层时间轮负责计时，值存放在分片 DashMap 中。
+读写只触碰 DashMap 分片锁；时间轮命令仍走 c100k 的 try_send 通道，吞吐特性与 c100k 相同。
+每个条目记录自己的截止时间：读取时精确判断过期，时间轮事件到达时再次校验，
+提前到达（tick 取整）或已被重新插入的旧事件只会按剩余时间重新挂入时间轮，不会误删新值。
+有容量上限时（见 bounded.rs），写入额外串行于策略锁，读取仍不阻塞。
+快照（见 snapshot.rs）带上值与剩余 TTL；装回的条目同样经过容量策略。
+*/
+
+use crate::cache::bounded::{Bounded, CacheBound, Policy};
+use crate::cache::c100k::{Cache, CacheKey};
+use crate::cache::snapshot::{save_on_cancel, CacheSnapshot, SnapshotEntry};
+use crate::utils::rt::GlobalRuntime;
+use ahash::RandomState;
+use dashmap::mapref::entry::Entry as MapEntry;
+use dashmap::DashMap;
+use exception::GlobalResult;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, MutexGuard, RwLock, Weak};
+use std::time::{Duration, SystemTime};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio_util::sync::{CancellationToken, DropGuard};
+
+//
+// ================= CONFIG =================
+//
+
+const REMOVED_CHANNEL: usize = 1024;
+/// Deadline offset used when `now + ttl` does not fit in an `Instant`, e.g. for `Duration::MAX`.
+const MAX_TTL: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+//
+// ================= EVENT =================
+//
+
+/// Why the cache dropped an entry on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalCause {
+    /// The entry's TTL elapsed.
+    Expired,
+    /// A bounded cache made room, or refused to admit the entry.
+    Evicted,
+    /// A restored snapshot entry overwrote the live value.
+    Replaced,
+}
+
+/// An entry the cache dropped on its own, carrying the value it held.
+#[derive(Clone, Debug)]
+pub struct Removed<K, V> {
+    pub key: K,
+    pub value: V,
+    pub cause: RemovalCause,
+}
+
+type RemovalCallback<K, V> = Arc<dyn Fn(&K, &V, RemovalCause) + Send + Sync>;
+
+//
+// ================= ENTRY =================
+//
+
+struct TtlEntry<V> {
+    value: V,
+    ttl: Duration,
+    deadline: Instant,
+}
+
+fn deadline_after(now: Instant, ttl: Duration) -> Instant {
+    now.checked_add(ttl).unwrap_or_else(|| now + MAX_TTL)
+}
+
+impl<V> TtlEntry<V> {
+    fn new(value: V, ttl: Duration, now: Instant) -> Self {
+        Self {
+            value,
+            ttl,
+            deadline: deadline_after(now, ttl),
+        }
+    }
+
+    fn with_remaining(value: V, ttl: Duration, remaining: Duration, now: Instant) -> Self {
+        Self {
+            value,
+            ttl,
+            deadline: deadline_after(now, remaining),
+        }
+    }
+
+    fn is_expired(&self, now: Instant) -> bool {
+        self.deadline <= now
+    }
+}
+
+/// Entries dropped by the policy while it was locked; reported once the lock is released.
+struct Settled<K, V> {
+    admitted: bool,
+    evicted: Vec<(K, TtlEntry<V>)>,
+}
+
+//
+// ================= CACHE =================
+//
+
+struct Inner<K: CacheKey, V> {
+    wheel: Arc<Cache<K>>,
+    entries: DashMap<K, TtlEntry<V>, RandomState>,
+    default_ttl: Duration,
+    bound: Option<Bounded<K, V>>,
+    callbacks: RwLock<Vec<RemovalCallback<K, V>>>,
+    removed_tx: broadcast::Sender<Removed<K, V>>,
+}
+
+/// Value-carrying TTL cache on top of the [`c100k`](crate::cache::c100k) timing wheel.
+///
+/// Values are returned by clone, so large values should be stored behind an `Arc`. Entries the
+/// cache drops on its own are handed to [`on_removal`](Self::on_removal) callbacks and
+/// [`subscribe`](Self::subscribe) receivers; explicit removes and overwrites by `insert` are
+/// not reported.
+pub struct TtlCache<K: CacheKey, V> {
+    inner: Arc<Inner<K, V>>,
+    _reaper: DropGuard,
+}
+
+impl<K, V> TtlCache<K, V>
+where
+    K: CacheKey,
+    V: Clone + Send + Sync + 'static,
+{
+    /// Creates an unbounded cache on a default-sized wheel; must be called inside a tokio runtime.
+    pub fn new(default_ttl: Duration) -> Self {
+        Self::from_wheel(default_ttl, Cache::default())
+    }
+
+    pub fn with_capacity(
+        default_ttl: Duration,
+        capacity_per_shard: usize,
+        shard_len: usize,
+    ) -> Self {
+        Self::from_wheel(
+            default_ttl,
+            Cache::with_capacity(capacity_per_shard, shard_len),
+        )
+    }
+
+    /// Creates a cache that evicts entries once `bound` is exceeded.
+    pub fn bounded(default_ttl: Duration, bound: CacheBound<K, V>) -> Self {
+        Self::build(default_ttl, Cache::default(), Some(Bounded::new(bound)))
+    }
+
+    /// Creates an unbounded cache on a wheel built with [`Cache::builder`].
+    pub fn from_wheel(default_ttl: Duration, wheel: Cache<K>) -> Self {
+        Self::build(default_ttl, wheel, None)
+    }
+
+    fn build(default_ttl: Duration, wheel: Cache<K>, bound: Option<Bounded<K, V>>) -> Self {
+        let (removed_tx, _) = broadcast::channel(REMOVED_CHANNEL);
+        let inner = Arc::new(Inner {
+            wheel: Arc::new(wheel),
+            entries: DashMap::with_hasher(RandomState::new()),
+            default_ttl,
+            bound,
+            callbacks: RwLock::new(Vec::new()),
+            removed_tx,
+        });
+        let cancel = CancellationToken::new();
+        tokio::spawn(reap(
+            inner.wheel.clone(),
+            Arc::downgrade(&inner),
+            cancel.clone(),
+        ));
+        Self {
+            inner,
+            _reaper: cancel.drop_guard(),
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let now = Instant::now();
+        {
+            let entry = self.inner.entries.get(key)?;
+            if !entry.is_expired(now) {
+                let value = entry.value.clone();
+                drop(entry);
+                self.inner.record_read(key);
+                return Some(value);
+            }
+        }
+        self.inner.expire_if_due(key, now);
+        None
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Inserts with the default TTL and returns the live value it replaced.
+    pub fn insert(&self, key: K, value: V) -> GlobalResult<Option<V>> {
+        self.insert_with_ttl(key, value, self.inner.default_ttl)
+    }
+
+    /// Inserts with a per-entry TTL and returns the live value it replaced.
+    ///
+    /// Fails without storing the value when the wheel shard is full or its command channel is;
+    /// a live previous value then stays. A bounded cache
+    /// may turn the value away; that is reported as [`RemovalCause::Evicted`], not as an error.
+    pub fn insert_with_ttl(&self, key: K, value: V, ttl: Duration) -> GlobalResult<Option<V>> {
+        let now = Instant::now();
+        self.inner.wheel.insert(key.clone(), ttl)?;
+        let (previous, _) = self.inner.store(key, TtlEntry::new(value, ttl, now));
+        Ok(previous
+            .filter(|entry| !entry.is_expired(now))
+            .map(|entry| entry.value))
+    }
+
+    /// Returns the live value or inserts the one built by `init` with the default TTL.
+    ///
+    /// `init` runs at most once and under the key's shard lock, so it must not touch this cache.
+    pub fn get_or_insert_with<F>(&self, key: K, init: F) -> GlobalResult<V>
+    where
+        F: FnOnce() -> V,
+    {
+        let now = Instant::now();
+        let ttl = self.inner.default_ttl;
+        let mut policy = self.inner.lock_policy();
+        let (value, weight, expired) = match self.inner.entries.entry(key.clone()) {
+            MapEntry::Occupied(entry) if !entry.get().is_expired(now) => {
+                if let Some(policy) = policy.as_mut() {
+                    policy.record(&key);
+                }
+                return Ok(entry.get().value.clone());
+            }
+            MapEntry::Occupied(mut entry) => {
+                let value = init();
+                let weight = self.inner.weigh(&key, &value);
+                self.inner.wheel.insert(key.clone(), ttl)?;
+                let old =
+                    std::mem::replace(entry.get_mut(), TtlEntry::new(value.clone(), ttl, now));
+                (value, weight, Some(old.value))
+            }
+            MapEntry::Vacant(entry) => {
+                let value = init();
+                let weight = self.inner.weigh(&key, &value);
+                self.inner.wheel.insert(key.clone(), ttl)?;
+                entry.insert(TtlEntry::new(value.clone(), ttl, now));
+                (value, weight, None)
+            }
+        };
+        let settled = self.inner.settle(policy, &key, weight);
+        self.inner.report(&key, settled);
+        if let Some(old) = expired {
+            self.inner.notify(key, old, RemovalCause::Expired);
+        }
+        Ok(value)
+    }
+
+    /// Removes the entry and returns its value if it was still live.
+    pub fn remove(&self, key: &K) -> Option<V> {
+        let (key, entry) = self.inner.entries.remove(key)?;
+        self.inner.forget(&key);
+        let live = !entry.is_expired(Instant::now());
+        // A failed delete only leaves a stale wheel slot; its event finds no entry.
+        let _ = self.inner.wheel.delete(key);
+        live.then_some(entry.value)
+    }
+
+    /// Restarts the entry's TTL; returns false if the key is missing or already expired.
+    pub fn touch(&self, key: &K) -> GlobalResult<bool> {
+        let now = Instant::now();
+        let Some(mut entry) = self.inner.entries.get_mut(key) else {
+            return Ok(false);
+        };
+        if entry.is_expired(now) {
+            drop(entry);
+            self.inner.expire_if_due(key, now);
+            return Ok(false);
+        }
+        entry.deadline = deadline_after(now, entry.ttl);
+        drop(entry);
+        self.inner.record_read(key);
+        self.inner.wheel.refresh(key.clone())?;
+        Ok(true)
+    }
+
+    /// Registers a callback for every entry the cache drops on its own.
+    ///
+    /// It runs on the reaper task, on the writer that caused an eviction, or on the caller whose
+    /// lookup found the entry expired first, so it must be cheap.
+    pub fn on_removal<F>(&self, callback: F)
+    where
+        F: Fn(&K, &V, RemovalCause) + Send + Sync + 'static,
+    {
+        self.inner
+            .callbacks
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(Arc::new(callback));
+    }
+
+    /// Stream of dropped entries; a receiver that falls behind sees `RecvError::Lagged`.
+    pub fn subscribe(&self) -> broadcast::Receiver<Removed<K, V>> {
+        self.inner.removed_tx.subscribe()
+    }
+
+    /// Entries currently stored, including expired ones the reaper has not removed yet.
+    pub fn len(&self) -> usize {
+        self.inner.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.entries.is_empty()
+    }
+
+    /// Summed weight tracked by a bounded cache; `None` when the cache is unbounded.
+    pub fn weighted_size(&self) -> Option<u64> {
+        self.inner.bound.as_ref().map(|bound| bound.lock().weight())
+    }
+
+    /// Live entries with their values and remaining TTLs.
+    pub fn snapshot(&self) -> CacheSnapshot<K, V> {
+        let now = Instant::now();
+        let entries = self
+            .inner
+            .entries
+            .iter()
+            .filter(|entry| !entry.is_expired(now))
+            .map(|entry| SnapshotEntry {
+                key: entry.key().clone(),
+                value: entry.value.clone(),
+                ttl: entry.ttl,
+                remaining: entry.deadline - now,
+            })
+            .collect();
+        CacheSnapshot::new(entries)
+    }
+
+    /// Stores the entries still live after the wall-clock time elapsed since the snapshot and
+    /// returns how many were restored.
+    ///
+    /// Restored entries overwrite live ones under the same key, which are reported as
+    /// [`RemovalCause::Replaced`]. Unlike [`insert`](Self::insert) this waits for room on the
+    /// wheel shard instead of failing on a full channel.
+    pub async fn restore(&self, snapshot: CacheSnapshot<K, V>) -> GlobalResult<usize> {
+        let mut restored = 0;
+        for entry in snapshot.into_live(SystemTime::now()) {
+            self.inner
+                .wheel
+                .restore_key(entry.key.clone(), entry.ttl, entry.remaining)
+                .await?;
+            let now = Instant::now();
+            let (previous, admitted) = self.inner.store(
+                entry.key.clone(),
+                TtlEntry::with_remaining(entry.value, entry.ttl, entry.remaining, now),
+            );
+            if let Some(previous) = previous.filter(|previous| !previous.is_expired(now)) {
+                self.inner
+                    .notify(entry.key, previous.value, RemovalCause::Replaced);
+            }
+            restored += usize::from(admitted);
+        }
+        Ok(restored)
+    }
+}
+
+impl<K, V> TtlCache<K, V>
+where
+    K: CacheKey + Serialize,
+    V: Clone + Send + Sync + Serialize + 'static,
+{
+    /// Saves a snapshot to `path` once `runtime` is cancelled by shutdown; the task holds
+    /// `cache` until then.
+    pub fn save_on_shutdown<C>(
+        cache: C,
+        runtime: &GlobalRuntime,
+        path: impl Into<PathBuf>,
+    ) -> GlobalResult<JoinHandle<()>>
+    where
+        C: Deref<Target = Self> + Send + Sync + 'static,
+    {
+        save_on_cancel(
+            runtime,
+            path.into(),
+            move || async move { Ok(cache.snapshot()) },
+        )
+    }
+}
+
+impl<K, V> TtlCache<K, V>
+where
+    K: CacheKey + DeserializeOwned,
+    V: Clone + Send + Sync + DeserializeOwned + 'static,
+{
+    /// Restores the snapshot saved at `path`; returns 0 when there is none.
+    pub async fn load(&self, path: &Path) -> GlobalResult<usize> {
+        match CacheSnapshot::<K, V>::load(path).await? {
+            Some(snapshot) => self.restore(snapshot).await,
+            None => Ok(0),
+        }
+    }
+}
+
+impl<K, V> Inner<K, V>
+where
+    K: CacheKey,
+    V: Clone + Send + Sync + 'static,
+{
+    /// Bounded caches take the policy lock before touching `entries` so that the map and the
+    /// policy change together.
+    fn lock_policy(&self) -> Option<MutexGuard<'_, Policy<K>>> {
+        self.bound.as_ref().map(Bounded::lock)
+    }
+
+    fn weigh(&self, key: &K, value: &V) -> u64 {
+        self.bound
+            .as_ref()
+            .map_or(1, |bound| bound.weigh(key, value))
+    }
+
+    fn record_read(&self, key: &K) {
+        if let Some(bound) = self.bound.as_ref() {
+            bound.record_read(key);
+        }
+    }
+
+    fn settle(
+        &self,
+        policy: Option<MutexGuard<'_, Policy<K>>>,
+        key: &K,
+        weight: u64,
+    ) -> Settled<K, V> {
+        let mut settled = Settled {
+            admitted: true,
+            evicted: Vec::new(),
+        };
+        let Some(mut policy) = policy else {
+            return settled;
+        };
+        let admission = policy.admit(key, weight);
+        settled.admitted = admission.admitted;
+        let rejected = (!admission.admitted).then_some(key);
+        for victim in admission.victims.iter().chain(rejected) {
+            settled.evicted.extend(self.entries.remove(victim));
+        }
+        settled
+    }
+
+    /// Writes an entry whose wheel slot is already scheduled, so a rejected wheel command never
+    /// leaves the map changed, and reports what a bounded cache evicted for it.
+    ///
+    /// Returns the entry it replaced and whether the new one was admitted. A rejected entry
+    /// leaves a stale wheel slot whose event finds nothing to expire.
+    fn store(&self, key: K, entry: TtlEntry<V>) -> (Option<TtlEntry<V>>, bool) {
+        let policy = self.lock_policy();
+        let weight = self.weigh(&key, &entry.value);
+        let previous = self.entries.insert(key.clone(), entry);
+        let settled = self.settle(policy, &key, weight);
+        let admitted = self.report(&key, settled);
+        (previous, admitted)
+    }
+
+    /// Reports evictions; returns whether the written key was admitted.
+    fn report(&self, key: &K, settled: Settled<K, V>) -> bool {
+        let now = Instant::now();
+        for (victim, entry) in settled.evicted {
+            if victim != *key {
+                let _ = self.wheel.delete(victim.clone());
+            }
+            let cause = if entry.is_expired(now) {
+                RemovalCause::Expired
+            } else {
+                RemovalCause::Evicted
+            };
+            self.notify(victim, entry.value, cause);
+        }
+        settled.admitted
+    }
+
+    /// Drops the policy record of a key that left `entries` outside the policy lock, unless a
+    /// concurrent writer has stored it again meanwhile.
+    fn forget(&self, key: &K) {
+        if let Some(bound) = self.bound.as_ref() {
+            let mut policy = bound.lock();
+            if !self.entries.contains_key(key) {
+                policy.remove(key);
+            }
+        }
+    }
+
+    fn expire_if_due(&self, key: &K, now: Instant) {
+        if let Some((key, entry)) = self
+            .entries
+            .remove_if(key, |_, entry| entry.is_expired(now))
+        {
+            self.forget(&key);
+            self.notify(key, entry.value, RemovalCause::Expired);
+        }
+    }
+
+    /// Handles a wheel event: expires the entry if due, otherwise re-arms the remaining TTL.
+    fn on_wheel_event(&self, key: K) {
+        let now = Instant::now();
+        let deadline = match self.entries.get(&key) {
+            None => return,
+            Some(entry) if entry.is_expired(now) => None,
+            Some(entry) => Some(entry.deadline),
+        };
+        let Some(deadline) = deadline else {
+            self.expire_if_due(&key, now);
+            return;
+        };
+        if self.wheel.insert(key.clone(), deadline - now).is_ok() {
+            return;
+        }
+        // Without a wheel slot nothing would reap the entry; a newer write has its own slot.
+        if let Some((key, entry)) = self
+            .entries
+            .remove_if(&key, |_, entry| entry.deadline == deadline)
+        {
+            self.forget(&key);
+            self.notify(key, entry.value, RemovalCause::Evicted);
+        }
+    }
+
+    fn notify(&self, key: K, value: V, cause: RemovalCause) {
+        let callbacks = self
+            .callbacks
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+        for callback in callbacks {
+            callback(&key, &value, cause);
+        }
+        // No receiver is not an error: the removal stream is optional.
+        let _ = self.removed_tx.send(Removed { key, value, cause });
+    }
+}
+
+async fn reap<K, V>(wheel: Arc<Cache<K>>, inner: Weak<Inner<K, V>>, cancel: CancellationToken)
+where
+    K: CacheKey,
+    V: Clone + Send + Sync + 'static,
+{
+    loop {
+        let batch = tokio::select! {
+            _ = cancel.cancelled() => return,
+            batch = wheel.next_batch() => batch,
+        };
+        let (Some(batch), Some(inner)) = (batch, inner.upgrade()) else {
+            return;
+        };
+        for event in batch {
+            inner.on_wheel_event(event.key);
+        }
+    }
+}
+
+// ================= TEST =================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::bounded::EvictionPolicy;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::time::{sleep, timeout};
+
+    #[tokio::test]
+    async fn test_insert_get_remove_touch() {
+        let cache = TtlCache::<String, u32>::with_capacity(Duration::from_secs(60), 1024, 2);
+        assert_eq!(cache.insert("a".to_string(), 1).unwrap(), None);
+        assert_eq!(cache.insert("a".to_string(), 2).unwrap(), Some(1));
+        assert_eq!(cache.get(&"a".to_string()), Some(2));
+        assert_eq!(cache.get_or_insert_with("a".to_string(), || 3).unwrap(), 2);
+        assert_eq!(cache.get_or_insert_with("b".to_string(), || 4).unwrap(), 4);
+        assert!(cache.touch(&"b".to_string()).unwrap());
+        assert!(!cache.touch(&"missing".to_string()).unwrap());
+        assert_eq!(cache.remove(&"a".to_string()), Some(2));
+        assert_eq!(cache.remove(&"a".to_string()), None);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.weighted_size(), None);
+    }
+
+    #[tokio::test]
+    async fn test_full_wheel_channel_keeps_previous_values() {
+        // The shard task cannot drain its one-slot channel until the test yields.
+        let wheel = Cache::<String>::builder()
+            .shards(1)
+            .channel_capacity(1)
+            .build()
+            .unwrap();
+        let cache = TtlCache::<String, u32>::from_wheel(Duration::from_secs(60), wheel);
+        assert_eq!(cache.insert("a".to_string(), 1).unwrap(), None);
+        assert!(cache.insert("a".to_string(), 2).is_err());
+        assert_eq!(cache.get(&"a".to_string()), Some(1));
+        assert!(cache.get_or_insert_with("b".to_string(), || 3).is_err());
+        assert_eq!(cache.get(&"b".to_string()), None);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_full_wheel_shard_rejects_before_storing() {
+        let wheel = Cache::<String>::builder()
+            .shards(1)
+            .capacity_per_shard(2)
+            .build()
+            .unwrap();
+        let cache = TtlCache::<String, u32>::from_wheel(Duration::from_secs(60), wheel);
+        cache.insert("a".to_string(), 1).unwrap();
+        cache.insert("b".to_string(), 2).unwrap();
+        assert!(cache.insert("c".to_string(), 3).is_err());
+        assert!(cache.get_or_insert_with("c".to_string(), || 3).is_err());
+        assert_eq!(cache.get(&"c".to_string()), None);
+        assert_eq!(cache.len(), 2);
+
+        // The slot comes back once the shard has processed the delete.
+        assert_eq!(cache.remove(&"a".to_string()), Some(1));
+        timeout(Duration::from_secs(2), async {
+            while cache.insert("c".to_string(), 3).is_err() {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(cache.get(&"c".to_string()), Some(3));
+        let stats = cache.inner.wheel.stats().await.unwrap();
+        assert!(stats.total.insert_full >= 3);
+    }
+
+    #[tokio::test]
+    async fn test_unbounded_ttl_does_not_overflow() {
+        let cache = TtlCache::<String, u32>::with_capacity(Duration::MAX, 1024, 2);
+        cache.insert("forever".to_string(), 1).unwrap();
+        assert!(cache.touch(&"forever".to_string()).unwrap());
+        assert_eq!(cache.get(&"forever".to_string()), Some(1));
+        let snapshot = cache.snapshot();
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(cache.restore(snapshot).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_expire_delivers_values() {
+        let cache = TtlCache::<String, u32>::with_capacity(Duration::from_secs(60), 1024, 2);
+        let called = Arc::new(AtomicUsize::new(0));
+        let counter = called.clone();
+        cache.on_removal(move |key, value, cause| {
+            assert_eq!(
+                (key.as_str(), *value, cause),
+                ("short", 7, RemovalCause::Expired)
+            );
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let mut removed = cache.subscribe();
+
+        cache
+            .insert_with_ttl("short".to_string(), 7, Duration::from_millis(300))
+            .unwrap();
+        cache.insert("long".to_string(), 8).unwrap();
+
+        let event = timeout(Duration::from_secs(2), removed.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((event.key.as_str(), event.value), ("short", 7));
+        assert_eq!(called.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.get(&"short".to_string()), None);
+        assert_eq!(cache.get(&"long".to_string()), Some(8));
+    }
+
+    #[tokio::test]
+    async fn test_reinsert_and_touch_outlive_stale_wheel_events() {
+        let cache = TtlCache::<String, u32>::with_capacity(Duration::from_millis(600), 1024, 2);
+        let mut removed = cache.subscribe();
+        cache
+            .insert_with_ttl("k".to_string(), 1, Duration::from_millis(200))
+            .unwrap();
+        cache.insert("k".to_string(), 2).unwrap();
+        sleep(Duration::from_millis(400)).await;
+        assert!(cache.touch(&"k".to_string()).unwrap());
+        sleep(Duration::from_millis(400)).await;
+        assert_eq!(cache.get(&"k".to_string()), Some(2));
+
+        let event = timeout(Duration::from_secs(2), removed.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.value, 2);
+        assert!(cache.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bounded_evictions_are_reported_apart_from_expiry() {
+        let cache = TtlCache::<String, String>::bounded(
+            Duration::from_secs(60),
+            CacheBound::max_weight(10, |_, value: &String| value.len() as u64),
+        );
+        let mut removed = cache.subscribe();
+        cache.insert("a".to_string(), "aaaa".to_string()).unwrap();
+        cache.insert("b".to_string(), "bbbb".to_string()).unwrap();
+        cache.get(&"a".to_string());
+        cache.insert("c".to_string(), "cccc".to_string()).unwrap();
+
+        let event = removed.try_recv().unwrap();
+        assert_eq!(
+            (event.key.as_str(), event.cause),
+            ("b", RemovalCause::Evicted)
+        );
+        assert_eq!(cache.get(&"b".to_string()), None);
+        assert_eq!(cache.weighted_size(), Some(8));
+
+        cache.insert("huge".to_string(), "h".repeat(11)).unwrap();
+        assert_eq!(removed.try_recv().unwrap().key, "huge");
+        assert_eq!(cache.len(), 2);
+
+        let lfu = TtlCache::<String, u32>::bounded(
+            Duration::from_secs(60),
+            CacheBound::max_entries(1).with_policy(EvictionPolicy::TinyLfu),
+        );
+        let mut removed = lfu.subscribe();
+        lfu.insert("hot".to_string(), 1).unwrap();
+        for _ in 0..4 {
+            lfu.get(&"hot".to_string());
+        }
+        lfu.insert("cold".to_string(), 2).unwrap();
+        let event = removed.try_recv().unwrap();
+        assert_eq!(
+            (event.key.as_str(), event.cause),
+            ("cold", RemovalCause::Evicted)
+        );
+        assert_eq!(lfu.get(&"hot".to_string()), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_restores_values_and_remaining_ttl() {
+        let cache = TtlCache::<String, u32>::with_capacity(Duration::from_secs(60), 1024, 2);
+        cache.insert("long".to_string(), 1).unwrap();
+        cache
+            .insert_with_ttl("short".to_string(), 2, Duration::from_millis(600))
+            .unwrap();
+        cache
+            .insert_with_ttl("gone".to_string(), 3, Duration::from_millis(1))
+            .unwrap();
+        sleep(Duration::from_millis(5)).await;
+
+        let path = std::env::temp_dir().join(format!("ttl-snapshot-{}.json", std::process::id()));
+        let snapshot = cache.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        snapshot.save(&path).await.unwrap();
+
+        let restored = TtlCache::<String, u32>::with_capacity(Duration::from_secs(60), 1024, 2);
+        let mut removed = restored.subscribe();
+        restored.insert("long".to_string(), 9).unwrap();
+        assert_eq!(restored.load(&path).await.unwrap(), 2);
+        tokio::fs::remove_file(&path).await.unwrap();
+        assert_eq!(restored.get(&"long".to_string()), Some(1));
+        assert_eq!(restored.get(&"short".to_string()), Some(2));
+        assert_eq!(restored.get(&"gone".to_string()), None);
+
+        let event = removed.try_recv().unwrap();
+        assert_eq!(
+            (event.key.as_str(), event.value, event.cause),
+            ("long", 9, RemovalCause::Replaced)
+        );
+        let event = timeout(Duration::from_secs(2), removed.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            (event.key.as_str(), event.cause),
+            ("short", RemovalCause::Expired)
+        );
+        assert_eq!(restored.load(&path).await.unwrap(), 0);
+    }
+}
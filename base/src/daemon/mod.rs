@@ -2,6 +2,7 @@ pub mod signal;
 #[cfg(unix)]
 mod unix;
 
+use crate::utils::rt::{DAEMON_STOP_GRACE, DAEMON_STOP_TIMEOUT_SECS};
 use cfg_lib::CliBasic;
 use exception::GlobalResult;
 use serde::{Deserialize, Serialize};
@@ -10,6 +11,7 @@ use std::io::Write;
 use std::path::PathBuf;
 use std::process;
 use std::sync::Once;
+use std::time::Duration;
 use std::{env, fs};
 
 //todo 优化，运行前检查是否已有进程运行：当前即使未再次运行成功也会重写meta数据
@@ -65,6 +67,9 @@ where
 struct DaemonMeta {
     config_path: String,
     daemon: bool,
+    /// Shutdown budget recorded by the running service, see [`record_shutdown_budget`].
+    #[serde(default)]
+    shutdown_budget_ms: Option<u64>,
 }
 impl DaemonMeta {
     fn get_meta_file_path() -> PathBuf {
@@ -73,16 +78,31 @@ impl DaemonMeta {
     }
 
     fn save_meta(&self) {
+        self.write_meta().expect("Failed to write meta");
+    }
+
+    fn write_meta(&self) -> std::io::Result<()> {
         let meta_path = Self::get_meta_file_path();
-        let content = serde_json::to_string(self).expect("Failed to serialize meta");
+        let content = serde_json::to_string(self)?;
         let mut file = OpenOptions::new()
             .write(true)
             .create(true)
             .truncate(true)
-            .open(meta_path)
-            .expect("Failed to open meta file");
+            .open(meta_path)?;
         file.write_all(content.as_bytes())
-            .expect("Failed to write meta");
+    }
+
+    fn try_load_meta() -> Option<Self> {
+        let content = fs::read_to_string(Self::get_meta_file_path()).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// How long `stop` waits after SIGTERM before SIGKILL.
+    fn stop_timeout(&self) -> Duration {
+        match self.shutdown_budget_ms {
+            Some(budget_ms) => Duration::from_millis(budget_ms) + DAEMON_STOP_GRACE,
+            None => Duration::from_secs(DAEMON_STOP_TIMEOUT_SECS),
+        }
     }
 
     fn load_meta() -> Self {
@@ -92,6 +112,20 @@ impl DaemonMeta {
     }
 }
 
+/// Stores the shutdown budget of a daemonized service so that `stop` and `restart` wait for it.
+pub(crate) fn record_shutdown_budget(budget: Duration) {
+    let Some(mut meta) = DaemonMeta::try_load_meta() else {
+        return;
+    };
+    if !meta.daemon {
+        return;
+    }
+    meta.shutdown_budget_ms = Some(budget.as_millis() as u64);
+    if let Err(err) = meta.write_meta() {
+        log::warn!("record shutdown budget failed: reason={err}");
+    }
+}
+
 pub fn run<D, T>()
 where
     D: Daemon<T>,
@@ -111,6 +145,7 @@ where
             let meta = DaemonMeta {
                 config_path,
                 daemon,
+                shutdown_budget_ms: None,
             };
             meta.save_meta();
             if daemon && (cfg!(target_os = "linux") || cfg!(target_os = "macos")) {
@@ -134,7 +169,7 @@ where
                 if cfg!(target_os = "linux") || cfg!(target_os = "macos") {
                     #[cfg(unix)]
                     {
-                        unix::stop_service();
+                        unix::stop_service(daemon_meta.stop_timeout());
                     }
                 } else {
                     eprintln!("The daemon only supports macOS, and Linux");
@@ -146,12 +181,12 @@ where
         Some(("restart", _)) => {
             let daemon_meta = DaemonMeta::load_meta();
             if daemon_meta.daemon {
-                let config_path = daemon_meta.config_path;
-                cfg_lib::conf::init_cfg(config_path);
+                let stop_timeout = daemon_meta.stop_timeout();
+                cfg_lib::conf::init_cfg(daemon_meta.config_path);
                 if cfg!(target_os = "linux") || cfg!(target_os = "macos") {
                     #[cfg(unix)]
                     {
-                        unix::restart_service::<D, T>();
+                        unix::restart_service::<D, T>(stop_timeout);
                     }
                 } else {
                     eprintln!("The daemon only supports macOS, and Linux");
@@ -238,6 +273,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn stop_timeout_follows_recorded_shutdown_budget() {
+        let meta: DaemonMeta =
+            serde_json::from_str(r#"{"config_path":"app.yml","daemon":true}"#).unwrap();
+        assert_eq!(
+            meta.stop_timeout(),
+            Duration::from_secs(DAEMON_STOP_TIMEOUT_SECS)
+        );
+        let meta = DaemonMeta {
+            shutdown_budget_ms: Some(30_000),
+            ..meta
+        };
+        assert_eq!(
+            meta.stop_timeout(),
+            Duration::from_secs(30) + DAEMON_STOP_GRACE
+        );
+    }
+
     #[test]
     fn sanitized_panic_process_helper() {
         if env::var_os(PANIC_HELPER_ENV).is_some() {
@@ -1,5 +1,4 @@
 use crate::daemon::Daemon;
-use crate::utils::rt::DAEMON_STOP_TIMEOUT_SECS;
 use chrono::{DateTime, NaiveDateTime};
 use daemonize::{Daemonize, Outcome};
 use std::fs::File;
@@ -214,7 +213,7 @@ where
 // ----------------------------
 // 停止服务
 // ----------------------------
-pub(super) fn stop_service() -> bool {
+pub(super) fn stop_service(timeout: Duration) -> bool {
     let pid = match read_pid() {
         Some(p) => p,
         None => {
@@ -237,7 +236,7 @@ pub(super) fn stop_service() -> bool {
         return false;
     }
 
-    if wait_for_process_exit(pid, DAEMON_STOP_TIMEOUT_SECS) {
+    if wait_for_process_exit(pid, timeout) {
         println!("Service stopped after SIGTERM.");
         remove_pid_file();
         return true;
@@ -261,12 +260,12 @@ pub(super) fn stop_service() -> bool {
 // ----------------------------
 // 重启服务
 // ----------------------------
-pub(super) fn restart_service<D, T>()
+pub(super) fn restart_service<D, T>(stop_timeout: Duration)
 where
     D: Daemon<T>,
 {
     println!("Restarting service...");
-    if stop_service() {
+    if stop_service(stop_timeout) {
         // 小延迟确保资源释放（可选）
         thread::sleep(Duration::from_millis(300));
         println!("Starting new instance...");
@@ -298,9 +297,8 @@ fn send_terminate_signal(pid: i32) -> Result<(), std::io::Error> {
     }
 }
 
-fn wait_for_process_exit(pid: i32, timeout_secs: u64) -> bool {
+fn wait_for_process_exit(pid: i32, timeout: Duration) -> bool {
     let start = Instant::now();
-    let timeout = Duration::from_secs(timeout_secs);
 
     while start.elapsed() < timeout {
         if !is_process_running(pid) {
@@ -0,0 +1,596 @@
+use crate::logger::episode::{EpisodeDecision, FailureEpisode};
+use exception::{GlobalError, GlobalResult};
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// IPv4 or IPv6 network in CIDR notation, e.g. `10.0.0.0/8` or `fd00::/8`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct IpCidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpCidr {
+    pub fn new(addr: IpAddr, prefix_len: u8) -> GlobalResult<Self> {
+        let max_len = if addr.is_ipv4() { 32 } else { 128 };
+        if prefix_len > max_len {
+            return Err(GlobalError::new_sys_error(
+                "cidr prefix length out of range",
+                |msg| debug!("{msg}: addr={addr}, prefix_len={prefix_len}"),
+            ));
+        }
+        Ok(Self { addr, prefix_len })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => prefix_eq(
+                u32::from(net).into(),
+                u32::from(ip).into(),
+                32,
+                self.prefix_len,
+            ),
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                prefix_eq(net.into(), ip.into(), 128, self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn prefix_eq(net: u128, ip: u128, bits: u8, prefix_len: u8) -> bool {
+    if prefix_len == 0 {
+        return true;
+    }
+    let shift = u32::from(bits - prefix_len);
+    net >> shift == ip >> shift
+}
+
+impl FromStr for IpCidr {
+    type Err = GlobalError;
+
+    /// Accepts `addr/prefix_len`, or a bare address as a single-host network.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || GlobalError::new_sys_error("invalid cidr", |msg| debug!("{msg}: value={s}"));
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (
+                addr.parse::<IpAddr>().map_err(|_| invalid())?,
+                Some(prefix_len.parse::<u8>().map_err(|_| invalid())?),
+            ),
+            None => (s.parse::<IpAddr>().map_err(|_| invalid())?, None),
+        };
+        let prefix_len = prefix_len.unwrap_or(if addr.is_ipv4() { 32 } else { 128 });
+        Self::new(addr, prefix_len)
+    }
+}
+
+impl fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// Token bucket refilled at `per_second`, holding at most `burst` accepts.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AcceptRate {
+    pub per_second: u32,
+    pub burst: u32,
+}
+
+/// Limits applied to accepted TCP connections before a connection task is spawned.
+///
+/// Rejected streams are dropped immediately. A non-empty `allow` list admits only matching peers
+/// and `deny` always wins over `allow`.
+#[derive(Clone, Debug, Default)]
+pub struct TcpAdmissionOptions {
+    pub max_connections: Option<usize>,
+    pub max_connections_per_ip: Option<usize>,
+    pub accept_rate: Option<AcceptRate>,
+    pub allow: Vec<IpCidr>,
+    pub deny: Vec<IpCidr>,
+}
+
+impl TcpAdmissionOptions {
+    fn is_enabled(&self) -> bool {
+        self.max_connections.is_some()
+            || self.max_connections_per_ip.is_some()
+            || self.accept_rate.is_some()
+            || !self.allow.is_empty()
+            || !self.deny.is_empty()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AdmissionRejection {
+    Denied,
+    MaxConnections,
+    MaxConnectionsPerIp,
+    RateLimited,
+}
+
+impl AdmissionRejection {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Denied => "denied",
+            Self::MaxConnections => "max_connections",
+            Self::MaxConnectionsPerIp => "max_connections_per_ip",
+            Self::RateLimited => "rate_limited",
+        }
+    }
+}
+
+/// Rejection counters since the endpoint started.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct AdmissionRejections {
+    pub denied: u64,
+    pub max_connections: u64,
+    pub max_connections_per_ip: u64,
+    pub rate_limited: u64,
+}
+
+impl AdmissionRejections {
+    pub fn total(&self) -> u64 {
+        self.denied + self.max_connections + self.max_connections_per_ip + self.rate_limited
+    }
+}
+
+struct TokenBucket {
+    rate: AcceptRate,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: AcceptRate) -> Self {
+        Self {
+            rate,
+            tokens: f64::from(rate.burst),
+            refilled_at: Instant::now(),
+        }
+    }
+
+    fn try_take(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+        self.refilled_at = now;
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * f64::from(self.rate.per_second))
+            .min(f64::from(self.rate.burst));
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+#[derive(Default)]
+struct RejectionCounters {
+    denied: AtomicU64,
+    max_connections: AtomicU64,
+    max_connections_per_ip: AtomicU64,
+    rate_limited: AtomicU64,
+}
+
+impl RejectionCounters {
+    fn counter(&self, rejection: AdmissionRejection) -> &AtomicU64 {
+        match rejection {
+            AdmissionRejection::Denied => &self.denied,
+            AdmissionRejection::MaxConnections => &self.max_connections,
+            AdmissionRejection::MaxConnectionsPerIp => &self.max_connections_per_ip,
+            AdmissionRejection::RateLimited => &self.rate_limited,
+        }
+    }
+}
+
+/// Admission state shared by an accept loop and the connection permits it hands out.
+pub(crate) struct TcpAdmission {
+    options: TcpAdmissionOptions,
+    active: AtomicUsize,
+    per_ip: Mutex<HashMap<IpAddr, usize>>,
+    bucket: Option<Mutex<TokenBucket>>,
+    rejections: RejectionCounters,
+    unreported: AtomicUsize,
+    episode: Mutex<FailureEpisode>,
+}
+
+impl TcpAdmission {
+    pub(crate) fn new(options: TcpAdmissionOptions) -> Arc<Self> {
+        Arc::new(Self {
+            bucket: options
+                .accept_rate
+                .map(|rate| Mutex::new(TokenBucket::new(rate))),
+            options,
+            active: AtomicUsize::new(0),
+            per_ip: Mutex::new(HashMap::new()),
+            rejections: RejectionCounters::default(),
+            unreported: AtomicUsize::new(0),
+            episode: Mutex::new(FailureEpisode::default()),
+        })
+    }
+
+    /// Returns a permit held for the connection lifetime, or `None` when the peer is rejected.
+    pub(crate) fn admit(self: &Arc<Self>, remote_addr: SocketAddr) -> Option<TcpAdmissionPermit> {
+        if !self.options.is_enabled() {
+            return Some(TcpAdmissionPermit::unlimited());
+        }
+        let ip = remote_addr.ip().to_canonical();
+        // The accept token is taken last so a peer rejected for its limits does not use it up.
+        let admitted = self
+            .check_lists(ip)
+            .and_then(|()| self.take_slot())
+            .and_then(|()| self.take_ip(ip).inspect_err(|_| self.release_slot()))
+            .and_then(|()| {
+                self.take_token().inspect_err(|_| {
+                    self.release_ip(ip);
+                    self.release_slot();
+                })
+            });
+        self.settle(remote_addr, admitted)
+            .then(|| TcpAdmissionPermit {
+                admission: Some(self.clone()),
+                ip: Some(ip),
+            })
+    }
+
+    /// Admits a stream from a PROXY protocol balancer before its header is read.
+    ///
+    /// The pending header counts against `max_connections` and the accept rate, and the peer
+    /// must be one of `balancers` unless that list is empty. The client the header names is
+    /// checked by [`TcpAdmissionPermit::admit_client`].
+    pub(crate) fn admit_balancer(
+        self: &Arc<Self>,
+        balancer_addr: SocketAddr,
+        balancers: &[IpCidr],
+    ) -> Option<TcpAdmissionPermit> {
+        let ip = balancer_addr.ip().to_canonical();
+        let trusted = if balancers.is_empty() || balancers.iter().any(|cidr| cidr.contains(ip)) {
+            Ok(())
+        } else {
+            Err(AdmissionRejection::Denied)
+        };
+        if trusted.is_ok() && !self.options.is_enabled() {
+            return Some(TcpAdmissionPermit::unlimited());
+        }
+        let admitted = trusted
+            .and_then(|()| self.take_slot())
+            .and_then(|()| self.take_token().inspect_err(|_| self.release_slot()));
+        self.settle(balancer_addr, admitted)
+            .then(|| TcpAdmissionPermit {
+                admission: Some(self.clone()),
+                ip: None,
+            })
+    }
+
+    fn check_lists(&self, ip: IpAddr) -> Result<(), AdmissionRejection> {
+        let options = &self.options;
+        if options.deny.iter().any(|cidr| cidr.contains(ip))
+            || (!options.allow.is_empty() && !options.allow.iter().any(|cidr| cidr.contains(ip)))
+        {
+            return Err(AdmissionRejection::Denied);
+        }
+        Ok(())
+    }
+
+    fn take_token(&self) -> Result<(), AdmissionRejection> {
+        if let Some(bucket) = &self.bucket {
+            if !lock(bucket).try_take(Instant::now()) {
+                return Err(AdmissionRejection::RateLimited);
+            }
+        }
+        Ok(())
+    }
+
+    fn take_slot(&self) -> Result<(), AdmissionRejection> {
+        let max_connections = self.options.max_connections.unwrap_or(usize::MAX);
+        self.active
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |active| {
+                (active < max_connections).then_some(active + 1)
+            })
+            .map(|_| ())
+            .map_err(|_| AdmissionRejection::MaxConnections)
+    }
+
+    fn release_slot(&self) {
+        self.active.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    fn take_ip(&self, ip: IpAddr) -> Result<(), AdmissionRejection> {
+        let mut per_ip = lock(&self.per_ip);
+        let count = per_ip.entry(ip).or_default();
+        if self
+            .options
+            .max_connections_per_ip
+            .is_some_and(|max_per_ip| *count >= max_per_ip)
+        {
+            if *count == 0 {
+                per_ip.remove(&ip);
+            }
+            return Err(AdmissionRejection::MaxConnectionsPerIp);
+        }
+        *count += 1;
+        Ok(())
+    }
+
+    fn release_ip(&self, ip: IpAddr) {
+        let mut per_ip = lock(&self.per_ip);
+        if let Some(count) = per_ip.get_mut(&ip) {
+            *count -= 1;
+            if *count == 0 {
+                per_ip.remove(&ip);
+            }
+        }
+    }
+
+    /// Counts and logs the outcome of an admission check; returns whether it passed.
+    fn settle(&self, remote_addr: SocketAddr, admitted: Result<(), AdmissionRejection>) -> bool {
+        match admitted {
+            Ok(()) => {
+                let decision = lock(&self.episode).record_success(Instant::now());
+                if let EpisodeDecision::Recovered {
+                    total, duration, ..
+                } = decision
+                {
+                    info!("tcp admission recovered: rejected={total}, duration={duration:?}");
+                }
+                true
+            }
+            Err(rejection) => {
+                self.rejections
+                    .counter(rejection)
+                    .fetch_add(1, Ordering::Relaxed);
+                self.unreported.fetch_add(1, Ordering::Relaxed);
+                self.log_rejection(remote_addr, rejection);
+                false
+            }
+        }
+    }
+
+    fn log_rejection(&self, remote_addr: SocketAddr, rejection: AdmissionRejection) {
+        let reason = rejection.as_str();
+        match lock(&self.episode).record_failure(Instant::now()) {
+            EpisodeDecision::Started => {
+                warn!("tcp admission started rejecting connections: reason={reason}, remote_addr={remote_addr}");
+            }
+            EpisodeDecision::Summary {
+                total,
+                since_last_summary,
+                duration,
+                ..
+            } => {
+                let rejections = self.rejections();
+                warn!(
+                    "tcp admission still rejecting connections: total={total}, since_last_summary={since_last_summary}, duration={duration:?}, {rejections:?}"
+                );
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn rejections(&self) -> AdmissionRejections {
+        AdmissionRejections {
+            denied: self.rejections.denied.load(Ordering::Relaxed),
+            max_connections: self.rejections.max_connections.load(Ordering::Relaxed),
+            max_connections_per_ip: self
+                .rejections
+                .max_connections_per_ip
+                .load(Ordering::Relaxed),
+            rate_limited: self.rejections.rate_limited.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Connections currently holding a permit.
+    #[cfg(test)]
+    pub(crate) fn active(&self) -> usize {
+        self.active.load(Ordering::Acquire)
+    }
+
+    /// Rejections not yet folded into a close report.
+    pub(crate) fn take_unreported(&self) -> usize {
+        self.unreported.swap(0, Ordering::AcqRel)
+    }
+}
+
+/// Releases the admitted connection slot, and the per-IP count if taken, when dropped.
+pub(crate) struct TcpAdmissionPermit {
+    admission: Option<Arc<TcpAdmission>>,
+    ip: Option<IpAddr>,
+}
+
+impl TcpAdmissionPermit {
+    fn unlimited() -> Self {
+        Self {
+            admission: None,
+            ip: None,
+        }
+    }
+
+    /// Checks the client named by a PROXY protocol header against the allow/deny lists and
+    /// the per-IP limit, keeping the connection slot taken for its balancer.
+    pub(crate) fn admit_client(mut self, client_addr: SocketAddr) -> Option<Self> {
+        let Some(admission) = self.admission.clone() else {
+            return Some(self);
+        };
+        let ip = client_addr.ip().to_canonical();
+        let admitted = admission
+            .check_lists(ip)
+            .and_then(|()| admission.take_ip(ip));
+        admission.settle(client_addr, admitted).then(|| {
+            self.ip = Some(ip);
+            self
+        })
+    }
+}
+
+impl Drop for TcpAdmissionPermit {
+    fn drop(&mut self) {
+        if let Some(admission) = self.admission.take() {
+            if let Some(ip) = self.ip {
+                admission.release_ip(ip);
+            }
+            admission.release_slot();
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(ip: &str, port: u16) -> SocketAddr {
+        SocketAddr::new(ip.parse().unwrap(), port)
+    }
+
+    #[test]
+    fn cidr_parses_and_matches_v4_v6_and_mapped_addresses() {
+        let net: IpCidr = "10.1.0.0/16".parse().unwrap();
+        assert!(net.contains("10.1.255.3".parse().unwrap()));
+        assert!(net.contains("::ffff:10.1.0.9".parse().unwrap()));
+        assert!(!net.contains("10.2.0.1".parse().unwrap()));
+        let v6: IpCidr = "fd00::/8".parse().unwrap();
+        assert!(v6.contains("fd12::1".parse().unwrap()));
+        assert!(!v6.contains("10.1.0.1".parse().unwrap()));
+        let host: IpCidr = "192.168.0.7".parse().unwrap();
+        assert_eq!(host.to_string(), "192.168.0.7/32");
+        assert!("0.0.0.0/0"
+            .parse::<IpCidr>()
+            .unwrap()
+            .contains("8.8.8.8".parse().unwrap()));
+        assert!("10.0.0.0/33".parse::<IpCidr>().is_err());
+        assert!("10.0.0/8".parse::<IpCidr>().is_err());
+    }
+
+    #[test]
+    fn admission_enforces_lists_and_connection_limits_until_permits_drop() {
+        let admission = TcpAdmission::new(TcpAdmissionOptions {
+            max_connections: Some(3),
+            max_connections_per_ip: Some(2),
+            allow: vec!["127.0.0.0/8".parse().unwrap()],
+            deny: vec!["127.0.0.9".parse().unwrap()],
+            ..TcpAdmissionOptions::default()
+        });
+        assert!(admission.admit(addr("10.0.0.1", 1)).is_none());
+        assert!(admission.admit(addr("127.0.0.9", 1)).is_none());
+
+        let first = admission.admit(addr("127.0.0.1", 1)).unwrap();
+        let _second = admission.admit(addr("127.0.0.1", 2)).unwrap();
+        assert!(admission.admit(addr("127.0.0.1", 3)).is_none());
+        let _third = admission.admit(addr("127.0.0.2", 1)).unwrap();
+        assert!(admission.admit(addr("127.0.0.3", 1)).is_none());
+
+        drop(first);
+        let _fourth = admission.admit(addr("127.0.0.1", 4)).unwrap();
+        assert_eq!(
+            admission.rejections(),
+            AdmissionRejections {
+                denied: 2,
+                max_connections: 1,
+                max_connections_per_ip: 1,
+                rate_limited: 0,
+            }
+        );
+        assert_eq!(admission.take_unreported(), 4);
+        assert_eq!(admission.take_unreported(), 0);
+    }
+
+    #[test]
+    fn balancer_admission_holds_a_slot_until_the_client_is_checked() {
+        let admission = TcpAdmission::new(TcpAdmissionOptions {
+            max_connections: Some(2),
+            max_connections_per_ip: Some(1),
+            deny: vec!["192.0.2.9".parse().unwrap()],
+            ..TcpAdmissionOptions::default()
+        });
+        let balancers = ["10.0.0.0/8".parse().unwrap()];
+        assert!(admission
+            .admit_balancer(addr("172.16.0.1", 1), &balancers)
+            .is_none());
+
+        let first = admission
+            .admit_balancer(addr("10.0.0.1", 1), &balancers)
+            .unwrap();
+        let pending = admission
+            .admit_balancer(addr("10.0.0.1", 2), &balancers)
+            .unwrap();
+        assert!(admission
+            .admit_balancer(addr("10.0.0.1", 3), &balancers)
+            .is_none());
+
+        let _client = first.admit_client(addr("192.0.2.7", 1)).unwrap();
+        assert!(pending.admit_client(addr("192.0.2.7", 2)).is_none());
+        let pending = admission
+            .admit_balancer(addr("10.0.0.1", 4), &balancers)
+            .unwrap();
+        assert!(pending.admit_client(addr("192.0.2.9", 1)).is_none());
+        assert_eq!(
+            admission.rejections(),
+            AdmissionRejections {
+                denied: 2,
+                max_connections: 1,
+                max_connections_per_ip: 1,
+                rate_limited: 0,
+            }
+        );
+        assert_eq!(admission.active(), 1);
+    }
+
+    #[test]
+    fn rejected_peers_do_not_use_up_accept_tokens() {
+        let admission = TcpAdmission::new(TcpAdmissionOptions {
+            max_connections_per_ip: Some(1),
+            accept_rate: Some(AcceptRate {
+                per_second: 1,
+                burst: 2,
+            }),
+            ..TcpAdmissionOptions::default()
+        });
+        let _first = admission.admit(addr("127.0.0.1", 1)).unwrap();
+        for port in 2..10 {
+            assert!(admission.admit(addr("127.0.0.1", port)).is_none());
+        }
+        let _second = admission.admit(addr("127.0.0.2", 1)).unwrap();
+        assert!(admission.admit(addr("127.0.0.3", 1)).is_none());
+        assert_eq!(
+            admission.rejections(),
+            AdmissionRejections {
+                denied: 0,
+                max_connections: 0,
+                max_connections_per_ip: 8,
+                rate_limited: 1,
+            }
+        );
+        assert_eq!(admission.active(), 2);
+    }
+
+    #[test]
+    fn token_bucket_limits_bursts_and_refills_over_time() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket {
+            rate: AcceptRate {
+                per_second: 10,
+                burst: 2,
+            },
+            tokens: 2.0,
+            refilled_at: start,
+        };
+        assert!(bucket.try_take(start));
+        assert!(bucket.try_take(start));
+        assert!(!bucket.try_take(start));
+        assert!(bucket.try_take(start + std::time::Duration::from_millis(100)));
+        assert!(!bucket.try_take(start + std::time::Duration::from_millis(150)));
+        assert!(bucket.try_take(start + std::time::Duration::from_secs(10)));
+        assert!(bucket.try_take(start + std::time::Duration::from_secs(10)));
+        assert!(!bucket.try_take(start + std::time::Duration::from_secs(10)));
+    }
+}
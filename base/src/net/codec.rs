@@ -0,0 +1,447 @@
+//! Built-in framings for [`PacketSplitter`] and their mirror [`PacketEncoder`]s.
+//!
+//! Splitters are created through `Default` by the packet I/O entry points, so framing is chosen
+//! at the type level: a prefix or delimiter marker plus a `MAX_FRAME_LEN` const. Frames longer
+//! than `MAX_FRAME_LEN` are rejected as soon as they are detected instead of being buffered.
+
+use crate::net::rw::{EncodedPacket, InlinePrefix, PacketEncoder, PacketSplitter, MAX_BUF_SIZE};
+use bytes::{Buf, Bytes, BytesMut};
+use exception::{GlobalError, GlobalResult};
+use log::error;
+use std::marker::PhantomData;
+
+const VARINT_MAX_LEN: usize = 10;
+
+/// Wire format of a frame length prefix. The prefix carries the payload length only.
+pub trait LengthPrefix: Send + Sync + 'static {
+    /// Largest payload length the prefix can carry.
+    const MAX_LEN: usize;
+
+    /// Returns `(prefix_len, payload_len)`, or `None` while the prefix is still incomplete.
+    fn decode(buf: &[u8]) -> GlobalResult<Option<(usize, usize)>>;
+
+    fn encode(len: usize) -> InlinePrefix;
+}
+
+macro_rules! fixed_length_prefix {
+    ($($name:ident => $ty:ty, $from:ident, $to:ident;)+) => {
+        $(
+            #[derive(Clone, Copy, Debug, Default)]
+            pub struct $name;
+
+            impl LengthPrefix for $name {
+                const MAX_LEN: usize = <$ty>::MAX as usize;
+
+                fn decode(buf: &[u8]) -> GlobalResult<Option<(usize, usize)>> {
+                    const N: usize = size_of::<$ty>();
+                    match buf.first_chunk::<N>() {
+                        Some(bytes) => Ok(Some((N, <$ty>::$from(*bytes) as usize))),
+                        None => Ok(None),
+                    }
+                }
+
+                fn encode(len: usize) -> InlinePrefix {
+                    InlinePrefix::$to(len as $ty)
+                }
+            }
+        )+
+    };
+}
+
+fixed_length_prefix! {
+    U16Be => u16, from_be_bytes, from_be;
+    U16Le => u16, from_le_bytes, from_le;
+    U32Be => u32, from_be_bytes, from_be;
+    U32Le => u32, from_le_bytes, from_le;
+}
+
+/// Unsigned LEB128 length prefix, as used by protobuf length-delimited streams.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Varint;
+
+impl LengthPrefix for Varint {
+    const MAX_LEN: usize = usize::MAX;
+
+    fn decode(buf: &[u8]) -> GlobalResult<Option<(usize, usize)>> {
+        let mut value = 0u64;
+        for (idx, byte) in buf.iter().take(VARINT_MAX_LEN).enumerate() {
+            let bits = u64::from(byte & 0x7f);
+            if idx == VARINT_MAX_LEN - 1 && bits > 1 {
+                break;
+            }
+            value |= bits << (idx * 7);
+            if byte & 0x80 == 0 {
+                return match usize::try_from(value) {
+                    Ok(len) => Ok(Some((idx + 1, len))),
+                    Err(_) => Err(varint_overflow()),
+                };
+            }
+        }
+        if buf.len() < VARINT_MAX_LEN {
+            return Ok(None);
+        }
+        Err(varint_overflow())
+    }
+
+    fn encode(len: usize) -> InlinePrefix {
+        let mut data = [0u8; VARINT_MAX_LEN];
+        let mut value = len as u64;
+        let mut idx = 0;
+        while value >= 0x80 {
+            data[idx] = (value as u8) | 0x80;
+            value >>= 7;
+            idx += 1;
+        }
+        data[idx] = value as u8;
+        InlinePrefix::new(&data[..=idx]).expect("varint prefix fits inline capacity")
+    }
+}
+
+fn varint_overflow() -> GlobalError {
+    GlobalError::new_sys_error("varint length prefix overflows", |msg| error!("{msg}"))
+}
+
+/// Splits a stream into frames that start with a `P` length prefix. The prefix is stripped.
+#[derive(Clone, Debug, Default)]
+pub struct LengthDelimitedSplitter<P, const MAX_FRAME_LEN: usize = MAX_BUF_SIZE> {
+    _prefix: PhantomData<P>,
+}
+
+impl<P, const MAX_FRAME_LEN: usize> LengthDelimitedSplitter<P, MAX_FRAME_LEN>
+where
+    P: LengthPrefix,
+{
+    pub fn new() -> Self {
+        Self {
+            _prefix: PhantomData,
+        }
+    }
+}
+
+impl<P, const MAX_FRAME_LEN: usize> PacketSplitter for LengthDelimitedSplitter<P, MAX_FRAME_LEN>
+where
+    P: LengthPrefix,
+{
+    fn feed_owned<F>(&mut self, chunk: &mut BytesMut, mut f: F) -> GlobalResult<()>
+    where
+        F: FnMut(Bytes) -> GlobalResult<()>,
+    {
+        while let Some((prefix_len, frame_len)) = P::decode(chunk)? {
+            check_frame_len::<MAX_FRAME_LEN>(frame_len)?;
+            let total = prefix_len + frame_len;
+            if chunk.len() < total {
+                chunk.reserve(total - chunk.len());
+                break;
+            }
+            chunk.advance(prefix_len);
+            f(chunk.split_to(frame_len).freeze())?;
+        }
+        Ok(())
+    }
+}
+
+/// Frame terminator used by [`DelimiterSplitter`] and [`DelimiterEncoder`].
+pub trait Delimiter: Send + Sync + 'static {
+    /// Must not be empty.
+    const DELIMITER: &'static [u8];
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CrLf;
+
+impl Delimiter for CrLf {
+    const DELIMITER: &'static [u8] = b"\r\n";
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Lf;
+
+impl Delimiter for Lf {
+    const DELIMITER: &'static [u8] = b"\n";
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Nul;
+
+impl Delimiter for Nul {
+    const DELIMITER: &'static [u8] = b"\0";
+}
+
+/// Splits a stream on `D::DELIMITER`. The delimiter is stripped and empty frames are kept.
+#[derive(Clone, Debug)]
+pub struct DelimiterSplitter<D, const MAX_FRAME_LEN: usize = MAX_BUF_SIZE> {
+    /// Bytes at the front of the buffer already known not to start a delimiter.
+    scanned: usize,
+    _delimiter: PhantomData<D>,
+}
+
+impl<D, const MAX_FRAME_LEN: usize> DelimiterSplitter<D, MAX_FRAME_LEN>
+where
+    D: Delimiter,
+{
+    pub fn new() -> Self {
+        const {
+            assert!(
+                !D::DELIMITER.is_empty(),
+                "Delimiter::DELIMITER must not be empty"
+            )
+        };
+        Self {
+            scanned: 0,
+            _delimiter: PhantomData,
+        }
+    }
+}
+
+impl<D, const MAX_FRAME_LEN: usize> Default for DelimiterSplitter<D, MAX_FRAME_LEN>
+where
+    D: Delimiter,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D, const MAX_FRAME_LEN: usize> PacketSplitter for DelimiterSplitter<D, MAX_FRAME_LEN>
+where
+    D: Delimiter,
+{
+    fn feed_owned<F>(&mut self, chunk: &mut BytesMut, mut f: F) -> GlobalResult<()>
+    where
+        F: FnMut(Bytes) -> GlobalResult<()>,
+    {
+        let delimiter = D::DELIMITER;
+        loop {
+            match find(&chunk[self.scanned..], delimiter) {
+                Some(pos) => {
+                    let frame_len = self.scanned + pos;
+                    self.scanned = 0;
+                    check_frame_len::<MAX_FRAME_LEN>(frame_len)?;
+                    let frame = chunk.split_to(frame_len).freeze();
+                    chunk.advance(delimiter.len());
+                    f(frame)?;
+                }
+                None => {
+                    // A delimiter may straddle the next read, so keep its possible head unscanned.
+                    self.scanned = chunk.len().saturating_sub(delimiter.len() - 1);
+                    return check_frame_len::<MAX_FRAME_LEN>(self.scanned);
+                }
+            }
+        }
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn check_frame_len<const MAX_FRAME_LEN: usize>(len: usize) -> GlobalResult<()> {
+    if len > MAX_FRAME_LEN {
+        return Err(GlobalError::new_sys_error(
+            "frame length exceeds max frame size",
+            |msg| error!("{msg}: len={len}, max={MAX_FRAME_LEN}"),
+        ));
+    }
+    Ok(())
+}
+
+/// Prepends a `P` length prefix on TCP; UDP datagrams are sent unchanged.
+#[derive(Clone, Debug, Default)]
+pub struct LengthPrefixEncoder<P, const MAX_FRAME_LEN: usize = MAX_BUF_SIZE> {
+    _prefix: PhantomData<P>,
+}
+
+impl<P, const MAX_FRAME_LEN: usize> LengthPrefixEncoder<P, MAX_FRAME_LEN>
+where
+    P: LengthPrefix,
+{
+    pub fn new() -> Self {
+        Self {
+            _prefix: PhantomData,
+        }
+    }
+}
+
+impl<P, const MAX_FRAME_LEN: usize> PacketEncoder for LengthPrefixEncoder<P, MAX_FRAME_LEN>
+where
+    P: LengthPrefix,
+{
+    fn encode_tcp(&self, data: Bytes) -> GlobalResult<EncodedPacket> {
+        check_frame_len::<MAX_FRAME_LEN>(data.len())?;
+        if data.len() > P::MAX_LEN {
+            return Err(GlobalError::new_sys_error(
+                "packet length exceeds prefix max",
+                |msg| error!("{msg}: len={}, max={}", data.len(), P::MAX_LEN),
+            ));
+        }
+        Ok(EncodedPacket::with_inline_prefix(
+            P::encode(data.len()),
+            data,
+        ))
+    }
+}
+
+/// Appends `D::DELIMITER` on TCP; UDP datagrams are sent unchanged.
+///
+/// Payloads containing the delimiter are rejected since the peer would split them.
+#[derive(Clone, Debug, Default)]
+pub struct DelimiterEncoder<D, const MAX_FRAME_LEN: usize = MAX_BUF_SIZE> {
+    _delimiter: PhantomData<D>,
+}
+
+impl<D, const MAX_FRAME_LEN: usize> DelimiterEncoder<D, MAX_FRAME_LEN>
+where
+    D: Delimiter,
+{
+    pub fn new() -> Self {
+        Self {
+            _delimiter: PhantomData,
+        }
+    }
+}
+
+impl<D, const MAX_FRAME_LEN: usize> PacketEncoder for DelimiterEncoder<D, MAX_FRAME_LEN>
+where
+    D: Delimiter,
+{
+    fn encode_tcp(&self, data: Bytes) -> GlobalResult<EncodedPacket> {
+        check_frame_len::<MAX_FRAME_LEN>(data.len())?;
+        if find(&data, D::DELIMITER).is_some() {
+            return Err(GlobalError::new_sys_error(
+                "packet contains frame delimiter",
+                |msg| error!("{msg}: len={}, delimiter={:?}", data.len(), D::DELIMITER),
+            ));
+        }
+        let mut frame = BytesMut::with_capacity(data.len() + D::DELIMITER.len());
+        frame.extend_from_slice(&data);
+        frame.extend_from_slice(D::DELIMITER);
+        Ok(EncodedPacket::single(frame.freeze()))
+    }
+}
+
+pub type U16BeLengthDelimitedSplitter = LengthDelimitedSplitter<U16Be>;
+pub type U16LeLengthDelimitedSplitter = LengthDelimitedSplitter<U16Le>;
+pub type U32BeLengthDelimitedSplitter = LengthDelimitedSplitter<U32Be>;
+pub type U32LeLengthDelimitedSplitter = LengthDelimitedSplitter<U32Le>;
+pub type VarintLengthDelimitedSplitter = LengthDelimitedSplitter<Varint>;
+pub type CrLfSplitter = DelimiterSplitter<CrLf>;
+pub type NulSplitter = DelimiterSplitter<Nul>;
+
+pub type U32BeLengthPrefixEncoder = LengthPrefixEncoder<U32Be>;
+pub type U32LeLengthPrefixEncoder = LengthPrefixEncoder<U32Le>;
+pub type VarintLengthPrefixEncoder = LengthPrefixEncoder<Varint>;
+pub type CrLfEncoder = DelimiterEncoder<CrLf>;
+pub type NulEncoder = DelimiterEncoder<Nul>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::rw::U16BeLengthPrefixEncoder;
+
+    fn encode<E: PacketEncoder>(encoder: &E, packets: &[&[u8]]) -> Vec<u8> {
+        let mut wire = Vec::new();
+        for packet in packets {
+            match encoder.encode_tcp(Bytes::copy_from_slice(packet)).unwrap() {
+                EncodedPacket::Single(data) => wire.extend_from_slice(&data),
+                EncodedPacket::InlinePrefix { prefix, payload } => {
+                    wire.extend_from_slice(prefix.as_slice());
+                    wire.extend_from_slice(&payload);
+                }
+            }
+        }
+        wire
+    }
+
+    fn split<S: PacketSplitter>(splitter: &mut S, wire: &[u8], chunk_len: usize) -> Vec<Bytes> {
+        let mut buf = BytesMut::new();
+        let mut frames = Vec::new();
+        for chunk in wire.chunks(chunk_len) {
+            buf.extend_from_slice(chunk);
+            splitter
+                .feed_owned(&mut buf, |frame| {
+                    frames.push(frame);
+                    Ok(())
+                })
+                .unwrap();
+        }
+        assert!(buf.is_empty());
+        frames
+    }
+
+    fn assert_round_trip<E, S>(encoder: E)
+    where
+        E: PacketEncoder,
+        S: PacketSplitter + Default,
+    {
+        let large = vec![7u8; 300];
+        let packets: [&[u8]; 4] = [b"hello", b"", &large, b"world"];
+        let wire = encode(&encoder, &packets);
+        for chunk_len in [1, 3, wire.len()] {
+            let frames = split(&mut S::default(), &wire, chunk_len);
+            assert_eq!(frames.len(), packets.len(), "chunk_len={chunk_len}");
+            for (frame, packet) in frames.iter().zip(packets) {
+                assert_eq!(frame.as_ref(), packet);
+            }
+        }
+    }
+
+    #[test]
+    fn encoders_and_splitters_round_trip_across_fragmented_reads() {
+        assert_round_trip::<_, U16BeLengthDelimitedSplitter>(U16BeLengthPrefixEncoder);
+        assert_round_trip::<_, U16LeLengthDelimitedSplitter>(LengthPrefixEncoder::<U16Le>::new());
+        assert_round_trip::<_, U32BeLengthDelimitedSplitter>(U32BeLengthPrefixEncoder::new());
+        assert_round_trip::<_, U32LeLengthDelimitedSplitter>(U32LeLengthPrefixEncoder::new());
+        assert_round_trip::<_, VarintLengthDelimitedSplitter>(VarintLengthPrefixEncoder::new());
+        assert_round_trip::<_, DelimiterSplitter<Lf>>(DelimiterEncoder::<Lf>::new());
+        assert_round_trip::<_, NulSplitter>(NulEncoder::new());
+
+        let wire = encode(&CrLfEncoder::new(), &[b"a\rb", b"c"]);
+        assert_eq!(wire, b"a\rb\r\nc\r\n");
+        let frames = split(&mut CrLfSplitter::new(), &wire, 1);
+        assert_eq!(
+            frames,
+            [Bytes::from_static(b"a\rb"), Bytes::from_static(b"c")]
+        );
+    }
+
+    #[test]
+    fn varint_prefix_uses_minimal_encoding_and_rejects_overflow() {
+        assert_eq!(Varint::encode(0).as_slice(), [0]);
+        assert_eq!(Varint::encode(300).as_slice(), [0xac, 0x02]);
+        assert_eq!(Varint::decode(&[0xac, 0x02]).unwrap(), Some((2, 300)));
+        assert_eq!(Varint::decode(&[0xac]).unwrap(), None);
+        assert_eq!(
+            Varint::decode(Varint::encode(u64::MAX as usize).as_slice()).unwrap(),
+            Some((10, u64::MAX as usize))
+        );
+        assert!(Varint::decode(&[0xff; 10]).is_err());
+        assert!(
+            Varint::decode(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02]).is_err()
+        );
+    }
+
+    #[test]
+    fn oversize_frames_are_rejected_before_buffering_payload() {
+        let mut splitter = LengthDelimitedSplitter::<U32Be, 8>::new();
+        let mut buf = BytesMut::from(&9u32.to_be_bytes()[..]);
+        assert!(splitter.feed_owned(&mut buf, |_| Ok(())).is_err());
+
+        let mut splitter = DelimiterSplitter::<CrLf, 4>::new();
+        let mut buf = BytesMut::from(&b"abcd\r"[..]);
+        splitter.feed_owned(&mut buf, |_| Ok(())).unwrap();
+        buf.extend_from_slice(b"\nabcdef");
+        assert!(splitter.feed_owned(&mut buf, |_| Ok(())).is_err());
+
+        assert!(LengthPrefixEncoder::<U32Be, 8>::new()
+            .encode_tcp(Bytes::from_static(b"123456789"))
+            .is_err());
+        assert!(LengthPrefixEncoder::<U16Le>::new()
+            .encode_tcp(Bytes::from(vec![0; u16::MAX as usize + 1]))
+            .is_err());
+        assert!(CrLfEncoder::new()
+            .encode_tcp(Bytes::from_static(b"a\r\nb"))
+            .is_err());
+    }
+}
@@ -0,0 +1,391 @@
+//! Request/response correlation over a [`PacketWriter`].
+//!
+//! A [`PacketCorrelator`] is installed as the endpoint dispatcher. Inbound frames that answer a
+//! pending [`PacketCaller::call`] complete that call; every other frame and every close event is
+//! forwarded to the wrapped dispatcher.
+
+use crate::net::rw::{PacketDispatcher, PacketEncoder, PacketWriter, RawPacketEncoder};
+use crate::net::state::Protocol;
+use bytes::Bytes;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use exception::{GlobalError, GlobalResult};
+use log::debug;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Extracts the key that pairs a request frame with its response frame.
+pub trait PacketCorrelation: Send + Sync + 'static {
+    type Key: Eq + Hash + Clone + Debug + Send + Sync + 'static;
+
+    /// Key of an outbound request; `None` rejects the call.
+    fn request_key(&self, frame: &Bytes) -> Option<Self::Key>;
+
+    /// Key of an inbound frame; `None` marks it as unsolicited.
+    fn response_key(&self, frame: &Bytes) -> Option<Self::Key>;
+}
+
+struct PendingCall {
+    call_id: u64,
+    response: oneshot::Sender<Bytes>,
+}
+
+/// Dispatcher that routes responses to pending calls and forwards everything else to `inner`.
+///
+/// Pending calls are keyed by peer address and correlation key, so the same key may be in
+/// flight to different peers. Calls to a stream peer fail as soon as that peer closes.
+pub struct PacketCorrelator<C, D>
+where
+    C: PacketCorrelation,
+    D: PacketDispatcher,
+{
+    correlation: C,
+    inner: Arc<D>,
+    pending: DashMap<(SocketAddr, C::Key), PendingCall>,
+    next_call_id: AtomicU64,
+}
+
+impl<C, D> PacketCorrelator<C, D>
+where
+    C: PacketCorrelation,
+    D: PacketDispatcher,
+{
+    pub fn new(correlation: C, inner: Arc<D>) -> Arc<Self> {
+        Arc::new(Self {
+            correlation,
+            inner,
+            pending: DashMap::new(),
+            next_call_id: AtomicU64::new(1),
+        })
+    }
+
+    /// Binds the correlator to the endpoint writer; calls are sent with `protocol`.
+    pub fn caller<E>(
+        self: &Arc<Self>,
+        writer: PacketWriter<E>,
+        protocol: Protocol,
+    ) -> PacketCaller<C, D, E>
+    where
+        E: PacketEncoder,
+    {
+        PacketCaller {
+            correlator: self.clone(),
+            writer,
+            protocol,
+        }
+    }
+
+    /// Number of calls still waiting for a response.
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+impl<C, D> PacketDispatcher for PacketCorrelator<C, D>
+where
+    C: PacketCorrelation,
+    D: PacketDispatcher,
+{
+    fn dispatch_owned(
+        &self,
+        data: Bytes,
+        remote_addr: SocketAddr,
+        protocol: Protocol,
+    ) -> GlobalResult<()> {
+        let pending = self
+            .correlation
+            .response_key(&data)
+            .and_then(|key| self.pending.remove(&(remote_addr, key)));
+        match pending {
+            Some((_, call)) => {
+                // The caller may have timed out between lookup and send; the response is dropped.
+                let _ = call.response.send(data);
+                Ok(())
+            }
+            None => self.inner.dispatch_owned(data, remote_addr, protocol),
+        }
+    }
+
+    fn close(&self, remote_addr: SocketAddr, protocol: Protocol) -> GlobalResult<()> {
+        if matches!(protocol, Protocol::TCP | Protocol::UnixStream) {
+            self.pending.retain(|(addr, _), _| *addr != remote_addr);
+        }
+        self.inner.close(remote_addr, protocol)
+    }
+}
+
+/// Sends correlated requests through a [`PacketWriter`] and awaits the matching response.
+pub struct PacketCaller<C, D, E = RawPacketEncoder>
+where
+    C: PacketCorrelation,
+    D: PacketDispatcher,
+    E: PacketEncoder,
+{
+    correlator: Arc<PacketCorrelator<C, D>>,
+    writer: PacketWriter<E>,
+    protocol: Protocol,
+}
+
+impl<C, D, E> Clone for PacketCaller<C, D, E>
+where
+    C: PacketCorrelation,
+    D: PacketDispatcher,
+    E: PacketEncoder,
+{
+    fn clone(&self) -> Self {
+        Self {
+            correlator: self.correlator.clone(),
+            writer: self.writer.clone(),
+            protocol: self.protocol,
+        }
+    }
+}
+
+/// Removes the pending entry when a call times out, fails to send, or is dropped.
+struct PendingGuard<'a, C, D>
+where
+    C: PacketCorrelation,
+    D: PacketDispatcher,
+{
+    correlator: &'a PacketCorrelator<C, D>,
+    key: (SocketAddr, C::Key),
+    call_id: u64,
+}
+
+impl<C, D> Drop for PendingGuard<'_, C, D>
+where
+    C: PacketCorrelation,
+    D: PacketDispatcher,
+{
+    fn drop(&mut self) {
+        self.correlator
+            .pending
+            .remove_if(&self.key, |_, call| call.call_id == self.call_id);
+    }
+}
+
+impl<C, D, E> PacketCaller<C, D, E>
+where
+    C: PacketCorrelation,
+    D: PacketDispatcher,
+    E: PacketEncoder,
+{
+    /// Writes `frame` to `remote_addr` and waits for the frame with the same key; `timeout`
+    /// bounds the write and the wait together.
+    ///
+    /// Fails when the key is already in flight to this peer, the write fails, the timeout
+    /// passes, or the stream peer closes first.
+    pub async fn call(
+        &self,
+        remote_addr: SocketAddr,
+        frame: Bytes,
+        timeout: Duration,
+    ) -> GlobalResult<Bytes> {
+        if timeout.is_zero() {
+            return Err(GlobalError::new_sys_error(
+                "packet call timeout must be greater than zero",
+                |msg| debug!("{msg}: remote_addr={remote_addr}"),
+            ));
+        }
+        let correlator = self.correlator.as_ref();
+        let key = correlator.correlation.request_key(&frame).ok_or_else(|| {
+            GlobalError::new_sys_error("packet call frame has no correlation key", |msg| {
+                debug!("{msg}: remote_addr={remote_addr}")
+            })
+        })?;
+        let call_id = correlator.next_call_id.fetch_add(1, Ordering::Relaxed);
+        let (response_tx, response_rx) = oneshot::channel();
+        match correlator.pending.entry((remote_addr, key.clone())) {
+            Entry::Occupied(_) => {
+                return Err(GlobalError::new_sys_error(
+                    "packet call key is already in flight",
+                    |msg| debug!("{msg}: remote_addr={remote_addr}, key={key:?}"),
+                ));
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(PendingCall {
+                    call_id,
+                    response: response_tx,
+                });
+            }
+        }
+        let guard = PendingGuard {
+            correlator,
+            key: (remote_addr, key.clone()),
+            call_id,
+        };
+
+        let exchange = async {
+            self.writer
+                .write_to(frame, remote_addr, self.protocol)
+                .await?;
+            response_rx.await.map_err(|_| {
+                GlobalError::new_sys_error("packet call peer closed before responding", |msg| {
+                    debug!("{msg}: remote_addr={remote_addr}, key={key:?}")
+                })
+            })
+        };
+        let result = tokio::time::timeout(timeout, exchange).await;
+        drop(guard);
+        result.unwrap_or_else(|_| {
+            Err(GlobalError::new_sys_error("packet call timed out", |msg| {
+                debug!("{msg}: remote_addr={remote_addr}, key={key:?}")
+            }))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PacketCorrelation, PacketCorrelator};
+    use crate::net::codec::{DelimiterEncoder, DelimiterSplitter, Lf};
+    use crate::net::rw::{managed_rw, ManagedTcpConnectOptions, PacketDispatcher};
+    use crate::net::state::Protocol;
+    use crate::tokio;
+    use crate::tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use crate::tokio::net::TcpListener;
+    use crate::tokio_util::sync::CancellationToken;
+    use crate::utils::rt::GlobalRuntime;
+    use bytes::Bytes;
+    use exception::GlobalResult;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// Frames look like `<id> <body>`; the id pairs requests with responses.
+    struct LeadingId;
+
+    impl PacketCorrelation for LeadingId {
+        type Key = Bytes;
+
+        fn request_key(&self, frame: &Bytes) -> Option<Bytes> {
+            self.response_key(frame)
+        }
+
+        fn response_key(&self, frame: &Bytes) -> Option<Bytes> {
+            let end = frame.iter().position(|byte| *byte == b' ')?;
+            Some(frame.slice(..end))
+        }
+    }
+
+    #[derive(Default)]
+    struct Unsolicited {
+        frames: Mutex<Vec<Bytes>>,
+    }
+
+    impl PacketDispatcher for Unsolicited {
+        fn dispatch_owned(
+            &self,
+            data: Bytes,
+            _remote_addr: SocketAddr,
+            _protocol: Protocol,
+        ) -> GlobalResult<()> {
+            self.frames.lock().unwrap().push(data);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn calls_complete_out_of_order_and_fail_on_timeout_or_close() {
+        type Splitter = DelimiterSplitter<Lf>;
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote_addr = server.local_addr().unwrap();
+        let unsolicited = Arc::new(Unsolicited::default());
+        let correlator = PacketCorrelator::new(LeadingId, unsolicited.clone());
+        let managed = managed_rw::<_, Splitter, _>(
+            &GlobalRuntime::get_main_runtime(),
+            format!("correlator-{remote_addr}"),
+            (None, None),
+            CancellationToken::new(),
+            correlator.clone(),
+            Arc::new(DelimiterEncoder::<Lf>::default()),
+        )
+        .unwrap();
+        let _connection = managed
+            .connect_tcp::<_, Splitter>(
+                &GlobalRuntime::get_main_runtime(),
+                format!("correlator-peer-{remote_addr}"),
+                ManagedTcpConnectOptions {
+                    remote_addr,
+                    local_addr: None,
+                    timeout: Duration::from_secs(1),
+                },
+                correlator.clone(),
+            )
+            .await
+            .unwrap();
+        let (peer, _) = server.accept().await.unwrap();
+        let (peer_read, mut peer_write) = peer.into_split();
+        let mut peer_lines = BufReader::new(peer_read).lines();
+        let caller = correlator.caller(managed.writer(), Protocol::TCP);
+
+        let timeout = Duration::from_secs(2);
+        let first = tokio::spawn({
+            let caller = caller.clone();
+            async move {
+                caller
+                    .call(remote_addr, Bytes::from_static(b"1 ping"), timeout)
+                    .await
+            }
+        });
+        let second = tokio::spawn({
+            let caller = caller.clone();
+            async move {
+                caller
+                    .call(remote_addr, Bytes::from_static(b"2 ping"), timeout)
+                    .await
+            }
+        });
+        let mut requests = vec![
+            peer_lines.next_line().await.unwrap().unwrap(),
+            peer_lines.next_line().await.unwrap().unwrap(),
+        ];
+        requests.sort();
+        assert_eq!(requests, ["1 ping", "2 ping"]);
+        assert!(caller
+            .call(remote_addr, Bytes::from_static(b"1 again"), timeout)
+            .await
+            .is_err());
+        peer_write
+            .write_all(b"2 pong\nnotice\n1 pong\n")
+            .await
+            .unwrap();
+        assert_eq!(first.await.unwrap().unwrap().as_ref(), b"1 pong");
+        assert_eq!(second.await.unwrap().unwrap().as_ref(), b"2 pong");
+        assert_eq!(
+            unsolicited.frames.lock().unwrap().as_slice(),
+            [Bytes::from_static(b"notice")]
+        );
+
+        assert!(caller
+            .call(
+                remote_addr,
+                Bytes::from_static(b"3 ping"),
+                Duration::from_millis(20)
+            )
+            .await
+            .is_err());
+        assert_eq!(correlator.pending_len(), 0);
+
+        let pending = tokio::spawn({
+            let caller = caller.clone();
+            async move {
+                caller
+                    .call(remote_addr, Bytes::from_static(b"4 ping"), timeout)
+                    .await
+            }
+        });
+        while peer_lines.next_line().await.unwrap().unwrap() != "4 ping" {}
+        drop(peer_write);
+        drop(peer_lines);
+        assert!(pending.await.unwrap().is_err());
+        assert_eq!(correlator.pending_len(), 0);
+        assert!(managed.close_and_wait().await.unwrap().is_complete());
+    }
+}
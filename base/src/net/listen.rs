@@ -1,4 +1,4 @@
-use crate::exception::{GlobalResult, GlobalResultExt};
+use crate::exception::{GlobalError, GlobalResult, GlobalResultExt};
 use crate::net::state::Protocol;
 use log::error;
 use std::net::{SocketAddr, TcpListener, UdpSocket};
@@ -22,5 +22,34 @@ pub fn listen(
             let tcp_listener = TcpListener::bind(socket_addr).hand_log(|msg| error!("{msg}"))?;
             Ok((Some(tcp_listener), Some(udp_socket)))
         }
+        Protocol::UnixStream | Protocol::UnixDatagram => Err(GlobalError::new_sys_error(
+            "unix socket protocols listen on a path; use listen_unix",
+            |msg| error!("{msg}: protocol={protocol}"),
+        )),
+    }
+}
+
+/// Binds a Unix socket at `path` for [`crate::net::rw::managed_unix_rw_with_options`] or
+/// [`crate::net::rw::unix_rw_with_options`].
+///
+/// A socket file left behind by a dead process is removed first; a live socket or any other
+/// file at `path` is an error.
+#[cfg(all(unix, feature = "net"))]
+pub fn listen_unix(
+    protocol: Protocol,
+    path: impl AsRef<std::path::Path>,
+) -> GlobalResult<(
+    Option<std::os::unix::net::UnixListener>,
+    Option<std::os::unix::net::UnixDatagram>,
+)> {
+    use crate::net::unix::{bind_unix_datagram, bind_unix_listener};
+
+    match protocol {
+        Protocol::UnixStream => Ok((Some(bind_unix_listener(path)?), None)),
+        Protocol::UnixDatagram => Ok((None, Some(bind_unix_datagram(path)?))),
+        _ => Err(GlobalError::new_sys_error(
+            "listen_unix requires a unix socket protocol",
+            |msg| error!("{msg}: protocol={protocol}"),
+        )),
     }
 }
@@ -1,6 +1,17 @@
+pub mod admission;
+pub mod codec;
+pub mod correlate;
 pub mod listen;
+pub mod multicast;
+pub mod proxy;
+pub mod reconnect;
 pub mod rw;
 pub mod state;
+pub mod stats;
+pub mod tls;
+pub mod unix;
 
 pub use listen::listen;
+#[cfg(unix)]
+pub use listen::listen_unix;
 pub use rw as reader;
@@ -0,0 +1,257 @@
+//! Multicast group membership and broadcast settings for the UDP socket of a packet I/O endpoint.
+//!
+//! Group and broadcast destinations need no special writer call: once the socket is configured,
+//! [`PacketWriter::write_to`](crate::net::rw::PacketWriter::write_to) with [`Protocol::UDP`]
+//! sends to them like any unicast peer.
+//!
+//! [`Protocol::UDP`]: crate::net::state::Protocol::UDP
+
+use exception::{GlobalError, GlobalResult, GlobalResultExt};
+use log::{debug, error};
+use socket2::SockRef;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use tokio::net::UdpSocket;
+
+/// A multicast group joined on one interface.
+///
+/// IPv4 interfaces are named by a local address (`0.0.0.0` lets the kernel choose); IPv6
+/// interfaces by index (`0` lets the kernel choose).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MulticastGroup {
+    V4 {
+        group: Ipv4Addr,
+        interface: Ipv4Addr,
+    },
+    V6 {
+        group: Ipv6Addr,
+        interface: u32,
+    },
+}
+
+impl MulticastGroup {
+    pub fn group(&self) -> IpAddr {
+        match self {
+            Self::V4 { group, .. } => IpAddr::V4(*group),
+            Self::V6 { group, .. } => IpAddr::V6(*group),
+        }
+    }
+
+    fn validate(&self, local_addr: SocketAddr) -> GlobalResult<()> {
+        if !self.group().is_multicast() {
+            return Err(GlobalError::new_sys_error(
+                "not a multicast group address",
+                |msg| error!("{msg}: group={}", self.group()),
+            ));
+        }
+        if self.group().is_ipv4() != local_addr.is_ipv4() {
+            return Err(GlobalError::new_sys_error(
+                "multicast group family does not match the udp socket",
+                |msg| error!("{msg}: group={}, local_addr={local_addr}", self.group()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Outgoing interface for multicast datagrams, see [`MulticastGroup`] for the naming rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MulticastInterface {
+    V4(Ipv4Addr),
+    V6(u32),
+}
+
+/// UDP socket settings applied when the endpoint starts.
+///
+/// Multicast settings for the socket's address family are used; TTL means the IPv6 hop limit on
+/// IPv6 sockets.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UdpSocketOptions {
+    /// Enables `SO_BROADCAST` so that writes to broadcast addresses are allowed.
+    pub broadcast: bool,
+    pub multicast_groups: Vec<MulticastGroup>,
+    pub multicast_ttl: Option<u32>,
+    /// Whether local receivers get our own multicast datagrams; the kernel default is on.
+    pub multicast_loopback: Option<bool>,
+    pub multicast_interface: Option<MulticastInterface>,
+}
+
+/// Groups joined by an endpoint's UDP socket; [`leave_all`](Self::leave_all) runs on shutdown.
+pub(crate) struct UdpMemberships {
+    socket: Arc<UdpSocket>,
+    local_addr: SocketAddr,
+    groups: Mutex<Vec<MulticastGroup>>,
+}
+
+impl UdpMemberships {
+    /// Applies `options` to `socket`; groups joined before a failure are left again.
+    pub(crate) fn configure(
+        socket: Arc<UdpSocket>,
+        options: &UdpSocketOptions,
+    ) -> GlobalResult<Self> {
+        let local_addr = socket.local_addr().hand_log(|msg| error!("{msg}"))?;
+        let memberships = Self {
+            socket,
+            local_addr,
+            groups: Mutex::new(Vec::new()),
+        };
+        memberships.apply(options)?;
+        for group in &options.multicast_groups {
+            if let Err(error) = memberships.join(*group) {
+                memberships.leave_all();
+                return Err(error);
+            }
+        }
+        Ok(memberships)
+    }
+
+    fn apply(&self, options: &UdpSocketOptions) -> GlobalResult<()> {
+        let log = |msg: std::fmt::Arguments<'_>| error!("{msg}: local_addr={}", self.local_addr);
+        let socket = SockRef::from(self.socket.as_ref());
+        if options.broadcast {
+            self.socket.set_broadcast(true).hand_log(log)?;
+        }
+        match (self.local_addr, options.multicast_ttl) {
+            (SocketAddr::V4(_), Some(ttl)) => {
+                self.socket.set_multicast_ttl_v4(ttl).hand_log(log)?
+            }
+            (SocketAddr::V6(_), Some(hops)) => socket.set_multicast_hops_v6(hops).hand_log(log)?,
+            (_, None) => {}
+        }
+        match (self.local_addr, options.multicast_loopback) {
+            (SocketAddr::V4(_), Some(on)) => self.socket.set_multicast_loop_v4(on).hand_log(log)?,
+            (SocketAddr::V6(_), Some(on)) => self.socket.set_multicast_loop_v6(on).hand_log(log)?,
+            (_, None) => {}
+        }
+        match (self.local_addr, options.multicast_interface) {
+            (SocketAddr::V4(_), Some(MulticastInterface::V4(interface))) => {
+                socket.set_multicast_if_v4(&interface).hand_log(log)
+            }
+            (SocketAddr::V6(_), Some(MulticastInterface::V6(interface))) => {
+                socket.set_multicast_if_v6(interface).hand_log(log)
+            }
+            (_, None) => Ok(()),
+            (local_addr, Some(interface)) => Err(GlobalError::new_sys_error(
+                "multicast interface family does not match the udp socket",
+                |msg| error!("{msg}: interface={interface:?}, local_addr={local_addr}"),
+            )),
+        }
+    }
+
+    /// Joins `group`; joining a group that is already joined is a no-op.
+    pub(crate) fn join(&self, group: MulticastGroup) -> GlobalResult<()> {
+        group.validate(self.local_addr)?;
+        let mut groups = self.lock_groups();
+        if groups.contains(&group) {
+            return Ok(());
+        }
+        match group {
+            MulticastGroup::V4 { group, interface } => {
+                self.socket.join_multicast_v4(group, interface)
+            }
+            MulticastGroup::V6 { group, interface } => {
+                self.socket.join_multicast_v6(&group, interface)
+            }
+        }
+        .hand_log(|msg| error!("{msg}: group={group:?}, local_addr={}", self.local_addr))?;
+        groups.push(group);
+        Ok(())
+    }
+
+    pub(crate) fn leave(&self, group: MulticastGroup) -> GlobalResult<()> {
+        let mut groups = self.lock_groups();
+        let Some(index) = groups.iter().position(|joined| *joined == group) else {
+            return Err(GlobalError::new_sys_error(
+                "multicast group is not joined",
+                |msg| error!("{msg}: group={group:?}, local_addr={}", self.local_addr),
+            ));
+        };
+        groups.remove(index);
+        self.drop_membership(group)
+            .hand_log(|msg| error!("{msg}: group={group:?}, local_addr={}", self.local_addr))
+    }
+
+    pub(crate) fn groups(&self) -> Vec<MulticastGroup> {
+        self.lock_groups().clone()
+    }
+
+    /// Best effort: closing the socket drops any membership that fails to leave here.
+    pub(crate) fn leave_all(&self) {
+        for group in std::mem::take(&mut *self.lock_groups()) {
+            if let Err(error) = self.drop_membership(group) {
+                debug!(
+                    "leave multicast group failed: group={group:?}, local_addr={}, error={error}",
+                    self.local_addr
+                );
+            }
+        }
+    }
+
+    fn drop_membership(&self, group: MulticastGroup) -> std::io::Result<()> {
+        match group {
+            MulticastGroup::V4 { group, interface } => {
+                self.socket.leave_multicast_v4(group, interface)
+            }
+            MulticastGroup::V6 { group, interface } => {
+                self.socket.leave_multicast_v6(&group, interface)
+            }
+        }
+    }
+
+    fn lock_groups(&self) -> std::sync::MutexGuard<'_, Vec<MulticastGroup>> {
+        self.groups
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MulticastGroup, MulticastInterface, UdpMemberships, UdpSocketOptions};
+    use std::net::Ipv4Addr;
+    use std::sync::Arc;
+    use tokio::net::UdpSocket;
+
+    #[tokio::test]
+    async fn validates_groups_and_tracks_membership() {
+        let socket = Arc::new(UdpSocket::bind("0.0.0.0:0").await.unwrap());
+        let group = MulticastGroup::V4 {
+            group: Ipv4Addr::new(239, 255, 42, 1),
+            interface: Ipv4Addr::LOCALHOST,
+        };
+        let options = UdpSocketOptions {
+            broadcast: true,
+            multicast_groups: vec![group],
+            multicast_ttl: Some(2),
+            multicast_loopback: Some(true),
+            ..UdpSocketOptions::default()
+        };
+        let memberships = UdpMemberships::configure(socket.clone(), &options).unwrap();
+        assert!(socket.broadcast().unwrap());
+        assert_eq!(socket.multicast_ttl_v4().unwrap(), 2);
+        assert_eq!(memberships.groups(), vec![group]);
+        memberships.join(group).unwrap();
+        assert_eq!(memberships.groups().len(), 1);
+
+        let unicast = MulticastGroup::V4 {
+            group: Ipv4Addr::new(10, 0, 0, 1),
+            interface: Ipv4Addr::UNSPECIFIED,
+        };
+        assert!(memberships.join(unicast).is_err());
+        let v6 = MulticastGroup::V6 {
+            group: "ff02::1".parse().unwrap(),
+            interface: 0,
+        };
+        assert!(memberships.join(v6).is_err());
+
+        memberships.leave(group).unwrap();
+        assert!(memberships.leave(group).is_err());
+        assert!(memberships.groups().is_empty());
+
+        let mismatched = UdpSocketOptions {
+            multicast_interface: Some(MulticastInterface::V6(0)),
+            ..UdpSocketOptions::default()
+        };
+        assert!(UdpMemberships::configure(socket, &mismatched).is_err());
+    }
+}
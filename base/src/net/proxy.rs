@@ -0,0 +1,254 @@
+//! PROXY protocol v1 (text) and v2 (binary) headers sent by load balancers ahead of the client
+//! stream, see <https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt>.
+
+use crate::net::admission::IpCidr;
+use exception::{GlobalError, GlobalResult, GlobalResultExt};
+use log::debug;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::select;
+use tokio_util::sync::CancellationToken;
+
+const V1_PREFIX: &[u8] = b"PROXY ";
+const V1_MAX_LEN: usize = 107;
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+const V2_HEADER_LEN: usize = 16;
+const DEFAULT_HEADER_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Requires a PROXY protocol header on every accepted stream.
+///
+/// Streams whose header is malformed or does not arrive within `header_timeout` are closed
+/// before the splitter or dispatcher sees them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyProtocolOptions {
+    pub header_timeout: Duration,
+    /// Peers allowed to send a header; other peers are rejected before it is read. Empty
+    /// trusts every peer.
+    pub balancers: Vec<IpCidr>,
+}
+
+impl Default for ProxyProtocolOptions {
+    fn default() -> Self {
+        Self {
+            header_timeout: DEFAULT_HEADER_TIMEOUT,
+            balancers: Vec::new(),
+        }
+    }
+}
+
+impl ProxyProtocolOptions {
+    /// Reads the header and returns the client address it carries.
+    ///
+    /// `None` means the balancer sent a LOCAL/UNKNOWN header (a health check, or an address
+    /// family without IP addresses) and the connection keeps its socket peer address.
+    pub(crate) async fn read_header<R>(
+        &self,
+        stream: &mut R,
+        remote_addr: SocketAddr,
+        cancel: &CancellationToken,
+    ) -> GlobalResult<Option<SocketAddr>>
+    where
+        R: AsyncRead + Unpin,
+    {
+        select! {
+            biased;
+            _ = cancel.cancelled() => Err(GlobalError::new_sys_error(
+                "proxy protocol header cancelled",
+                |msg| debug!("{msg}: remote_addr={remote_addr}"),
+            )),
+            result = tokio::time::timeout(self.header_timeout, read_proxy_header(stream)) => {
+                match result {
+                    Ok(result) => result.inspect_err(|error| {
+                        debug!("proxy protocol header rejected: remote_addr={remote_addr}, error={error}")
+                    }),
+                    Err(_) => Err(GlobalError::new_sys_error(
+                        "proxy protocol header timed out",
+                        |msg| debug!("{msg}: remote_addr={remote_addr}"),
+                    )),
+                }
+            }
+        }
+    }
+}
+
+/// Reads exactly the header bytes so that the first payload byte stays in the stream.
+async fn read_proxy_header<R>(stream: &mut R) -> GlobalResult<Option<SocketAddr>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut header = [0u8; V2_HEADER_LEN];
+    read_exact(stream, &mut header[..V1_PREFIX.len()]).await?;
+    if header[..V1_PREFIX.len()] == *V1_PREFIX {
+        let mut line = header[..V1_PREFIX.len()].to_vec();
+        while !line.ends_with(b"\r\n") {
+            if line.len() == V1_MAX_LEN {
+                return Err(malformed("v1 header is too long"));
+            }
+            let mut byte = [0u8; 1];
+            read_exact(stream, &mut byte).await?;
+            line.push(byte[0]);
+        }
+        return parse_v1(&line);
+    }
+    if header[..V1_PREFIX.len()] != V2_SIGNATURE[..V1_PREFIX.len()] {
+        return Err(malformed("missing proxy protocol signature"));
+    }
+    read_exact(stream, &mut header[V1_PREFIX.len()..]).await?;
+    let body_len = usize::from(u16::from_be_bytes([header[14], header[15]]));
+    let mut body = vec![0u8; body_len];
+    read_exact(stream, &mut body).await?;
+    parse_v2(&header, &body)
+}
+
+async fn read_exact<R>(stream: &mut R, buf: &mut [u8]) -> GlobalResult<()>
+where
+    R: AsyncRead + Unpin,
+{
+    stream
+        .read_exact(buf)
+        .await
+        .map(drop)
+        .hand_log(|msg| debug!("proxy protocol header read failed: {msg}"))
+}
+
+/// Parses `PROXY TCP4|TCP6 <src> <dst> <sport> <dport>\r\n` or `PROXY UNKNOWN ...\r\n`.
+fn parse_v1(line: &[u8]) -> GlobalResult<Option<SocketAddr>> {
+    let line = line
+        .strip_suffix(b"\r\n")
+        .and_then(|line| std::str::from_utf8(line).ok())
+        .ok_or_else(|| malformed("v1 header is not a CRLF terminated text line"))?;
+    let mut fields = line.split(' ');
+    fields.next();
+    let ipv4 = match fields.next() {
+        Some("UNKNOWN") => return Ok(None),
+        Some("TCP4") => true,
+        Some("TCP6") => false,
+        _ => return Err(malformed("unsupported v1 protocol")),
+    };
+    let (Some(src), Some(_dst), Some(sport), Some(_dport), None) = (
+        fields.next(),
+        fields.next(),
+        fields.next(),
+        fields.next(),
+        fields.next(),
+    ) else {
+        return Err(malformed("v1 header has the wrong number of fields"));
+    };
+    let ip = src
+        .parse::<IpAddr>()
+        .map_err(|_| malformed("invalid v1 source address"))?;
+    if ipv4 != ip.is_ipv4() {
+        return Err(malformed("v1 source address does not match protocol"));
+    }
+    let port = sport
+        .parse::<u16>()
+        .map_err(|_| malformed("invalid v1 source port"))?;
+    Ok(Some(SocketAddr::new(ip, port)))
+}
+
+fn parse_v2(header: &[u8; V2_HEADER_LEN], body: &[u8]) -> GlobalResult<Option<SocketAddr>> {
+    if header[..12] != V2_SIGNATURE {
+        return Err(malformed("missing proxy protocol signature"));
+    }
+    match header[12] {
+        0x20 => return Ok(None),
+        0x21 => {}
+        _ => return Err(malformed("unsupported v2 version or command")),
+    }
+    // High nibble is the address family; TCP and UDP over IPv4/IPv6 carry a client address.
+    match header[13] >> 4 {
+        0x1 if body.len() >= 12 => {
+            let ip = Ipv4Addr::new(body[0], body[1], body[2], body[3]);
+            let port = u16::from_be_bytes([body[8], body[9]]);
+            Ok(Some(SocketAddr::new(ip.into(), port)))
+        }
+        0x2 if body.len() >= 36 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&body[..16]);
+            let port = u16::from_be_bytes([body[32], body[33]]);
+            Ok(Some(SocketAddr::new(Ipv6Addr::from(octets).into(), port)))
+        }
+        0x1 | 0x2 => Err(malformed("v2 address block is truncated")),
+        _ => Ok(None),
+    }
+}
+
+fn malformed(reason: &str) -> GlobalError {
+    GlobalError::new_sys_error(reason, |msg| {
+        debug!("malformed proxy protocol header: {msg}")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_v1, ProxyProtocolOptions, V2_SIGNATURE};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_util::sync::CancellationToken;
+
+    #[test]
+    fn parses_v1_addresses_and_rejects_malformed_lines() {
+        assert_eq!(
+            parse_v1(b"PROXY TCP4 192.0.2.7 198.51.100.1 5000 443\r\n").unwrap(),
+            Some("192.0.2.7:5000".parse().unwrap())
+        );
+        assert_eq!(
+            parse_v1(b"PROXY TCP6 2001:db8::7 2001:db8::1 5000 443\r\n").unwrap(),
+            Some("[2001:db8::7]:5000".parse().unwrap())
+        );
+        assert_eq!(parse_v1(b"PROXY UNKNOWN\r\n").unwrap(), None);
+        assert!(parse_v1(b"PROXY TCP4 2001:db8::7 198.51.100.1 5000 443\r\n").is_err());
+        assert!(parse_v1(b"PROXY TCP4 192.0.2.7 198.51.100.1 5000\r\n").is_err());
+        assert!(parse_v1(b"PROXY TCP4 192.0.2.7 198.51.100.1 70000 443\r\n").is_err());
+        assert!(parse_v1(b"PROXY UDP4 192.0.2.7 198.51.100.1 5000 443\r\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn reads_headers_without_consuming_payload_and_times_out() {
+        let options = ProxyProtocolOptions {
+            header_timeout: Duration::from_millis(50),
+            ..ProxyProtocolOptions::default()
+        };
+        let cancel = CancellationToken::new();
+        let balancer = "10.0.0.1:4000".parse().unwrap();
+
+        let mut v2 = V2_SIGNATURE.to_vec();
+        v2.extend_from_slice(&[
+            0x21, 0x11, 0, 12, 192, 0, 2, 9, 198, 51, 100, 1, 0x13, 0x88, 1, 187,
+        ]);
+        for (header, expected) in [
+            (
+                b"PROXY TCP4 192.0.2.7 198.51.100.1 5000 443\r\n".to_vec(),
+                Some("192.0.2.7:5000".parse().unwrap()),
+            ),
+            (v2, Some("192.0.2.9:5000".parse().unwrap())),
+            ([&V2_SIGNATURE[..], &[0x20, 0x00, 0, 0]].concat(), None),
+        ] {
+            let (mut client, mut server) = tokio::io::duplex(256);
+            client.write_all(&header).await.unwrap();
+            client.write_all(b"payload").await.unwrap();
+            let real = options
+                .read_header(&mut server, balancer, &cancel)
+                .await
+                .unwrap();
+            assert_eq!(real, expected);
+            let mut payload = [0u8; 7];
+            server.read_exact(&mut payload).await.unwrap();
+            assert_eq!(&payload, b"payload");
+        }
+
+        let (mut client, mut server) = tokio::io::duplex(256);
+        client.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+        assert!(options
+            .read_header(&mut server, balancer, &cancel)
+            .await
+            .is_err());
+        let (mut client, mut server) = tokio::io::duplex(256);
+        client.write_all(b"PROXY TCP4 192.0.2.7").await.unwrap();
+        assert!(options
+            .read_header(&mut server, balancer, &cancel)
+            .await
+            .is_err());
+    }
+}
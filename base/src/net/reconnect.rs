@@ -0,0 +1,126 @@
+use crate::net::rw::ManagedTcpConnectOptions;
+use crate::net::tls::TcpTlsConnector;
+use rand::Rng;
+use std::time::Duration;
+
+/// Exponential redial backoff with symmetric jitter.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    /// Fraction of the delay added or removed at random, clamped to `0.0..=1.0`.
+    pub jitter_ratio: f64,
+    /// Consecutive failed dials before giving up; `None` redials until closed.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter_ratio: 0.2,
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let base = self.delay_without_jitter(attempt);
+        if self.jitter_ratio <= 0.0 || base.is_zero() {
+            return base;
+        }
+        let jitter = base.as_secs_f64() * self.jitter_ratio.clamp(0.0, 1.0);
+        let delta = rand::thread_rng().gen_range(-jitter..=jitter);
+        Duration::from_secs_f64((base.as_secs_f64() + delta).max(0.0))
+    }
+
+    pub fn delay_without_jitter(&self, attempt: u32) -> Duration {
+        let exponent = i32::try_from(attempt.saturating_sub(1)).unwrap_or(i32::MAX);
+        let seconds = self.initial_delay.as_secs_f64() * self.multiplier.max(1.0).powi(exponent);
+        Duration::from_secs_f64(seconds.min(self.max_delay.as_secs_f64()))
+    }
+
+    pub fn permits(&self, attempt: u32) -> bool {
+        self.max_attempts.is_none_or(|max| attempt <= max)
+    }
+}
+
+/// Lifecycle of a reconnecting TCP client; `generation` increases with every dial.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionState {
+    Idle,
+    Connecting { generation: u64, attempt: u32 },
+    Connected { generation: u64 },
+    Disconnected { generation: u64, reason: String },
+    Stopped,
+}
+
+/// What `write` does while the client has no live connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DisconnectedWritePolicy {
+    /// Fail the write immediately.
+    #[default]
+    Reject,
+    /// Queue up to `capacity` packets and send them in order after the next connect; writes
+    /// beyond the capacity fail.
+    Buffer { capacity: usize },
+}
+
+/// Parameters for [`ManagedPacketIo::connect_tcp_reconnecting`].
+///
+/// `connect.timeout` bounds each dial; TLS, when set, is negotiated on every new connection.
+///
+/// [`ManagedPacketIo::connect_tcp_reconnecting`]: crate::net::rw::ManagedPacketIo::connect_tcp_reconnecting
+#[derive(Clone)]
+pub struct ManagedTcpReconnectOptions {
+    pub connect: ManagedTcpConnectOptions,
+    pub retry: ReconnectPolicy,
+    pub disconnected_writes: DisconnectedWritePolicy,
+    pub tls: Option<TcpTlsConnector>,
+}
+
+impl ManagedTcpReconnectOptions {
+    pub fn new(connect: ManagedTcpConnectOptions) -> Self {
+        Self {
+            connect,
+            retry: ReconnectPolicy::default(),
+            disconnected_writes: DisconnectedWritePolicy::default(),
+            tls: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReconnectPolicy;
+    use std::time::Duration;
+
+    #[test]
+    fn caps_exponential_delay_and_bounds_jitter() {
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(5),
+            multiplier: 2.0,
+            jitter_ratio: 0.0,
+            max_attempts: Some(3),
+        };
+        assert_eq!(policy.delay_without_jitter(1), Duration::from_secs(1));
+        assert_eq!(policy.delay_without_jitter(3), Duration::from_secs(4));
+        assert_eq!(policy.delay(5), Duration::from_secs(5));
+        assert!(policy.permits(3));
+        assert!(!policy.permits(4));
+
+        let jittered = ReconnectPolicy {
+            jitter_ratio: 0.5,
+            ..policy
+        };
+        for _ in 0..32 {
+            let delay = jittered.delay(2);
+            assert!(delay >= Duration::from_secs(1) && delay <= Duration::from_secs(3));
+        }
+    }
+}
@@ -1,21 +1,38 @@
+use crate::net::admission::{
+    AdmissionRejections, TcpAdmission, TcpAdmissionOptions, TcpAdmissionPermit,
+};
+use crate::net::multicast::{MulticastGroup, UdpMemberships, UdpSocketOptions};
+use crate::net::proxy::ProxyProtocolOptions;
+use crate::net::reconnect::{ConnectionState, DisconnectedWritePolicy, ManagedTcpReconnectOptions};
 use crate::net::state::Protocol;
+use crate::net::stats::{
+    PacketIoCounters, PacketIoStats, TcpConnectionCounters, TcpConnectionStats,
+};
+use crate::net::tls::{TcpTlsAcceptor, TcpTlsConnector};
+use crate::net::unix::stream_protocol;
+#[cfg(unix)]
+use crate::net::unix::{next_unix_peer_addr, UnixDatagramSocket, UnixSocketFile};
 use crate::utils::rt::GlobalRuntime;
 use bytes::{Bytes, BytesMut};
 use dashmap::DashMap;
 use exception::{GlobalError, GlobalResult, GlobalResultExt};
 use log::{debug, error, warn};
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::io::{self, IoSlice};
-use std::net::{IpAddr, SocketAddr};
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex as StdMutex, RwLock as StdRwLock, Weak};
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
-use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::net::tcp::OwnedWriteHalf;
 use tokio::net::{TcpSocket, TcpStream, UdpSocket};
 use tokio::select;
 use tokio::sync::mpsc::error::TrySendError;
-use tokio::sync::{mpsc, oneshot, Mutex, Notify, RwLock};
+use tokio::sync::{mpsc, oneshot, watch, Mutex, Notify, RwLock};
 use tokio::task::{JoinError, JoinHandle, JoinSet};
+use tokio::time::Instant;
 use tokio_util::sync::CancellationToken;
 
 pub trait PacketDispatcher: Send + Sync + 'static {
@@ -37,16 +54,112 @@ pub trait PacketSplitter: Send + 'static {
         F: FnMut(Bytes) -> GlobalResult<()>;
 }
 
-const MAX_BUF_SIZE: usize = 2 * 1024 * 1024;
+/// Stream listener served by the TCP connection pipeline.
+trait PacketStreamListener: Send + 'static {
+    type Stream: PacketStream;
+    const PROTOCOL: Protocol;
+
+    fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<io::Result<(Self::Stream, SocketAddr)>>;
+}
+
+trait PacketStream: AsyncRead + AsyncWrite + Send + Unpin + 'static {
+    type ReadHalf: AsyncRead + Send + Unpin + 'static;
+    type WriteHalf: AsyncWrite + Send + Unpin + 'static;
+
+    fn split_owned(self) -> (Self::ReadHalf, Self::WriteHalf);
+}
+
+/// Datagram socket served by the UDP receive loop.
+trait PacketDatagramSocket: Send + Sync + 'static {
+    const PROTOCOL: Protocol;
+
+    fn poll_recv_from(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<SocketAddr>>;
+}
+
+impl PacketStreamListener for tokio::net::TcpListener {
+    type Stream = TcpStream;
+    const PROTOCOL: Protocol = Protocol::TCP;
+
+    fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<io::Result<(TcpStream, SocketAddr)>> {
+        tokio::net::TcpListener::poll_accept(self, cx)
+    }
+}
+
+impl PacketStream for TcpStream {
+    type ReadHalf = tokio::net::tcp::OwnedReadHalf;
+    type WriteHalf = OwnedWriteHalf;
+
+    fn split_owned(self) -> (Self::ReadHalf, Self::WriteHalf) {
+        self.into_split()
+    }
+}
+
+impl PacketDatagramSocket for UdpSocket {
+    const PROTOCOL: Protocol = Protocol::UDP;
+
+    fn poll_recv_from(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<SocketAddr>> {
+        UdpSocket::poll_recv_from(self, cx, buf)
+    }
+}
+
+#[cfg(unix)]
+impl PacketStreamListener for tokio::net::UnixListener {
+    type Stream = tokio::net::UnixStream;
+    const PROTOCOL: Protocol = Protocol::UnixStream;
+
+    fn poll_accept(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<(tokio::net::UnixStream, SocketAddr)>> {
+        let (stream, _) = ready!(tokio::net::UnixListener::poll_accept(self, cx))?;
+        Poll::Ready(Ok((stream, next_unix_peer_addr())))
+    }
+}
+
+#[cfg(unix)]
+impl PacketStream for tokio::net::UnixStream {
+    type ReadHalf = tokio::net::unix::OwnedReadHalf;
+    type WriteHalf = tokio::net::unix::OwnedWriteHalf;
+
+    fn split_owned(self) -> (Self::ReadHalf, Self::WriteHalf) {
+        self.into_split()
+    }
+}
+
+#[cfg(unix)]
+impl PacketDatagramSocket for UnixDatagramSocket {
+    const PROTOCOL: Protocol = Protocol::UnixDatagram;
+
+    fn poll_recv_from(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<SocketAddr>> {
+        UnixDatagramSocket::poll_recv_from(self, cx, buf)
+    }
+}
+
+pub(crate) const MAX_BUF_SIZE: usize = 2 * 1024 * 1024;
 const TCP_READ_BUF_SIZE: usize = 64 * 1024;
 const TCP_MIN_READ_SPARE: usize = 4 * 1024;
 const UDP_MAX_DATAGRAM_SIZE: usize = u16::MAX as usize;
 const TCP_ACCEPT_ERROR_BACKOFF_MIN: std::time::Duration = std::time::Duration::from_millis(10);
 const TCP_ACCEPT_ERROR_BACKOFF_MAX: std::time::Duration = std::time::Duration::from_secs(1);
+const TCP_IDLE_CHECK_MIN: Duration = Duration::from_millis(10);
 
 const TCP_WRITE_QUEUE_SIZE: usize = 1024;
 pub const INLINE_PREFIX_CAPACITY: usize = 16;
 
+type BoxedTcpWriteHalf = Box<dyn AsyncWrite + Send + Unpin>;
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct InlinePrefix {
     len: u8,
@@ -184,6 +297,18 @@ impl EncodedPacket {
     pub fn with_inline_prefix(prefix: InlinePrefix, payload: Bytes) -> Self {
         Self::InlinePrefix { prefix, payload }
     }
+
+    /// Encoded size on the wire.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Single(data) => data.len(),
+            Self::InlinePrefix { prefix, payload } => prefix.len() + payload.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 pub trait PacketEncoder: Send + Sync + 'static {
@@ -242,6 +367,196 @@ impl TcpWriteMode {
     }
 }
 
+/// Endpoint-wide settings shared by every connection accepted on a packet I/O endpoint.
+#[derive(Clone, Default)]
+pub struct PacketIoOptions {
+    pub tcp_write_mode: TcpWriteMode,
+    /// Terminates TLS on accepted TCP connections before the splitter sees any data.
+    pub tls: Option<TcpTlsAcceptor>,
+    pub tcp_idle: TcpIdleOptions,
+    pub tcp_admission: TcpAdmissionOptions,
+    /// Reads a PROXY protocol header from every accepted stream and uses the client address it
+    /// carries for admission, dispatch and the writer; header-less streams are rejected.
+    pub proxy_protocol: Option<ProxyProtocolOptions>,
+    /// Broadcast and multicast settings of the UDP socket; managed endpoints leave every joined
+    /// group on [`ManagedPacketIo::close_and_wait`].
+    pub udp: UdpSocketOptions,
+}
+
+/// Idle supervision applied to every TCP connection of an endpoint, accepted or connected.
+///
+/// A connection that exceeds a timeout is closed like a peer EOF: its writer is unregistered and
+/// [`PacketDispatcher::close`] is notified.
+#[derive(Clone, Default)]
+pub struct TcpIdleOptions {
+    /// Closes the connection when nothing has been read for this long.
+    pub read_timeout: Option<Duration>,
+    /// Closes the connection when nothing has been written for this long.
+    pub write_timeout: Option<Duration>,
+    /// Writes a ping whenever the connection has not written anything for one interval.
+    pub heartbeat: Option<TcpHeartbeat>,
+}
+
+impl TcpIdleOptions {
+    fn is_enabled(&self) -> bool {
+        self.read_timeout.is_some() || self.write_timeout.is_some() || self.heartbeat.is_some()
+    }
+}
+
+/// Heartbeat hook; the returned payload goes through the endpoint encoder like any other write.
+#[derive(Clone)]
+pub struct TcpHeartbeat {
+    pub interval: Duration,
+    pub ping: Arc<dyn Fn(SocketAddr) -> Bytes + Send + Sync>,
+}
+
+impl TcpHeartbeat {
+    pub fn new<F>(interval: Duration, ping: F) -> Self
+    where
+        F: Fn(SocketAddr) -> Bytes + Send + Sync + 'static,
+    {
+        Self {
+            interval,
+            ping: Arc::new(ping),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+struct TcpIdleSupervisor {
+    options: TcpIdleOptions,
+    reaped: Arc<AtomicUsize>,
+}
+
+impl TcpIdleSupervisor {
+    fn new(options: TcpIdleOptions) -> Self {
+        Self {
+            options,
+            reaped: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    fn take_reaped(&self) -> usize {
+        self.reaped.swap(0, Ordering::AcqRel)
+    }
+}
+
+/// Endpoint settings handed to every accepted TCP connection task.
+#[derive(Clone)]
+struct TcpAcceptOptions {
+    tls: Option<TcpTlsAcceptor>,
+    idle: TcpIdleSupervisor,
+    admission: Arc<TcpAdmission>,
+    proxy_protocol: Option<ProxyProtocolOptions>,
+}
+
+struct TcpStreamOptions {
+    idle: TcpIdleSupervisor,
+    /// Signalled once the connection writer is registered.
+    ready: Option<oneshot::Sender<()>>,
+}
+
+/// Last read/write activity of one connection, in milliseconds since `started`.
+struct TcpActivity {
+    started: Instant,
+    read_ms: AtomicU64,
+    write_ms: AtomicU64,
+}
+
+impl TcpActivity {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            read_ms: AtomicU64::new(0),
+            write_ms: AtomicU64::new(0),
+        }
+    }
+
+    fn now_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    fn mark(&self, last: &AtomicU64) {
+        last.store(self.now_ms(), Ordering::Relaxed);
+    }
+
+    fn idle(&self, last: &AtomicU64) -> Duration {
+        Duration::from_millis(self.now_ms().saturating_sub(last.load(Ordering::Relaxed)))
+    }
+}
+
+struct ActivityReader<R> {
+    inner: R,
+    activity: Arc<TcpActivity>,
+}
+
+impl<R> AsyncRead for ActivityReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if matches!(poll, Poll::Ready(Ok(()))) && buf.filled().len() > filled {
+            self.activity.mark(&self.activity.read_ms);
+        }
+        poll
+    }
+}
+
+struct ActivityWriter<W> {
+    inner: W,
+    activity: Arc<TcpActivity>,
+}
+
+impl<W> ActivityWriter<W> {
+    fn marked(&self, poll: Poll<io::Result<usize>>) -> Poll<io::Result<usize>> {
+        if matches!(poll, Poll::Ready(Ok(written)) if written > 0) {
+            self.activity.mark(&self.activity.write_ms);
+        }
+        poll
+    }
+}
+
+impl<W> AsyncWrite for ActivityWriter<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        self.marked(poll)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+        self.marked(poll)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
 pub struct QueuedTcpSink<E = RawPacketEncoder>
 where
     E: PacketEncoder,
@@ -250,6 +565,7 @@ where
     sender: mpsc::Sender<EncodedPacket>,
     encoder: Arc<E>,
     cancel: CancellationToken,
+    counters: Arc<TcpConnectionCounters>,
 }
 
 impl<E> Clone for QueuedTcpSink<E>
@@ -262,6 +578,7 @@ where
             sender: self.sender.clone(),
             encoder: self.encoder.clone(),
             cancel: self.cancel.clone(),
+            counters: self.counters.clone(),
         }
     }
 }
@@ -275,12 +592,14 @@ where
         sender: mpsc::Sender<EncodedPacket>,
         encoder: Arc<E>,
         cancel: CancellationToken,
+        counters: Arc<TcpConnectionCounters>,
     ) -> Self {
         Self {
             remote_addr,
             sender,
             encoder,
             cancel,
+            counters,
         }
     }
 
@@ -326,10 +645,13 @@ where
         let packet = self.encoder.encode_tcp(data)?;
         match self.sender.try_send(packet) {
             Ok(_) => Ok(()),
-            Err(TrySendError::Full(_)) => Err(GlobalError::new_sys_error(
-                "tcp write channel is full",
-                |msg| error!("{msg}: remote_addr={}", self.remote_addr),
-            )),
+            Err(TrySendError::Full(_)) => {
+                self.counters.record_queue_full();
+                Err(GlobalError::new_sys_error(
+                    "tcp write channel is full",
+                    |msg| error!("{msg}: remote_addr={}", self.remote_addr),
+                ))
+            }
             Err(TrySendError::Closed(_)) => Err(GlobalError::new_sys_error(
                 "tcp write channel closed",
                 |msg| error!("{msg}: remote_addr={}", self.remote_addr),
@@ -343,9 +665,10 @@ where
     E: PacketEncoder,
 {
     remote_addr: SocketAddr,
-    stream: Arc<Mutex<OwnedWriteHalf>>,
+    stream: Arc<Mutex<BoxedTcpWriteHalf>>,
     encoder: Arc<E>,
     cancel: CancellationToken,
+    counters: Arc<TcpConnectionCounters>,
 }
 
 impl<E> Clone for DirectTcpSink<E>
@@ -358,6 +681,7 @@ where
             stream: self.stream.clone(),
             encoder: self.encoder.clone(),
             cancel: self.cancel.clone(),
+            counters: self.counters.clone(),
         }
     }
 }
@@ -368,15 +692,17 @@ where
 {
     fn new(
         remote_addr: SocketAddr,
-        stream: OwnedWriteHalf,
+        stream: BoxedTcpWriteHalf,
         encoder: Arc<E>,
         cancel: CancellationToken,
+        counters: Arc<TcpConnectionCounters>,
     ) -> Self {
         Self {
             remote_addr,
             stream: Arc::new(Mutex::new(stream)),
             encoder,
             cancel,
+            counters,
         }
     }
 
@@ -399,6 +725,7 @@ where
             }));
         }
         let packet = self.encoder.encode_tcp(data)?;
+        let packet_len = packet.len();
         let mut stream = self.stream.lock().await;
         if self.cancel.is_cancelled() {
             return Err(GlobalError::new_sys_error("tcp sink is closed", |msg| {
@@ -411,12 +738,17 @@ where
                 "tcp sink is closed",
                 |msg| debug!("{msg}: remote_addr={}", self.remote_addr),
             )),
-            result = write_encoded_packet(&mut *stream, packet) => {
+            result = async {
+                write_encoded_packet(&mut *stream, packet).await?;
+                // TLS keeps ciphertext buffered in the session until flushed.
+                stream.flush().await
+            } => {
                 result.hand_log(|msg| error!("{msg}: remote_addr={}", self.remote_addr))
             }
         };
-        if result.is_err() {
-            self.cancel.cancel();
+        match result {
+            Ok(()) => self.counters.record_tx(packet_len),
+            Err(_) => self.cancel.cancel(),
         }
         result
     }
@@ -480,6 +812,13 @@ where
         }
     }
 
+    fn counters(&self) -> &Arc<TcpConnectionCounters> {
+        match self {
+            Self::Queued(sink) => &sink.counters,
+            Self::Direct(sink) => &sink.counters,
+        }
+    }
+
     pub fn try_write(&self, data: Bytes) -> GlobalResult<()> {
         match self {
             Self::Queued(sink) => sink.try_write(data),
@@ -510,6 +849,8 @@ where
     E: PacketEncoder,
 {
     udp_socket: Arc<RwLock<Option<Arc<UdpSocket>>>>,
+    #[cfg(unix)]
+    unix_datagram: Arc<RwLock<Option<Arc<UnixDatagramSocket>>>>,
     tcp_writers: Arc<DashMap<SocketAddr, RegisteredTcpSink<E>>>,
     tcp_writer_addrs_by_ip: Arc<DashMap<IpAddr, Vec<TcpWriterRegistration>>>,
     tcp_writer_registry_lock: Arc<StdRwLock<()>>,
@@ -518,6 +859,7 @@ where
     encoder: Arc<E>,
     tcp_write_mode: TcpWriteMode,
     closed: CancellationToken,
+    stats: Arc<PacketIoCounters>,
 }
 
 impl<E> Clone for PacketWriter<E>
@@ -527,6 +869,8 @@ where
     fn clone(&self) -> Self {
         Self {
             udp_socket: self.udp_socket.clone(),
+            #[cfg(unix)]
+            unix_datagram: self.unix_datagram.clone(),
             tcp_writers: self.tcp_writers.clone(),
             tcp_writer_addrs_by_ip: self.tcp_writer_addrs_by_ip.clone(),
             tcp_writer_registry_lock: self.tcp_writer_registry_lock.clone(),
@@ -535,6 +879,7 @@ where
             encoder: self.encoder.clone(),
             tcp_write_mode: self.tcp_write_mode,
             closed: self.closed.clone(),
+            stats: self.stats.clone(),
         }
     }
 }
@@ -550,6 +895,8 @@ where
     ) -> Self {
         Self {
             udp_socket: Arc::new(RwLock::new(udp_socket)),
+            #[cfg(unix)]
+            unix_datagram: Arc::new(RwLock::new(None)),
             tcp_writers: Arc::new(DashMap::new()),
             tcp_writer_addrs_by_ip: Arc::new(DashMap::new()),
             tcp_writer_registry_lock: Arc::new(StdRwLock::new(())),
@@ -558,6 +905,7 @@ where
             encoder,
             tcp_write_mode,
             closed: CancellationToken::new(),
+            stats: Arc::new(PacketIoCounters::default()),
         }
     }
 
@@ -581,12 +929,19 @@ where
                         return Err(packet_writer_closed());
                     }
                     result = socket.send_to(packet.as_ref(), remote_addr) => {
-                        result.hand_log(|msg| error!("{msg}: remote_addr={remote_addr}"))?;
+                        let sent = result.hand_log(|msg| error!("{msg}: remote_addr={remote_addr}"))?;
+                        self.stats.record_udp_tx(sent);
                     }
                 }
                 Ok(())
             }
-            Protocol::TCP => self.tcp_sink_or_err(remote_addr)?.write(data).await,
+            Protocol::TCP | Protocol::UnixStream => {
+                self.tcp_sink_or_err(remote_addr)?.write(data).await
+            }
+            Protocol::UnixDatagram => {
+                let packet = self.encoder.encode_udp(data)?;
+                self.send_unix_datagram(packet.as_ref(), remote_addr).await
+            }
             Protocol::ALL => Err(GlobalError::new_sys_error(
                 "protocol ALL cannot be used to write a packet",
                 |msg| error!("{msg}"),
@@ -613,15 +968,17 @@ where
                         return Err(packet_writer_closed());
                     }
                     result = socket.send_to(data, remote_addr) => {
-                        result.hand_log(|msg| error!("{msg}: remote_addr={remote_addr}"))?;
+                        let sent = result.hand_log(|msg| error!("{msg}: remote_addr={remote_addr}"))?;
+                        self.stats.record_udp_tx(sent);
                     }
                 }
                 Ok(())
             }
-            Protocol::TCP => Err(GlobalError::new_sys_error(
-                "write_slice_to cannot write TCP without copy; use write_to with Bytes",
-                |msg| error!("{msg}: remote_addr={remote_addr}"),
+            Protocol::TCP | Protocol::UnixStream => Err(GlobalError::new_sys_error(
+                "write_slice_to cannot write a stream without copy; use write_to with Bytes",
+                |msg| error!("{msg}: remote_addr={remote_addr}, protocol={protocol}"),
             )),
+            Protocol::UnixDatagram => self.send_unix_datagram(data, remote_addr).await,
             Protocol::ALL => Err(GlobalError::new_sys_error(
                 "protocol ALL cannot be used to write a packet",
                 |msg| error!("{msg}"),
@@ -657,12 +1014,19 @@ where
                 let socket = socket.as_ref().ok_or_else(|| {
                     GlobalError::new_sys_error("udp socket is not available", |msg| error!("{msg}"))
                 })?;
-                socket
+                let sent = socket
                     .try_send_to(packet.as_ref(), remote_addr)
                     .hand_log(|msg| error!("{msg}: remote_addr={remote_addr}"))?;
+                self.stats.record_udp_tx(sent);
                 Ok(())
             }
-            Protocol::TCP => self.tcp_sink_or_err(remote_addr)?.try_write(data),
+            Protocol::TCP | Protocol::UnixStream => {
+                self.tcp_sink_or_err(remote_addr)?.try_write(data)
+            }
+            Protocol::UnixDatagram => {
+                let packet = self.encoder.encode_udp(data)?;
+                self.try_send_unix_datagram(packet.as_ref(), remote_addr)
+            }
             Protocol::ALL => Err(GlobalError::new_sys_error(
                 "protocol ALL cannot be used to write a packet",
                 |msg| error!("{msg}"),
@@ -674,6 +1038,64 @@ where
         self.tcp_write_mode
     }
 
+    /// Socket path of a Unix datagram peer that has sent to this endpoint.
+    #[cfg(unix)]
+    pub fn unix_peer_path(&self, remote_addr: &SocketAddr) -> Option<std::path::PathBuf> {
+        let socket = self.unix_datagram.try_read().ok()?;
+        socket.as_ref()?.peer_path(remote_addr)
+    }
+
+    #[cfg(unix)]
+    async fn send_unix_datagram(&self, data: &[u8], remote_addr: SocketAddr) -> GlobalResult<()> {
+        let socket = self.unix_datagram.read().await;
+        let socket = socket.as_ref().ok_or_else(unix_datagram_unavailable)?;
+        select! {
+            biased;
+            _ = self.closed.cancelled() => Err(packet_writer_closed()),
+            result = socket.send_to(data, remote_addr) => {
+                self.stats.record_udp_tx(result?);
+                Ok(())
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn try_send_unix_datagram(&self, data: &[u8], remote_addr: SocketAddr) -> GlobalResult<()> {
+        let socket = self.unix_datagram.try_read().map_err(|_| {
+            GlobalError::new_sys_error("unix datagram socket is closing", |msg| error!("{msg}"))
+        })?;
+        let socket = socket.as_ref().ok_or_else(unix_datagram_unavailable)?;
+        self.stats
+            .record_udp_tx(socket.try_send_to(data, remote_addr)?);
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    async fn send_unix_datagram(&self, _data: &[u8], _remote_addr: SocketAddr) -> GlobalResult<()> {
+        Err(unix_datagram_unavailable())
+    }
+
+    #[cfg(not(unix))]
+    fn try_send_unix_datagram(&self, _data: &[u8], _remote_addr: SocketAddr) -> GlobalResult<()> {
+        Err(unix_datagram_unavailable())
+    }
+
+    /// Endpoint-wide traffic counters shared by every clone of this writer.
+    pub fn stats(&self) -> PacketIoStats {
+        self.stats.snapshot()
+    }
+
+    /// Counters of the currently registered TCP peer; they are discarded when the peer closes.
+    pub fn tcp_connection_stats(&self, remote_addr: &SocketAddr) -> Option<TcpConnectionStats> {
+        self.tcp_writers
+            .get(remote_addr)
+            .map(|item| item.value().sink.counters().snapshot(*remote_addr))
+    }
+
+    fn new_connection_counters(&self) -> Arc<TcpConnectionCounters> {
+        TcpConnectionCounters::new(self.stats.clone())
+    }
+
     pub fn tcp_sink(&self, remote_addr: &SocketAddr) -> Option<TcpPacketSink<E>> {
         self.tcp_writers
             .get(remote_addr)
@@ -742,7 +1164,12 @@ where
         sender: mpsc::Sender<EncodedPacket>,
         cancel: CancellationToken,
     ) {
-        self.insert_registered_tcp_writer(remote_addr, sender, cancel);
+        self.insert_registered_tcp_writer(
+            remote_addr,
+            sender,
+            cancel,
+            self.new_connection_counters(),
+        );
     }
 
     fn insert_registered_tcp_writer(
@@ -750,12 +1177,14 @@ where
         remote_addr: SocketAddr,
         sender: mpsc::Sender<EncodedPacket>,
         cancel: CancellationToken,
+        counters: Arc<TcpConnectionCounters>,
     ) -> Option<TcpWriterRegistration> {
         let sink = TcpPacketSink::Queued(QueuedTcpSink::new(
             remote_addr,
             sender,
             self.encoder.clone(),
             cancel,
+            counters,
         ));
         self.insert_tcp_sink(remote_addr, sink)
     }
@@ -766,20 +1195,27 @@ where
         stream: OwnedWriteHalf,
         cancel: CancellationToken,
     ) {
-        self.insert_registered_direct_tcp_writer(remote_addr, stream, cancel);
+        self.insert_registered_direct_tcp_writer(
+            remote_addr,
+            Box::new(stream),
+            cancel,
+            self.new_connection_counters(),
+        );
     }
 
     fn insert_registered_direct_tcp_writer(
         &self,
         remote_addr: SocketAddr,
-        stream: OwnedWriteHalf,
+        stream: BoxedTcpWriteHalf,
         cancel: CancellationToken,
+        counters: Arc<TcpConnectionCounters>,
     ) -> Option<TcpWriterRegistration> {
         let sink = TcpPacketSink::Direct(DirectTcpSink::new(
             remote_addr,
             stream,
             self.encoder.clone(),
             cancel,
+            counters,
         ));
         self.insert_tcp_sink(remote_addr, sink)
     }
@@ -900,6 +1336,8 @@ where
             }
         }
         self.udp_socket.write().await.take();
+        #[cfg(unix)]
+        self.unix_datagram.write().await.take();
 
         match close_error {
             Some(error) => Err(error),
@@ -908,6 +1346,12 @@ where
     }
 }
 
+fn unix_datagram_unavailable() -> GlobalError {
+    GlobalError::new_sys_error("unix datagram socket is not available", |msg| {
+        error!("{msg}")
+    })
+}
+
 fn packet_writer_closed() -> GlobalError {
     GlobalError::new_sys_error("packet writer is closed", |_| {})
 }
@@ -935,6 +1379,12 @@ pub struct NetworkCloseReport {
     pub failed: usize,
     pub panicked: usize,
     pub remaining: usize,
+    /// TCP connections closed by [`TcpIdleOptions`] supervision; informational, also counted above.
+    /// Unmanaged endpoints have no close report and count them in
+    /// [`PacketIoStats::tcp_idle_reaped`] only.
+    pub idle_reaped: usize,
+    /// Accepted TCP streams dropped by [`TcpAdmissionOptions`] before a task was spawned.
+    pub rejected: usize,
 }
 
 /// Connection parameters for a TCP peer actively opened by a managed endpoint.
@@ -1015,6 +1465,13 @@ where
             .await
     }
 
+    /// Counters of this connection while it is registered with the endpoint writer.
+    pub fn stats(&self) -> Option<TcpConnectionStats> {
+        self.inner
+            .writer
+            .tcp_connection_stats(&self.inner.remote_addr)
+    }
+
     /// Cancels this connection and waits for its read/write tasks to exit.
     pub async fn close_and_wait(&self) -> GlobalResult<NetworkCloseReport> {
         let mut state = self.inner.close_state.lock().await;
@@ -1074,98 +1531,417 @@ where
     }
 }
 
-impl NetworkCloseReport {
-    /// Returns `true` when every owned task reached a terminal state without failure.
-    pub fn is_complete(&self) -> bool {
-        self.failed == 0 && self.panicked == 0 && self.remaining == 0
-    }
-
-    fn merge(&mut self, other: ManagedTaskReport) {
-        self.completed += other.completed;
-        self.cancelled += other.cancelled;
-        self.failed += other.failed;
-        self.panicked += other.panicked;
-        self.remaining += other.remaining;
-    }
-
-    fn merge_network(&mut self, other: Self) {
-        self.completed += other.completed;
-        self.cancelled += other.cancelled;
-        self.failed += other.failed;
-        self.panicked += other.panicked;
-        self.remaining += other.remaining;
-    }
-}
-
-#[derive(Default)]
-struct ManagedTaskReport {
-    completed: usize,
-    cancelled: usize,
-    failed: usize,
-    panicked: usize,
-    remaining: usize,
-}
-
-impl ManagedTaskReport {
-    fn merge(&mut self, other: Self) {
-        self.completed += other.completed;
-        self.cancelled += other.cancelled;
-        self.failed += other.failed;
-        self.panicked += other.panicked;
-        self.remaining += other.remaining;
-    }
-}
-
-struct ManagedCloseState {
-    root_task: Option<JoinHandle<ManagedTaskReport>>,
-    report: NetworkCloseReport,
-    writer_closed: bool,
-    completed: bool,
-    failure_logged: bool,
+/// Packets waiting for a reconnecting client and the sink of its live connection.
+///
+/// `sink` is only set once `pending` has been flushed, so direct writes never overtake queued
+/// ones.
+struct ReconnectOutbox<E>
+where
+    E: PacketEncoder,
+{
+    sink: Option<TcpPacketSink<E>>,
+    pending: VecDeque<Bytes>,
+    stopped: bool,
 }
 
-/// Owns a packet writer and the lifecycle of its TCP/UDP receive tasks.
-///
-/// Dropping this value requests cancellation but does not wait for resource release. Owners that
-/// require deterministic listener release must call [`ManagedPacketIo::close_and_wait`].
-#[must_use = "managed packet I/O must be retained and closed explicitly"]
-pub struct ManagedPacketIo<E = RawPacketEncoder>
+struct ManagedReconnectingTcpClientInner<E>
 where
     E: PacketEncoder,
 {
-    writer: PacketWriter<E>,
-    cancel: CancellationToken,
-    connecting_tcp_peers: StdMutex<HashSet<SocketAddr>>,
-    active_connections: Arc<StdMutex<HashMap<u64, ManagedTcpConnection<E>>>>,
-    completed_connection_report: Arc<StdMutex<NetworkCloseReport>>,
-    next_connection_id: AtomicU64,
-    close_state: Mutex<ManagedCloseState>,
+    connection: ManagedTcpConnection<E>,
+    disconnected_writes: DisconnectedWritePolicy,
+    outbox: Arc<StdMutex<ReconnectOutbox<E>>>,
+    state: watch::Receiver<ConnectionState>,
 }
 
-struct TcpConnectReservation<'a> {
-    remote_addr: SocketAddr,
-    connecting_tcp_peers: &'a StdMutex<HashSet<SocketAddr>>,
+/// An outbound TCP client of a [`ManagedPacketIo`] endpoint that redials after every disconnect.
+#[must_use = "reconnecting TCP client must be retained and closed explicitly"]
+pub struct ManagedReconnectingTcpClient<E = RawPacketEncoder>
+where
+    E: PacketEncoder,
+{
+    inner: Arc<ManagedReconnectingTcpClientInner<E>>,
 }
 
-impl Drop for TcpConnectReservation<'_> {
-    fn drop(&mut self) {
-        lock_unpoisoned(self.connecting_tcp_peers).remove(&self.remote_addr);
+impl<E> Clone for ManagedReconnectingTcpClient<E>
+where
+    E: PacketEncoder,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
     }
 }
 
-impl<E> ManagedPacketIo<E>
+impl<E> ManagedReconnectingTcpClient<E>
 where
     E: PacketEncoder,
 {
-    /// Returns a clone of the writer bound to this managed endpoint.
-    pub fn writer(&self) -> PacketWriter<E> {
-        self.writer.clone()
+    pub fn remote_addr(&self) -> SocketAddr {
+        self.inner.connection.remote_addr()
     }
 
-    fn reserve_tcp_connect(
+    /// Receiver of connection state changes; it reports [`ConnectionState::Stopped`] last.
+    pub fn state(&self) -> watch::Receiver<ConnectionState> {
+        self.inner.state.clone()
+    }
+
+    /// Writes to the live connection, or applies the [`DisconnectedWritePolicy`] without one.
+    ///
+    /// A write already handed to a connection that then breaks fails and is not retried.
+    pub async fn write(&self, data: Bytes) -> GlobalResult<()> {
+        let remote_addr = self.remote_addr();
+        let sink = {
+            let mut outbox = lock_unpoisoned(&self.inner.outbox);
+            if outbox.stopped {
+                return Err(GlobalError::new_sys_error(
+                    "reconnecting tcp client is stopped",
+                    |msg| debug!("{msg}: remote_addr={remote_addr}"),
+                ));
+            }
+            match (outbox.sink.clone(), self.inner.disconnected_writes) {
+                (Some(sink), _) => sink,
+                (None, DisconnectedWritePolicy::Buffer { capacity })
+                    if outbox.pending.len() < capacity =>
+                {
+                    outbox.pending.push_back(data);
+                    return Ok(());
+                }
+                (None, DisconnectedWritePolicy::Buffer { .. }) => {
+                    return Err(GlobalError::new_sys_error(
+                        "reconnecting tcp client buffer is full",
+                        |msg| debug!("{msg}: remote_addr={remote_addr}"),
+                    ));
+                }
+                (None, DisconnectedWritePolicy::Reject) => {
+                    return Err(GlobalError::new_sys_error(
+                        "reconnecting tcp client is disconnected",
+                        |msg| debug!("{msg}: remote_addr={remote_addr}"),
+                    ));
+                }
+            }
+        };
+        sink.write(data).await
+    }
+
+    /// Stops redialing, closes the live connection and waits for the supervisor to exit.
+    pub async fn close_and_wait(&self) -> GlobalResult<NetworkCloseReport> {
+        self.inner.connection.close_and_wait().await
+    }
+}
+
+struct ReconnectSupervisor<E>
+where
+    E: PacketEncoder,
+{
+    options: ManagedTcpReconnectOptions,
+    writer: PacketWriter<E>,
+    idle: TcpIdleSupervisor,
+    outbox: Arc<StdMutex<ReconnectOutbox<E>>>,
+    state: watch::Sender<ConnectionState>,
+    _reservation: TcpConnectReservation,
+}
+
+impl<E> ReconnectSupervisor<E>
+where
+    E: PacketEncoder,
+{
+    /// Sends queued packets in order, then opens the client for direct writes.
+    async fn flush(&self, generation: u64) {
+        let remote_addr = self.options.connect.remote_addr;
+        let Some(sink) = self.writer.tcp_sink(&remote_addr) else {
+            return;
+        };
+        loop {
+            let packet = {
+                let mut outbox = lock_unpoisoned(&self.outbox);
+                match outbox.pending.pop_front() {
+                    Some(packet) => packet,
+                    None => {
+                        outbox.sink = Some(sink);
+                        break;
+                    }
+                }
+            };
+            if let Err(error) = sink.write(packet.clone()).await {
+                lock_unpoisoned(&self.outbox).pending.push_front(packet);
+                debug!("reconnecting tcp flush failed: remote_addr={remote_addr}, error={error}");
+                return;
+            }
+        }
+        let _ = self.state.send(ConnectionState::Connected { generation });
+    }
+}
+
+async fn run_reconnecting_tcp_client<D, S, E>(
+    supervisor: ReconnectSupervisor<E>,
+    cancel: CancellationToken,
+    dispatcher: Arc<D>,
+) -> GlobalResult<()>
+where
+    D: PacketDispatcher,
+    S: PacketSplitter + Default,
+    E: PacketEncoder,
+{
+    let remote_addr = supervisor.options.connect.remote_addr;
+    let retry = &supervisor.options.retry;
+    let mut generation = 0u64;
+    let mut attempt = 0u32;
+    while !cancel.is_cancelled() {
+        attempt = attempt.saturating_add(1);
+        if !retry.permits(attempt) {
+            warn!(
+                "reconnecting tcp client gave up after {} attempts: remote_addr={remote_addr}",
+                attempt - 1
+            );
+            break;
+        }
+        generation = generation.saturating_add(1);
+        let _ = supervisor.state.send(ConnectionState::Connecting {
+            generation,
+            attempt,
+        });
+        let result = match dial_tcp(&supervisor.options.connect, &cancel).await {
+            Ok(stream) => {
+                attempt = 0;
+                run_reconnect_generation::<D, S, E>(
+                    &supervisor,
+                    stream,
+                    generation,
+                    cancel.child_token(),
+                    dispatcher.clone(),
+                )
+                .await
+            }
+            Err(error) => Err(error),
+        };
+        if cancel.is_cancelled() {
+            break;
+        }
+        let reason = result.err().map_or_else(
+            || "connection closed".to_string(),
+            |error| error.to_string(),
+        );
+        debug!("reconnecting tcp client disconnected: remote_addr={remote_addr}, reason={reason}");
+        let _ = supervisor
+            .state
+            .send(ConnectionState::Disconnected { generation, reason });
+        select! {
+            biased;
+            _ = cancel.cancelled() => break,
+            _ = tokio::time::sleep(retry.delay(attempt.max(1))) => {}
+        }
+    }
+
+    let dropped = {
+        let mut outbox = lock_unpoisoned(&supervisor.outbox);
+        outbox.sink = None;
+        outbox.stopped = true;
+        std::mem::take(&mut outbox.pending).len()
+    };
+    if dropped > 0 {
+        debug!("reconnecting tcp client stopped with {dropped} unsent packets: remote_addr={remote_addr}");
+    }
+    let _ = supervisor.state.send(ConnectionState::Stopped);
+    Ok(())
+}
+
+async fn run_reconnect_generation<D, S, E>(
+    supervisor: &ReconnectSupervisor<E>,
+    stream: TcpStream,
+    generation: u64,
+    cancel: CancellationToken,
+    dispatcher: Arc<D>,
+) -> GlobalResult<()>
+where
+    D: PacketDispatcher,
+    S: PacketSplitter + Default,
+    E: PacketEncoder,
+{
+    let remote_addr = supervisor.options.connect.remote_addr;
+    let (ready_tx, ready_rx) = oneshot::channel();
+    let stream_options = TcpStreamOptions {
+        idle: supervisor.idle.clone(),
+        ready: Some(ready_tx),
+    };
+    let stats = supervisor.writer.stats.clone();
+    let run = async {
+        match supervisor.options.tls.as_ref() {
+            None => {
+                PacketIoCounters::incr(&stats.tcp_connected);
+                run_managed_tcp_connection::<D, S, E, _>(
+                    stream,
+                    remote_addr,
+                    cancel.clone(),
+                    dispatcher,
+                    supervisor.writer.clone(),
+                    stream_options,
+                )
+                .await
+            }
+            Some(tls) => {
+                let stream = tls.connect(stream, remote_addr, &cancel).await?;
+                PacketIoCounters::incr(&stats.tcp_connected);
+                let (read_half, write_half) = tokio::io::split(stream);
+                run_managed_tcp_stream::<D, S, E, _, _>(
+                    read_half,
+                    write_half,
+                    remote_addr,
+                    cancel.clone(),
+                    dispatcher,
+                    supervisor.writer.clone(),
+                    stream_options,
+                )
+                .await
+            }
+        }
+    };
+    let (result, ()) = tokio::join!(run, async {
+        if ready_rx.await.is_ok() {
+            supervisor.flush(generation).await;
+        }
+    });
+    lock_unpoisoned(&supervisor.outbox).sink = None;
+    result
+}
+
+impl NetworkCloseReport {
+    /// Returns `true` when every owned task reached a terminal state without failure.
+    pub fn is_complete(&self) -> bool {
+        self.failed == 0 && self.panicked == 0 && self.remaining == 0
+    }
+
+    fn merge(&mut self, other: ManagedTaskReport) {
+        self.completed += other.completed;
+        self.cancelled += other.cancelled;
+        self.failed += other.failed;
+        self.panicked += other.panicked;
+        self.remaining += other.remaining;
+    }
+
+    fn merge_network(&mut self, other: Self) {
+        self.completed += other.completed;
+        self.cancelled += other.cancelled;
+        self.failed += other.failed;
+        self.panicked += other.panicked;
+        self.remaining += other.remaining;
+        self.idle_reaped += other.idle_reaped;
+        self.rejected += other.rejected;
+    }
+}
+
+#[derive(Default)]
+struct ManagedTaskReport {
+    completed: usize,
+    cancelled: usize,
+    failed: usize,
+    panicked: usize,
+    remaining: usize,
+}
+
+impl ManagedTaskReport {
+    fn merge(&mut self, other: Self) {
+        self.completed += other.completed;
+        self.cancelled += other.cancelled;
+        self.failed += other.failed;
+        self.panicked += other.panicked;
+        self.remaining += other.remaining;
+    }
+}
+
+struct ManagedCloseState {
+    root_task: Option<JoinHandle<ManagedTaskReport>>,
+    report: NetworkCloseReport,
+    writer_closed: bool,
+    completed: bool,
+    failure_logged: bool,
+}
+
+/// Owns a packet writer and the lifecycle of its TCP/UDP receive tasks.
+///
+/// Dropping this value requests cancellation but does not wait for resource release. Owners that
+/// require deterministic listener release must call [`ManagedPacketIo::close_and_wait`].
+#[must_use = "managed packet I/O must be retained and closed explicitly"]
+pub struct ManagedPacketIo<E = RawPacketEncoder>
+where
+    E: PacketEncoder,
+{
+    writer: PacketWriter<E>,
+    cancel: CancellationToken,
+    connecting_tcp_peers: Arc<StdMutex<HashSet<SocketAddr>>>,
+    active_connections: Arc<StdMutex<HashMap<u64, ManagedTcpConnection<E>>>>,
+    completed_connection_report: Arc<StdMutex<NetworkCloseReport>>,
+    next_connection_id: AtomicU64,
+    tcp_idle: TcpIdleSupervisor,
+    tcp_admission: Arc<TcpAdmission>,
+    #[cfg(unix)]
+    unix_socket_files: Vec<UnixSocketFile>,
+    udp_memberships: StdMutex<Option<UdpMemberships>>,
+    close_state: Mutex<ManagedCloseState>,
+}
+
+struct TcpConnectReservation {
+    remote_addr: SocketAddr,
+    connecting_tcp_peers: Arc<StdMutex<HashSet<SocketAddr>>>,
+}
+
+impl Drop for TcpConnectReservation {
+    fn drop(&mut self) {
+        lock_unpoisoned(&self.connecting_tcp_peers).remove(&self.remote_addr);
+    }
+}
+
+impl<E> ManagedPacketIo<E>
+where
+    E: PacketEncoder,
+{
+    /// Returns a clone of the writer bound to this managed endpoint.
+    pub fn writer(&self) -> PacketWriter<E> {
+        self.writer.clone()
+    }
+
+    /// Endpoint-wide traffic counters; see [`PacketWriter::stats`].
+    pub fn stats(&self) -> PacketIoStats {
+        self.writer.stats()
+    }
+
+    /// Accepted TCP streams rejected by admission control since the endpoint started.
+    pub fn tcp_admission_rejections(&self) -> AdmissionRejections {
+        self.tcp_admission.rejections()
+    }
+
+    /// Joins a multicast group on the endpoint's UDP socket.
+    pub fn join_multicast(&self, group: MulticastGroup) -> GlobalResult<()> {
+        self.with_udp_memberships(|memberships| memberships.join(group))
+    }
+
+    pub fn leave_multicast(&self, group: MulticastGroup) -> GlobalResult<()> {
+        self.with_udp_memberships(|memberships| memberships.leave(group))
+    }
+
+    /// Groups currently joined, in join order.
+    pub fn multicast_groups(&self) -> Vec<MulticastGroup> {
+        lock_unpoisoned(&self.udp_memberships)
+            .as_ref()
+            .map(UdpMemberships::groups)
+            .unwrap_or_default()
+    }
+
+    fn with_udp_memberships<T>(
         &self,
-        remote_addr: SocketAddr,
-    ) -> GlobalResult<TcpConnectReservation<'_>> {
+        f: impl FnOnce(&UdpMemberships) -> GlobalResult<T>,
+    ) -> GlobalResult<T> {
+        match lock_unpoisoned(&self.udp_memberships).as_ref() {
+            Some(memberships) => f(memberships),
+            None => Err(GlobalError::new_sys_error(
+                "udp socket is not available",
+                |msg| error!("{msg}"),
+            )),
+        }
+    }
+
+    fn reserve_tcp_connect(&self, remote_addr: SocketAddr) -> GlobalResult<TcpConnectReservation> {
         let mut connecting_tcp_peers = lock_unpoisoned(&self.connecting_tcp_peers);
         if self.writer.has_tcp_writer(&remote_addr) || !connecting_tcp_peers.insert(remote_addr) {
             return Err(GlobalError::new_sys_error(
@@ -1176,7 +1952,7 @@ where
         drop(connecting_tcp_peers);
         Ok(TcpConnectReservation {
             remote_addr,
-            connecting_tcp_peers: &self.connecting_tcp_peers,
+            connecting_tcp_peers: self.connecting_tcp_peers.clone(),
         })
     }
 
@@ -1212,99 +1988,93 @@ where
         D: PacketDispatcher,
         S: PacketSplitter + Default,
     {
-        if options.timeout.is_zero() {
-            return Err(GlobalError::new_sys_error(
-                "managed tcp connect timeout must be greater than zero",
-                |msg| debug!("{msg}: remote_addr={}", options.remote_addr),
-            ));
-        }
-        if options
-            .local_addr
-            .is_some_and(|local| local.is_ipv4() != options.remote_addr.is_ipv4())
-        {
-            return Err(GlobalError::new_sys_error(
-                "managed tcp local and remote address families differ",
-                |msg| {
-                    debug!(
-                        "{msg}: local_addr={:?}, remote_addr={}",
-                        options.local_addr, options.remote_addr
-                    )
-                },
-            ));
-        }
+        self.connect_tcp_with::<D, S>(runtime, task_name, options, None, dispatcher)
+            .await
+    }
+
+    /// Actively connects a TCP peer over TLS; see [`ManagedPacketIo::connect_tcp`].
+    ///
+    /// The TLS handshake completes before this method returns, so handshake failures and
+    /// timeouts are reported to the caller instead of the connection task.
+    pub async fn connect_tcp_tls<D, S>(
+        &self,
+        runtime: &GlobalRuntime,
+        task_name: impl Into<String>,
+        options: ManagedTcpConnectOptions,
+        tls: &TcpTlsConnector,
+        dispatcher: Arc<D>,
+    ) -> GlobalResult<ManagedTcpConnection<E>>
+    where
+        D: PacketDispatcher,
+        S: PacketSplitter + Default,
+    {
+        self.connect_tcp_with::<D, S>(runtime, task_name, options, Some(tls), dispatcher)
+            .await
+    }
 
+    async fn connect_tcp_with<D, S>(
+        &self,
+        runtime: &GlobalRuntime,
+        task_name: impl Into<String>,
+        options: ManagedTcpConnectOptions,
+        tls: Option<&TcpTlsConnector>,
+        dispatcher: Arc<D>,
+    ) -> GlobalResult<ManagedTcpConnection<E>>
+    where
+        D: PacketDispatcher,
+        S: PacketSplitter + Default,
+    {
+        validate_tcp_connect_options(&options)?;
         if self.cancel.is_cancelled() {
             return Err(managed_tcp_connect_cancelled(options.remote_addr));
         }
         let _connect_reservation = self.reserve_tcp_connect(options.remote_addr)?;
         self.reap_finished_connections().await;
 
-        let socket = if options.remote_addr.is_ipv4() {
-            TcpSocket::new_v4()
-        } else {
-            TcpSocket::new_v6()
-        }
-        .hand_log(|msg| debug!("{msg}: remote_addr={}", options.remote_addr))?;
-        if let Some(local_addr) = options.local_addr {
-            socket
-                .bind(local_addr)
-                .hand_log(|msg| debug!("{msg}: local_addr={local_addr}"))?;
-        }
-
-        let stream = select! {
-            biased;
-            _ = self.cancel.cancelled() => {
-                return Err(managed_tcp_connect_cancelled(options.remote_addr));
-            }
-            result = tokio::time::timeout(options.timeout, socket.connect(options.remote_addr)) => {
-                match result {
-                    Ok(result) => result.hand_log(|msg| {
-                        debug!("{msg}: remote_addr={}", options.remote_addr)
-                    })?,
-                    Err(_) => {
-                        return Err(GlobalError::new_sys_error(
-                            "managed tcp connect timed out",
-                            |msg| debug!("{msg}: remote_addr={}", options.remote_addr),
-                        ));
-                    }
-                }
-            }
-        };
+        let stream = dial_tcp(&options, &self.cancel).await?;
         let local_addr = stream
             .local_addr()
             .hand_log(|msg| debug!("{msg}: remote_addr={}", options.remote_addr))?;
         let cancel = self.cancel.child_token();
         let (ready_tx, ready_rx) = oneshot::channel();
-        let task = runtime.spawn(
-            task_name,
-            run_managed_tcp_connection::<D, S, E>(
-                stream,
-                options.remote_addr,
-                cancel.clone(),
-                dispatcher,
-                self.writer.clone(),
-                Some(ready_tx),
-            ),
-        )?;
-        let connection_id = self.next_connection_id.fetch_add(1, Ordering::Relaxed);
-        let connection = ManagedTcpConnection {
-            inner: Arc::new(ManagedTcpConnectionInner {
-                connection_id,
-                local_addr,
-                remote_addr: options.remote_addr,
-                writer: self.writer.clone(),
-                cancel,
-                close_state: Mutex::new(ManagedTcpConnectionCloseState {
-                    task: Some(task),
-                    report: NetworkCloseReport::default(),
-                    completed: false,
-                    failure_logged: false,
-                }),
-                owner_registered: AtomicBool::new(false),
-                owner_connections: Arc::downgrade(&self.active_connections),
-                owner_report: Arc::downgrade(&self.completed_connection_report),
-            }),
+        let stats = self.writer.stats.clone();
+        let task = match tls {
+            None => runtime.spawn(
+                task_name,
+                run_managed_tcp_connection::<D, S, E, _>(
+                    stream,
+                    options.remote_addr,
+                    cancel.clone(),
+                    dispatcher,
+                    self.writer.clone(),
+                    TcpStreamOptions {
+                        idle: self.tcp_idle.clone(),
+                        ready: Some(ready_tx),
+                    },
+                ),
+            )?,
+            Some(tls) => {
+                let stream = tls.connect(stream, options.remote_addr, &cancel).await?;
+                let (read_half, write_half) = tokio::io::split(stream);
+                runtime.spawn(
+                    task_name,
+                    run_managed_tcp_stream::<D, S, E, _, _>(
+                        read_half,
+                        write_half,
+                        options.remote_addr,
+                        cancel.clone(),
+                        dispatcher,
+                        self.writer.clone(),
+                        TcpStreamOptions {
+                            idle: self.tcp_idle.clone(),
+                            ready: Some(ready_tx),
+                        },
+                    ),
+                )?
+            }
         };
+        PacketIoCounters::incr(&stats.tcp_connected);
+        let connection = self.track_connection(local_addr, options.remote_addr, cancel, task);
 
         let ready = select! {
             biased;
@@ -1320,23 +2090,125 @@ where
             }
             return Err(managed_tcp_connect_cancelled(options.remote_addr));
         }
+        self.register_connection(connection).await
+    }
+
+    /// Starts an outbound TCP client that redials `options.connect.remote_addr` with backoff
+    /// whenever a dial fails or an established connection closes.
+    ///
+    /// Returns once the supervisor task runs; follow [`ManagedReconnectingTcpClient::state`] for
+    /// progress. The client shares endpoint cancellation and, like [`ManagedTcpConnection`], is
+    /// awaited and reported by [`ManagedPacketIo::close_and_wait`].
+    pub async fn connect_tcp_reconnecting<D, S>(
+        &self,
+        runtime: &GlobalRuntime,
+        task_name: impl Into<String>,
+        options: ManagedTcpReconnectOptions,
+        dispatcher: Arc<D>,
+    ) -> GlobalResult<ManagedReconnectingTcpClient<E>>
+    where
+        D: PacketDispatcher,
+        S: PacketSplitter + Default,
+    {
+        let remote_addr = options.connect.remote_addr;
+        validate_tcp_connect_options(&options.connect)?;
+        if self.cancel.is_cancelled() {
+            return Err(managed_tcp_connect_cancelled(remote_addr));
+        }
+        let reservation = self.reserve_tcp_connect(remote_addr)?;
+        self.reap_finished_connections().await;
+
+        let cancel = self.cancel.child_token();
+        let (state_tx, state_rx) = watch::channel(ConnectionState::Idle);
+        let outbox = Arc::new(StdMutex::new(ReconnectOutbox {
+            sink: None,
+            pending: VecDeque::new(),
+            stopped: false,
+        }));
+        let disconnected_writes = options.disconnected_writes;
+        let local_addr = options.connect.local_addr.unwrap_or_else(|| {
+            let ip: IpAddr = if remote_addr.is_ipv4() {
+                Ipv4Addr::UNSPECIFIED.into()
+            } else {
+                Ipv6Addr::UNSPECIFIED.into()
+            };
+            SocketAddr::new(ip, 0)
+        });
+        let task = runtime.spawn(
+            task_name,
+            run_reconnecting_tcp_client::<D, S, E>(
+                ReconnectSupervisor {
+                    options,
+                    writer: self.writer.clone(),
+                    idle: self.tcp_idle.clone(),
+                    outbox: outbox.clone(),
+                    state: state_tx,
+                    _reservation: reservation,
+                },
+                cancel.clone(),
+                dispatcher,
+            ),
+        )?;
+        let connection = self.track_connection(local_addr, remote_addr, cancel, task);
+        Ok(ManagedReconnectingTcpClient {
+            inner: Arc::new(ManagedReconnectingTcpClientInner {
+                connection: self.register_connection(connection).await?,
+                disconnected_writes,
+                outbox,
+                state: state_rx,
+            }),
+        })
+    }
+
+    fn track_connection(
+        &self,
+        local_addr: SocketAddr,
+        remote_addr: SocketAddr,
+        cancel: CancellationToken,
+        task: JoinHandle<GlobalResult<()>>,
+    ) -> ManagedTcpConnection<E> {
+        ManagedTcpConnection {
+            inner: Arc::new(ManagedTcpConnectionInner {
+                connection_id: self.next_connection_id.fetch_add(1, Ordering::Relaxed),
+                local_addr,
+                remote_addr,
+                writer: self.writer.clone(),
+                cancel,
+                close_state: Mutex::new(ManagedTcpConnectionCloseState {
+                    task: Some(task),
+                    report: NetworkCloseReport::default(),
+                    completed: false,
+                    failure_logged: false,
+                }),
+                owner_registered: AtomicBool::new(false),
+                owner_connections: Arc::downgrade(&self.active_connections),
+                owner_report: Arc::downgrade(&self.completed_connection_report),
+            }),
+        }
+    }
 
+    /// Hands the connection to endpoint close, or closes it when the endpoint is already closing.
+    async fn register_connection(
+        &self,
+        connection: ManagedTcpConnection<E>,
+    ) -> GlobalResult<ManagedTcpConnection<E>> {
+        let remote_addr = connection.remote_addr();
         let state = self.close_state.lock().await;
         if self.cancel.is_cancelled() || state.completed {
             drop(state);
             if let Err(error) = connection.close_and_wait().await {
                 debug!(
-                    "late managed tcp connection close failed: remote_addr={}, error={error}",
-                    options.remote_addr
+                    "late managed tcp connection close failed: remote_addr={remote_addr}, error={error}"
                 );
             }
-            return Err(managed_tcp_connect_cancelled(options.remote_addr));
+            return Err(managed_tcp_connect_cancelled(remote_addr));
         }
         connection
             .inner
             .owner_registered
             .store(true, Ordering::Release);
-        lock_unpoisoned(&self.active_connections).insert(connection_id, connection.clone());
+        lock_unpoisoned(&self.active_connections)
+            .insert(connection.inner.connection_id, connection.clone());
         Ok(connection)
     }
 
@@ -1392,6 +2264,15 @@ where
                 Err(join_error) => record_join_error(&mut state.report, join_error),
             }
         }
+        #[cfg(unix)]
+        for socket_file in &self.unix_socket_files {
+            socket_file.remove();
+        }
+        if let Some(memberships) = lock_unpoisoned(&self.udp_memberships).take() {
+            memberships.leave_all();
+        }
+        state.report.idle_reaped += self.tcp_idle.take_reaped();
+        state.report.rejected += self.tcp_admission.take_unreported();
 
         state.completed = true;
         let log_failure = !state.failure_logged;
@@ -1402,6 +2283,63 @@ where
     }
 }
 
+fn validate_tcp_connect_options(options: &ManagedTcpConnectOptions) -> GlobalResult<()> {
+    if options.timeout.is_zero() {
+        return Err(GlobalError::new_sys_error(
+            "managed tcp connect timeout must be greater than zero",
+            |msg| debug!("{msg}: remote_addr={}", options.remote_addr),
+        ));
+    }
+    if options
+        .local_addr
+        .is_some_and(|local| local.is_ipv4() != options.remote_addr.is_ipv4())
+    {
+        return Err(GlobalError::new_sys_error(
+            "managed tcp local and remote address families differ",
+            |msg| {
+                debug!(
+                    "{msg}: local_addr={:?}, remote_addr={}",
+                    options.local_addr, options.remote_addr
+                )
+            },
+        ));
+    }
+    Ok(())
+}
+
+async fn dial_tcp(
+    options: &ManagedTcpConnectOptions,
+    cancel: &CancellationToken,
+) -> GlobalResult<TcpStream> {
+    let socket = if options.remote_addr.is_ipv4() {
+        TcpSocket::new_v4()
+    } else {
+        TcpSocket::new_v6()
+    }
+    .hand_log(|msg| debug!("{msg}: remote_addr={}", options.remote_addr))?;
+    if let Some(local_addr) = options.local_addr {
+        socket
+            .bind(local_addr)
+            .hand_log(|msg| debug!("{msg}: local_addr={local_addr}"))?;
+    }
+
+    select! {
+        biased;
+        _ = cancel.cancelled() => Err(managed_tcp_connect_cancelled(options.remote_addr)),
+        result = tokio::time::timeout(options.timeout, socket.connect(options.remote_addr)) => {
+            match result {
+                Ok(result) => result.hand_log(|msg| {
+                    debug!("{msg}: remote_addr={}", options.remote_addr)
+                }),
+                Err(_) => Err(GlobalError::new_sys_error(
+                    "managed tcp connect timed out",
+                    |msg| debug!("{msg}: remote_addr={}", options.remote_addr),
+                )),
+            }
+        }
+    }
+}
+
 fn managed_tcp_connect_cancelled(remote_addr: SocketAddr) -> GlobalError {
     GlobalError::new_sys_error("managed tcp connect cancelled", |msg| {
         debug!("{msg}: remote_addr={remote_addr}")
@@ -1497,43 +2435,261 @@ where
 }
 
 /// Starts managed packet I/O with an explicit TCP write mode.
+pub fn managed_rw_with_tcp_write_mode<D, S, E>(
+    runtime: &GlobalRuntime,
+    task_name: impl Into<String>,
+    tu: (Option<std::net::TcpListener>, Option<std::net::UdpSocket>),
+    external_cancel: CancellationToken,
+    dispatcher: Arc<D>,
+    encoder: Arc<E>,
+    tcp_write_mode: TcpWriteMode,
+) -> GlobalResult<ManagedPacketIo<E>>
+where
+    D: PacketDispatcher,
+    S: PacketSplitter + Default,
+    E: PacketEncoder,
+{
+    managed_rw_with_options::<D, S, E>(
+        runtime,
+        task_name,
+        tu,
+        external_cancel,
+        dispatcher,
+        encoder,
+        PacketIoOptions {
+            tcp_write_mode,
+            ..PacketIoOptions::default()
+        },
+    )
+}
+
+/// Starts managed packet I/O with explicit endpoint options.
 ///
 /// TCP accept/read work and UDP receive work run in independent protocol tasks. A single runtime
 /// supervisor owns those tasks and provides deterministic shutdown through [`ManagedPacketIo`].
-pub fn managed_rw_with_tcp_write_mode<D, S, E>(
+pub fn managed_rw_with_options<D, S, E>(
     runtime: &GlobalRuntime,
     task_name: impl Into<String>,
     tu: (Option<std::net::TcpListener>, Option<std::net::UdpSocket>),
     external_cancel: CancellationToken,
     dispatcher: Arc<D>,
     encoder: Arc<E>,
-    tcp_write_mode: TcpWriteMode,
+    options: PacketIoOptions,
+) -> GlobalResult<ManagedPacketIo<E>>
+where
+    D: PacketDispatcher,
+    S: PacketSplitter + Default,
+    E: PacketEncoder,
+{
+    let (tcp, udp, writer) = prepare_packet_io(tu, encoder, options.tcp_write_mode)?;
+    let memberships = udp
+        .clone()
+        .map(|udp| UdpMemberships::configure(udp, &options.udp))
+        .transpose()?;
+    let managed = spawn_managed_packet_io::<D, S, E>(
+        runtime,
+        task_name,
+        ManagedSockets {
+            tcp,
+            udp,
+            ..ManagedSockets::default()
+        },
+        writer,
+        external_cancel,
+        dispatcher,
+        options,
+    )?;
+    *lock_unpoisoned(&managed.udp_memberships) = memberships;
+    Ok(managed)
+}
+
+/// Starts managed packet I/O on Unix domain sockets bound by [`crate::net::listen_unix`].
+///
+/// Stream peers share the TCP pipeline (splitter, writer mode, idle supervision, admission and
+/// TLS) and are dispatched as [`Protocol::UnixStream`]; datagrams are dispatched as
+/// [`Protocol::UnixDatagram`]. Peers are named by synthetic addresses (see [`crate::net::unix`]),
+/// so per-IP admission limits and CIDR lists apply per connection. Both socket files are
+/// unlinked by [`ManagedPacketIo::close_and_wait`].
+#[cfg(unix)]
+pub fn managed_unix_rw_with_options<D, S, E>(
+    runtime: &GlobalRuntime,
+    task_name: impl Into<String>,
+    su: (
+        Option<std::os::unix::net::UnixListener>,
+        Option<std::os::unix::net::UnixDatagram>,
+    ),
+    external_cancel: CancellationToken,
+    dispatcher: Arc<D>,
+    encoder: Arc<E>,
+    options: PacketIoOptions,
+) -> GlobalResult<ManagedPacketIo<E>>
+where
+    D: PacketDispatcher,
+    S: PacketSplitter + Default,
+    E: PacketEncoder,
+{
+    let (unix_stream, unix_datagram, socket_files) = prepare_unix_io(su)?;
+    let mut writer = PacketWriter::new(None, encoder, options.tcp_write_mode);
+    writer.unix_datagram = Arc::new(RwLock::new(unix_datagram.clone()));
+    let mut managed = spawn_managed_packet_io::<D, S, E>(
+        runtime,
+        task_name,
+        ManagedSockets {
+            unix_stream,
+            unix_datagram,
+            ..ManagedSockets::default()
+        },
+        writer,
+        external_cancel,
+        dispatcher,
+        options,
+    )?;
+    managed.unix_socket_files = socket_files;
+    Ok(managed)
+}
+
+/// Unmanaged counterpart of [`managed_unix_rw_with_options`], serving Unix domain sockets
+/// like [`rw_with_options`] serves TCP and UDP.
+///
+/// Nothing reports when the tasks finish, so the socket files are unlinked as soon as
+/// `cancel` fires.
+#[cfg(unix)]
+pub fn unix_rw_with_options<D, S, E>(
+    su: (
+        Option<std::os::unix::net::UnixListener>,
+        Option<std::os::unix::net::UnixDatagram>,
+    ),
+    cancel: CancellationToken,
+    dispatcher: Arc<D>,
+    encoder: Arc<E>,
+    options: PacketIoOptions,
+) -> GlobalResult<PacketWriter<E>>
+where
+    D: PacketDispatcher,
+    S: PacketSplitter + Default,
+    E: PacketEncoder,
+{
+    let (unix_stream, unix_datagram, socket_files) = prepare_unix_io(su)?;
+    let mut writer = PacketWriter::new(None, encoder, options.tcp_write_mode);
+    writer.unix_datagram = Arc::new(RwLock::new(unix_datagram.clone()));
+    if let Some(unix_listener) = unix_stream {
+        drop(tokio::spawn(run_tcp_listener::<D, S, E, _>(
+            unix_listener,
+            cancel.clone(),
+            dispatcher.clone(),
+            writer.clone(),
+            TcpAcceptOptions {
+                tls: options.tls,
+                idle: TcpIdleSupervisor::new(options.tcp_idle),
+                admission: TcpAdmission::new(options.tcp_admission),
+                proxy_protocol: options.proxy_protocol,
+            },
+        )));
+    }
+    if let Some(unix_datagram) = unix_datagram {
+        drop(tokio::spawn(run_udp_receiver(
+            unix_datagram,
+            cancel.clone(),
+            dispatcher,
+            writer.stats.clone(),
+        )));
+    }
+    drop(tokio::spawn(async move {
+        cancel.cancelled().await;
+        socket_files.iter().for_each(UnixSocketFile::remove);
+    }));
+    Ok(writer)
+}
+
+#[cfg(unix)]
+type PreparedUnixIo = (
+    Option<tokio::net::UnixListener>,
+    Option<Arc<UnixDatagramSocket>>,
+    Vec<UnixSocketFile>,
+);
+
+#[cfg(unix)]
+fn prepare_unix_io(
+    su: (
+        Option<std::os::unix::net::UnixListener>,
+        Option<std::os::unix::net::UnixDatagram>,
+    ),
+) -> GlobalResult<PreparedUnixIo> {
+    let (stream, datagram) = su;
+    let mut socket_files = Vec::new();
+    let unix_stream = stream
+        .map(|listener| {
+            let path = listener.local_addr().ok();
+            socket_files.extend(UnixSocketFile::bound(
+                path.as_ref().and_then(|path| path.as_pathname()),
+            ));
+            listener
+                .set_nonblocking(true)
+                .hand_log(|msg| error!("{msg}"))?;
+            tokio::net::UnixListener::from_std(listener).hand_log(|msg| error!("{msg}"))
+        })
+        .transpose()?;
+    let unix_datagram = datagram
+        .map(|socket| {
+            socket
+                .set_nonblocking(true)
+                .hand_log(|msg| error!("{msg}"))?;
+            tokio::net::UnixDatagram::from_std(socket)
+                .map(|socket| Arc::new(UnixDatagramSocket::new(socket)))
+                .hand_log(|msg| error!("{msg}"))
+        })
+        .transpose()?;
+    if let Some(socket) = unix_datagram.as_ref() {
+        socket_files.extend(UnixSocketFile::bound(socket.local_path().as_deref()));
+    }
+    Ok((unix_stream, unix_datagram, socket_files))
+}
+
+fn spawn_managed_packet_io<D, S, E>(
+    runtime: &GlobalRuntime,
+    task_name: impl Into<String>,
+    sockets: ManagedSockets,
+    writer: PacketWriter<E>,
+    external_cancel: CancellationToken,
+    dispatcher: Arc<D>,
+    options: PacketIoOptions,
 ) -> GlobalResult<ManagedPacketIo<E>>
 where
     D: PacketDispatcher,
     S: PacketSplitter + Default,
     E: PacketEncoder,
 {
-    let (tcp_listener, udp_socket, writer) = prepare_packet_io(tu, encoder, tcp_write_mode)?;
     let cancel = runtime.cancel.child_token();
+    let tcp_idle = TcpIdleSupervisor::new(options.tcp_idle);
+    let tcp_admission = TcpAdmission::new(options.tcp_admission);
     let root_task = runtime.spawn(
         task_name,
         run_managed_packet_io::<D, S, E>(
-            tcp_listener,
-            udp_socket,
+            sockets,
             cancel.clone(),
             external_cancel,
             dispatcher,
             writer.clone(),
+            TcpAcceptOptions {
+                tls: options.tls,
+                idle: tcp_idle.clone(),
+                admission: tcp_admission.clone(),
+                proxy_protocol: options.proxy_protocol,
+            },
         ),
     )?;
     Ok(ManagedPacketIo {
         writer,
         cancel,
-        connecting_tcp_peers: StdMutex::new(HashSet::new()),
+        connecting_tcp_peers: Arc::new(StdMutex::new(HashSet::new())),
         active_connections: Arc::new(StdMutex::new(HashMap::new())),
         completed_connection_report: Arc::new(StdMutex::new(NetworkCloseReport::default())),
         next_connection_id: AtomicU64::new(1),
+        tcp_idle,
+        tcp_admission,
+        #[cfg(unix)]
+        unix_socket_files: Vec::new(),
+        udp_memberships: StdMutex::new(None),
         close_state: Mutex::new(ManagedCloseState {
             root_task: Some(root_task),
             report: NetworkCloseReport::default(),
@@ -1624,18 +2780,56 @@ where
     S: PacketSplitter + Default,
     E: PacketEncoder,
 {
-    let (tcp_listener, udp_socket, writer) = prepare_packet_io(tu, encoder, tcp_write_mode)?;
+    rw_with_options::<D, S, E>(
+        tu,
+        cancel,
+        dispatcher,
+        encoder,
+        PacketIoOptions {
+            tcp_write_mode,
+            ..PacketIoOptions::default()
+        },
+    )
+}
+
+pub fn rw_with_options<D, S, E>(
+    tu: (Option<std::net::TcpListener>, Option<std::net::UdpSocket>),
+    cancel: CancellationToken,
+    dispatcher: Arc<D>,
+    encoder: Arc<E>,
+    options: PacketIoOptions,
+) -> GlobalResult<PacketWriter<E>>
+where
+    D: PacketDispatcher,
+    S: PacketSplitter + Default,
+    E: PacketEncoder,
+{
+    let (tcp_listener, udp_socket, writer) =
+        prepare_packet_io(tu, encoder, options.tcp_write_mode)?;
+    if let Some(udp_socket) = udp_socket.clone() {
+        // Unmanaged endpoints keep their groups until the socket closes.
+        UdpMemberships::configure(udp_socket, &options.udp)?;
+    }
     if let Some(tcp_listener) = tcp_listener {
-        drop(tokio::spawn(run_tcp_listener::<D, S, E>(
+        drop(tokio::spawn(run_tcp_listener::<D, S, E, _>(
             tcp_listener,
             cancel.clone(),
             dispatcher.clone(),
             writer.clone(),
+            TcpAcceptOptions {
+                tls: options.tls,
+                idle: TcpIdleSupervisor::new(options.tcp_idle),
+                admission: TcpAdmission::new(options.tcp_admission),
+                proxy_protocol: options.proxy_protocol,
+            },
         )));
     }
     if let Some(udp_socket) = udp_socket {
         drop(tokio::spawn(run_udp_receiver(
-            udp_socket, cancel, dispatcher,
+            udp_socket,
+            cancel,
+            dispatcher,
+            writer.stats.clone(),
         )));
     }
     Ok(writer)
@@ -1789,13 +2983,24 @@ where
     Ok(())
 }
 
+/// Sockets served by one managed endpoint.
+#[derive(Default)]
+struct ManagedSockets {
+    tcp: Option<tokio::net::TcpListener>,
+    udp: Option<Arc<UdpSocket>>,
+    #[cfg(unix)]
+    unix_stream: Option<tokio::net::UnixListener>,
+    #[cfg(unix)]
+    unix_datagram: Option<Arc<UnixDatagramSocket>>,
+}
+
 async fn run_managed_packet_io<D, S, E>(
-    tcp_listener: Option<tokio::net::TcpListener>,
-    udp_socket: Option<Arc<UdpSocket>>,
+    sockets: ManagedSockets,
     cancel: CancellationToken,
     external_cancel: CancellationToken,
     dispatcher: Arc<D>,
     writer: PacketWriter<E>,
+    accept_options: TcpAcceptOptions,
 ) -> ManagedTaskReport
 where
     D: PacketDispatcher,
@@ -1804,17 +3009,43 @@ where
 {
     let mut report = ManagedTaskReport::default();
     let mut protocol_tasks = JoinSet::new();
+    let stats = writer.stats.clone();
 
-    if let Some(tcp_listener) = tcp_listener {
-        protocol_tasks.spawn(run_tcp_listener::<D, S, E>(
+    if let Some(tcp_listener) = sockets.tcp {
+        protocol_tasks.spawn(run_tcp_listener::<D, S, E, _>(
             tcp_listener,
             cancel.clone(),
             dispatcher.clone(),
-            writer,
+            writer.clone(),
+            accept_options.clone(),
         ));
     }
-    if let Some(udp_socket) = udp_socket {
-        protocol_tasks.spawn(run_udp_receiver(udp_socket, cancel.clone(), dispatcher));
+    if let Some(udp_socket) = sockets.udp {
+        protocol_tasks.spawn(run_udp_receiver(
+            udp_socket,
+            cancel.clone(),
+            dispatcher.clone(),
+            stats.clone(),
+        ));
+    }
+    #[cfg(unix)]
+    if let Some(unix_listener) = sockets.unix_stream {
+        protocol_tasks.spawn(run_tcp_listener::<D, S, E, _>(
+            unix_listener,
+            cancel.clone(),
+            dispatcher.clone(),
+            writer.clone(),
+            accept_options.clone(),
+        ));
+    }
+    #[cfg(unix)]
+    if let Some(unix_datagram) = sockets.unix_datagram {
+        protocol_tasks.spawn(run_udp_receiver(
+            unix_datagram,
+            cancel.clone(),
+            dispatcher.clone(),
+            stats.clone(),
+        ));
     }
 
     if protocol_tasks.is_empty() {
@@ -1842,16 +3073,18 @@ where
     report
 }
 
-async fn run_tcp_listener<D, S, E>(
-    tcp_listener: tokio::net::TcpListener,
+async fn run_tcp_listener<D, S, E, L>(
+    tcp_listener: L,
     cancel: CancellationToken,
     dispatcher: Arc<D>,
     writer: PacketWriter<E>,
+    accept_options: TcpAcceptOptions,
 ) -> ManagedTaskReport
 where
     D: PacketDispatcher,
     S: PacketSplitter + Default,
     E: PacketEncoder,
+    L: PacketStreamListener,
 {
     let mut report = ManagedTaskReport::default();
     let mut connections = JoinSet::new();
@@ -1872,24 +3105,53 @@ where
                 }
             }
 
-            accepted = tcp_listener.accept() => {
+            accepted = std::future::poll_fn(|cx| tcp_listener.poll_accept(cx)) => {
                 match accepted {
                     Ok((stream, remote_addr)) if !cancel.is_cancelled() => {
                         accept_error_backoff = std::time::Duration::ZERO;
-                        connections.spawn(run_managed_tcp_connection::<D, S, E>(
-                            stream,
-                            remote_addr,
-                            cancel.child_token(),
-                            dispatcher.clone(),
-                            writer.clone(),
-                            None,
-                        ));
+                        if let Some(proxy) = &accept_options.proxy_protocol {
+                            // The balancer takes a connection slot and an accept token now; the
+                            // client it names is checked once the header arrives.
+                            if let Some(permit) = accept_options
+                                .admission
+                                .admit_balancer(remote_addr, &proxy.balancers)
+                            {
+                                connections.spawn(run_proxied_tcp_connection::<D, S, E, _>(
+                                    stream,
+                                    remote_addr,
+                                    permit,
+                                    cancel.child_token(),
+                                    dispatcher.clone(),
+                                    writer.clone(),
+                                    accept_options.clone(),
+                                ));
+                            } else {
+                                PacketIoCounters::incr(&writer.stats.tcp_rejected);
+                            }
+                        } else if let Some(permit) = accept_options.admission.admit(remote_addr) {
+                            PacketIoCounters::incr(&writer.stats.tcp_accepted);
+                            let connection = run_accepted_tcp_connection::<D, S, E, _>(
+                                stream,
+                                remote_addr,
+                                cancel.child_token(),
+                                dispatcher.clone(),
+                                writer.clone(),
+                                accept_options.clone(),
+                            );
+                            connections.spawn(async move {
+                                let _permit = permit;
+                                connection.await
+                            });
+                        } else {
+                            PacketIoCounters::incr(&writer.stats.tcp_rejected);
+                        }
                     }
                     Ok(_) => break,
                     Err(error) => {
                         accept_error_backoff = next_accept_error_backoff(accept_error_backoff);
                         warn!(
-                            "tcp accept failed; retrying after {:?}: {error}",
+                            "{} accept failed; retrying after {:?}: {error}",
+                            L::PROTOCOL,
                             accept_error_backoff
                         );
                         select! {
@@ -1921,13 +3183,15 @@ fn next_accept_error_backoff(current: std::time::Duration) -> std::time::Duratio
     }
 }
 
-async fn run_udp_receiver<D>(
-    udp_socket: Arc<UdpSocket>,
+async fn run_udp_receiver<D, U>(
+    udp_socket: Arc<U>,
     cancel: CancellationToken,
     dispatcher: Arc<D>,
+    stats: Arc<PacketIoCounters>,
 ) -> ManagedTaskReport
 where
     D: PacketDispatcher,
+    U: PacketDatagramSocket,
 {
     let mut report = ManagedTaskReport::default();
     let mut receive_buf = vec![0u8; UDP_MAX_DATAGRAM_SIZE];
@@ -1940,22 +3204,23 @@ where
                 break;
             }
 
-            received = udp_socket_read_owned_buf(&mut receive_buf, udp_socket.as_ref()) => {
+            received = datagram_read_owned_buf(&mut receive_buf, udp_socket.as_ref()) => {
                 match received {
                     Ok((size, remote_addr)) if size != 0 => {
+                        stats.record_udp_rx(size, size == receive_buf.len());
                         let packet = Bytes::copy_from_slice(&receive_buf[..size]);
                         if let Err(error) = dispatcher.dispatch_owned(
                             packet,
                             remote_addr,
-                            Protocol::UDP,
+                            U::PROTOCOL,
                         ) {
-                            debug!("udp dispatch {remote_addr} failed: {error}");
+                            debug!("{} dispatch {remote_addr} failed: {error}", U::PROTOCOL);
                         }
                     }
                     Ok(_) => {}
                     Err(error) => {
                         report.failed += 1;
-                        debug!("udp read failed: {error}");
+                        debug!("{} read failed: {error}", U::PROTOCOL);
                         break;
                     }
                 }
@@ -1980,63 +3245,343 @@ fn record_protocol_join(
     }
 }
 
-async fn run_managed_tcp_connection<D, S, E>(
-    stream: TcpStream,
+async fn run_proxied_tcp_connection<D, S, E, T>(
+    mut stream: T,
+    balancer_addr: SocketAddr,
+    permit: TcpAdmissionPermit,
+    cancel: CancellationToken,
+    dispatcher: Arc<D>,
+    writer: PacketWriter<E>,
+    accept_options: TcpAcceptOptions,
+) -> GlobalResult<()>
+where
+    D: PacketDispatcher,
+    S: PacketSplitter + Default,
+    E: PacketEncoder,
+    T: PacketStream,
+{
+    // Like a failed TLS handshake, a bad header never reached the dispatcher.
+    let header = match &accept_options.proxy_protocol {
+        Some(proxy) => proxy.read_header(&mut stream, balancer_addr, &cancel).await,
+        None => Ok(None),
+    };
+    let Ok(client_addr) = header else {
+        PacketIoCounters::incr(&writer.stats.tcp_rejected);
+        return Ok(());
+    };
+    let remote_addr = client_addr.unwrap_or(balancer_addr);
+    let Some(_permit) = permit.admit_client(remote_addr) else {
+        PacketIoCounters::incr(&writer.stats.tcp_rejected);
+        return Ok(());
+    };
+    PacketIoCounters::incr(&writer.stats.tcp_accepted);
+    run_accepted_tcp_connection::<D, S, E, T>(
+        stream,
+        remote_addr,
+        cancel,
+        dispatcher,
+        writer,
+        accept_options,
+    )
+    .await
+}
+
+async fn run_accepted_tcp_connection<D, S, E, T>(
+    stream: T,
     remote_addr: SocketAddr,
     cancel: CancellationToken,
     dispatcher: Arc<D>,
     writer: PacketWriter<E>,
-    ready: Option<oneshot::Sender<()>>,
+    accept_options: TcpAcceptOptions,
 ) -> GlobalResult<()>
 where
     D: PacketDispatcher,
     S: PacketSplitter + Default,
     E: PacketEncoder,
+    T: PacketStream,
 {
-    let (read_half, write_half) = stream.into_split();
-    match writer.tcp_write_mode().queue_size() {
+    let stream_options = TcpStreamOptions {
+        idle: accept_options.idle,
+        ready: None,
+    };
+    let Some(tls) = accept_options.tls else {
+        return run_managed_tcp_connection::<D, S, E, T>(
+            stream,
+            remote_addr,
+            cancel,
+            dispatcher,
+            writer,
+            stream_options,
+        )
+        .await;
+    };
+    // A peer that fails the handshake never reached the dispatcher, so it is not a task failure.
+    let Ok(stream) = tls.accept(stream, remote_addr, &cancel).await else {
+        PacketIoCounters::incr(&writer.stats.tcp_closed);
+        return Ok(());
+    };
+    let (read_half, write_half) = tokio::io::split(stream);
+    run_managed_tcp_stream::<D, S, E, _, _>(
+        read_half,
+        write_half,
+        remote_addr,
+        cancel,
+        dispatcher,
+        writer,
+        stream_options,
+    )
+    .await
+}
+
+async fn run_managed_tcp_connection<D, S, E, T>(
+    stream: T,
+    remote_addr: SocketAddr,
+    cancel: CancellationToken,
+    dispatcher: Arc<D>,
+    writer: PacketWriter<E>,
+    stream_options: TcpStreamOptions,
+) -> GlobalResult<()>
+where
+    D: PacketDispatcher,
+    S: PacketSplitter + Default,
+    E: PacketEncoder,
+    T: PacketStream,
+{
+    let (read_half, write_half) = stream.split_owned();
+    run_managed_tcp_stream::<D, S, E, _, _>(
+        read_half,
+        write_half,
+        remote_addr,
+        cancel,
+        dispatcher,
+        writer,
+        stream_options,
+    )
+    .await
+}
+
+async fn run_managed_tcp_stream<D, S, E, R, W>(
+    read_half: R,
+    write_half: W,
+    remote_addr: SocketAddr,
+    cancel: CancellationToken,
+    dispatcher: Arc<D>,
+    writer: PacketWriter<E>,
+    stream_options: TcpStreamOptions,
+) -> GlobalResult<()>
+where
+    D: PacketDispatcher,
+    S: PacketSplitter + Default,
+    E: PacketEncoder,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Send + Unpin + 'static,
+{
+    let TcpStreamOptions {
+        idle: tcp_idle,
+        ready,
+    } = stream_options;
+    let stats = writer.stats.clone();
+    if !tcp_idle.options.is_enabled() {
+        let result = run_tcp_stream_halves::<D, S, E, R, W>(
+            read_half,
+            write_half,
+            remote_addr,
+            cancel,
+            dispatcher,
+            writer,
+            ready,
+        )
+        .await;
+        PacketIoCounters::incr(&stats.tcp_closed);
+        return result;
+    }
+
+    let activity = Arc::new(TcpActivity::new());
+    let read_half = ActivityReader {
+        inner: read_half,
+        activity: activity.clone(),
+    };
+    let write_half = ActivityWriter {
+        inner: write_half,
+        activity: activity.clone(),
+    };
+    let supervisor_stop = cancel.child_token();
+    let (result, ()) = tokio::join!(
+        async {
+            let result = run_tcp_stream_halves::<D, S, E, _, _>(
+                read_half,
+                write_half,
+                remote_addr,
+                cancel.clone(),
+                dispatcher,
+                writer.clone(),
+                ready,
+            )
+            .await;
+            supervisor_stop.cancel();
+            result
+        },
+        supervise_tcp_idle(
+            remote_addr,
+            &cancel,
+            &supervisor_stop,
+            &tcp_idle,
+            &activity,
+            &writer,
+        ),
+    );
+    PacketIoCounters::incr(&stats.tcp_closed);
+    result
+}
+
+/// Closes the connection through `cancel` once a configured idle deadline passes.
+async fn supervise_tcp_idle<E>(
+    remote_addr: SocketAddr,
+    cancel: &CancellationToken,
+    stop: &CancellationToken,
+    tcp_idle: &TcpIdleSupervisor,
+    activity: &TcpActivity,
+    writer: &PacketWriter<E>,
+) where
+    E: PacketEncoder,
+{
+    let options = &tcp_idle.options;
+    loop {
+        let read_idle = activity.idle(&activity.read_ms);
+        let mut write_idle = activity.idle(&activity.write_ms);
+        if let Some(heartbeat) = options
+            .heartbeat
+            .as_ref()
+            .filter(|heartbeat| write_idle >= heartbeat.interval)
+        {
+            if let Some(sink) = writer.tcp_sink(&remote_addr) {
+                let ping = (heartbeat.ping)(remote_addr);
+                select! {
+                    biased;
+                    _ = stop.cancelled() => return,
+                    result = tokio::time::timeout(heartbeat.interval, sink.write(ping)) => {
+                        if let Ok(Err(error)) = result {
+                            debug!("tcp heartbeat {remote_addr} failed: {error}");
+                        }
+                    }
+                }
+            }
+            write_idle = activity.idle(&activity.write_ms);
+        }
+
+        let expired = [
+            ("read", options.read_timeout, read_idle),
+            ("write", options.write_timeout, write_idle),
+        ]
+        .into_iter()
+        .find(|(_, timeout, idle)| timeout.is_some_and(|timeout| *idle >= timeout));
+        if let Some((direction, _, idle)) = expired {
+            debug!("tcp {direction} idle for {idle:?}; close peer {remote_addr}");
+            tcp_idle.reaped.fetch_add(1, Ordering::Relaxed);
+            PacketIoCounters::incr(&writer.stats.tcp_idle_reaped);
+            cancel.cancel();
+            return;
+        }
+
+        let next_check = [
+            options
+                .read_timeout
+                .map(|timeout| timeout.saturating_sub(read_idle)),
+            options
+                .write_timeout
+                .map(|timeout| timeout.saturating_sub(write_idle)),
+            options
+                .heartbeat
+                .as_ref()
+                .map(|heartbeat| heartbeat.interval.saturating_sub(write_idle)),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+        .unwrap_or_default()
+        .max(TCP_IDLE_CHECK_MIN);
+        select! {
+            biased;
+            _ = stop.cancelled() => return,
+            _ = tokio::time::sleep(next_check) => {}
+        }
+    }
+}
+
+async fn run_tcp_stream_halves<D, S, E, R, W>(
+    read_half: R,
+    write_half: W,
+    remote_addr: SocketAddr,
+    cancel: CancellationToken,
+    dispatcher: Arc<D>,
+    writer: PacketWriter<E>,
+    ready: Option<oneshot::Sender<()>>,
+) -> GlobalResult<()>
+where
+    D: PacketDispatcher,
+    S: PacketSplitter + Default,
+    E: PacketEncoder,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Send + Unpin + 'static,
+{
+    let counters = writer.new_connection_counters();
+    match writer.tcp_write_mode().queue_size() {
         Some(queue_size) => {
             let (tx, rx) = mpsc::channel(queue_size);
-            let Some(registration) =
-                writer.insert_registered_tcp_writer(remote_addr, tx, cancel.clone())
-            else {
+            let Some(registration) = writer.insert_registered_tcp_writer(
+                remote_addr,
+                tx,
+                cancel.clone(),
+                counters.clone(),
+            ) else {
                 return Err(packet_writer_closed());
             };
             if let Some(ready) = ready {
                 let _ = ready.send(());
             }
             let (read_result, write_result) = tokio::join!(
-                handle_tcp_read_owned_half::<D, S, E>(
+                handle_tcp_read_owned_half::<D, S, E, R>(
                     read_half,
                     remote_addr,
                     cancel.clone(),
                     dispatcher,
-                    S::default(),
                     writer.clone(),
                     registration,
+                    &counters,
+                ),
+                handle_tcp_write::<E, W>(
+                    write_half,
+                    remote_addr,
+                    cancel,
+                    rx,
+                    writer,
+                    registration,
+                    &counters,
                 ),
-                handle_tcp_write::<E>(write_half, remote_addr, cancel, rx, writer, registration,),
             );
             read_result?;
             write_result
         }
         None => {
-            let Some(registration) =
-                writer.insert_registered_direct_tcp_writer(remote_addr, write_half, cancel.clone())
-            else {
+            let Some(registration) = writer.insert_registered_direct_tcp_writer(
+                remote_addr,
+                Box::new(write_half),
+                cancel.clone(),
+                counters.clone(),
+            ) else {
                 return Err(packet_writer_closed());
             };
             if let Some(ready) = ready {
                 let _ = ready.send(());
             }
-            handle_tcp_read_owned_half::<D, S, E>(
+            handle_tcp_read_owned_half::<D, S, E, R>(
                 read_half,
                 remote_addr,
                 cancel,
                 dispatcher,
-                S::default(),
                 writer,
                 registration,
+                &counters,
             )
             .await
         }
@@ -2146,20 +3691,23 @@ where
     Ok(())
 }
 
-async fn handle_tcp_read_owned_half<D, S, E>(
-    mut stream: OwnedReadHalf,
+async fn handle_tcp_read_owned_half<D, S, E, R>(
+    mut stream: R,
     remote_addr: SocketAddr,
     cancel: CancellationToken,
     dispatcher: Arc<D>,
-    mut splitter: S,
     writer: PacketWriter<E>,
     registration: TcpWriterRegistration,
+    counters: &TcpConnectionCounters,
 ) -> GlobalResult<()>
 where
     D: PacketDispatcher,
-    S: PacketSplitter,
+    S: PacketSplitter + Default,
     E: PacketEncoder,
+    R: AsyncRead + Unpin,
 {
+    let protocol = stream_protocol(&remote_addr);
+    let mut splitter = S::default();
     let mut buf = BytesMut::with_capacity(TCP_READ_BUF_SIZE);
     loop {
         select! {
@@ -2170,7 +3718,7 @@ where
                         warn!("recv data greater than max buf size; close peer");
                         break;
                     }
-                    Ok(_) => {}
+                    Ok(read) => counters.record_rx_bytes(read),
                     Err(err) => {
                         debug!("tcp read {remote_addr} failed: {err}");
                         break;
@@ -2178,27 +3726,30 @@ where
                 }
 
                 splitter.feed_owned(&mut buf, |pkt| {
-                    dispatcher.dispatch_owned(pkt, remote_addr, Protocol::TCP)
-                })?;
+                    counters.record_rx_packet();
+                    dispatcher.dispatch_owned(pkt, remote_addr, protocol)
+                }).inspect_err(|_| counters.record_splitter_error())?;
             }
             _ = cancel.cancelled() => break,
         }
     }
     writer.remove_registered_tcp_writer(registration);
-    dispatcher.close(remote_addr, Protocol::TCP)?;
+    dispatcher.close(remote_addr, protocol)?;
     Ok(())
 }
 
-async fn handle_tcp_write<E>(
-    mut stream: OwnedWriteHalf,
+async fn handle_tcp_write<E, W>(
+    mut stream: W,
     remote_addr: SocketAddr,
     cancel: CancellationToken,
     mut rx: mpsc::Receiver<EncodedPacket>,
     writer: PacketWriter<E>,
     registration: TcpWriterRegistration,
+    counters: &TcpConnectionCounters,
 ) -> GlobalResult<()>
 where
     E: PacketEncoder,
+    W: AsyncWrite + Unpin,
 {
     loop {
         select! {
@@ -2206,10 +3757,20 @@ where
                 let Some(data) = item else {
                     break;
                 };
+                let packet_len = data.len();
                 if let Err(err) = write_encoded_packet(&mut stream, data).await {
                     debug!("tcp write {remote_addr} failed: {err}");
                     break;
                 }
+                counters.record_tx(packet_len);
+                // TLS keeps ciphertext buffered in the session; push it out once the queue is
+                // drained instead of waiting for the next packet. A no-op for plain TCP.
+                if rx.is_empty() {
+                    if let Err(err) = stream.flush().await {
+                        debug!("tcp flush {remote_addr} failed: {err}");
+                        break;
+                    }
+                }
             }
             _ = cancel.cancelled() => break,
         }
@@ -2339,6 +3900,19 @@ where
     Ok(())
 }
 
+async fn datagram_read_owned_buf<U>(buf: &mut [u8], socket: &U) -> GlobalResult<(usize, SocketAddr)>
+where
+    U: PacketDatagramSocket,
+{
+    std::future::poll_fn(|cx| {
+        let mut read_buf = ReadBuf::new(&mut *buf);
+        let remote_addr = ready!(socket.poll_recv_from(cx, &mut read_buf))?;
+        Poll::Ready(io::Result::Ok((read_buf.filled().len(), remote_addr)))
+    })
+    .await
+    .hand_log(|msg| error!("read buf failed:{msg}"))
+}
+
 async fn udp_socket_read_owned_buf(
     buf: &mut [u8],
     socket: &UdpSocket,
@@ -2352,12 +3926,23 @@ async fn udp_socket_read_owned_buf(
 #[cfg(test)]
 mod tests {
     use super::{
-        handle_tcp_write, into_tokio_udp_socket, managed_rw_with_tcp_write_mode, ManagedCloseState,
+        handle_tcp_write, into_tokio_udp_socket, managed_rw_with_options,
+        managed_rw_with_tcp_write_mode, rw_with_options, EncodedPacket, ManagedCloseState,
         ManagedPacketIo, ManagedTaskReport, ManagedTcpConnectOptions, NetworkCloseReport,
-        PacketDispatcher, PacketSplitter, PacketWriter, RawPacketEncoder, TcpWriteMode,
-        TcpWriterRegistration, TCP_ACCEPT_ERROR_BACKOFF_MAX, TCP_ACCEPT_ERROR_BACKOFF_MIN,
+        PacketDispatcher, PacketIoOptions, PacketSplitter, PacketWriter, RawPacketEncoder,
+        TcpHeartbeat, TcpIdleOptions, TcpIdleSupervisor, TcpWriteMode, TcpWriterRegistration,
+        TCP_ACCEPT_ERROR_BACKOFF_MAX, TCP_ACCEPT_ERROR_BACKOFF_MIN,
+    };
+    use crate::net::admission::{TcpAdmission, TcpAdmissionOptions};
+    use crate::net::multicast::{MulticastGroup, MulticastInterface, UdpSocketOptions};
+    use crate::net::proxy::ProxyProtocolOptions;
+    use crate::net::reconnect::{
+        ConnectionState, DisconnectedWritePolicy, ManagedTcpReconnectOptions, ReconnectPolicy,
     };
     use crate::net::state::Protocol;
+    use crate::net::tls::{
+        ClientAuthMode, TcpTlsAcceptor, TcpTlsClientConfig, TcpTlsConnector, TcpTlsServerConfig,
+    };
     use crate::tokio;
     use crate::tokio::net::{TcpListener, TcpStream};
     use crate::tokio::sync::{mpsc, Mutex, Notify};
@@ -2418,6 +4003,35 @@ mod tests {
         }
     }
 
+    fn test_tls_acceptor(client_auth: ClientAuthMode) -> TcpTlsAcceptor {
+        TcpTlsServerConfig {
+            certificate_pem: include_bytes!("../../tests/fixtures/tls/server.pem").to_vec(),
+            private_key_pem: include_bytes!("../../tests/fixtures/tls/server-key.pem").to_vec(),
+            client_ca_pem: Some(include_bytes!("../../tests/fixtures/tls/ca.pem").to_vec()),
+            client_auth,
+            handshake_timeout: Duration::from_secs(1),
+        }
+        .build()
+        .unwrap()
+    }
+
+    fn test_tls_connector() -> TcpTlsConnector {
+        TcpTlsClientConfig {
+            domain_name: Some("localhost".to_string()),
+            ca_certificate_pem: Some(include_bytes!("../../tests/fixtures/tls/ca.pem").to_vec()),
+            client_certificate_pem: Some(
+                include_bytes!("../../tests/fixtures/tls/client.pem").to_vec(),
+            ),
+            client_private_key_pem: Some(
+                include_bytes!("../../tests/fixtures/tls/client-key.pem").to_vec(),
+            ),
+            use_native_roots: false,
+            handshake_timeout: Duration::from_secs(1),
+        }
+        .build()
+        .unwrap()
+    }
+
     #[test]
     fn inline_prefix_macro_uses_numeric_width_and_endian() {
         assert_eq!(
@@ -2470,10 +4084,20 @@ mod tests {
         let (replacement_tx, _replacement_rx) = mpsc::channel(1);
 
         let first = writer
-            .insert_registered_tcp_writer(first_addr, first_tx, first_cancel.clone())
+            .insert_registered_tcp_writer(
+                first_addr,
+                first_tx,
+                first_cancel.clone(),
+                writer.new_connection_counters(),
+            )
             .unwrap();
         let second = writer
-            .insert_registered_tcp_writer(second_addr, second_tx, second_cancel.clone())
+            .insert_registered_tcp_writer(
+                second_addr,
+                second_tx,
+                second_cancel.clone(),
+                writer.new_connection_counters(),
+            )
             .unwrap();
         assert_eq!(
             writer
@@ -2494,7 +4118,12 @@ mod tests {
         );
 
         let replacement = writer
-            .insert_registered_tcp_writer(first_addr, replacement_tx, replacement_cancel.clone())
+            .insert_registered_tcp_writer(
+                first_addr,
+                replacement_tx,
+                replacement_cancel.clone(),
+                writer.new_connection_counters(),
+            )
             .unwrap();
         assert!(first_cancel.is_cancelled());
         writer.remove_registered_tcp_writer(first);
@@ -2524,11 +4153,12 @@ mod tests {
             remote_addr,
             cancel.clone(),
             rx,
-            writer,
+            writer.clone(),
             TcpWriterRegistration {
                 remote_addr,
                 writer_id: 1,
             },
+            &writer.new_connection_counters(),
         )
         .await
         .unwrap();
@@ -2537,6 +4167,80 @@ mod tests {
         drop(client);
     }
 
+    /// 模拟 TLS 会话：写入先进入缓冲，只有 flush 后才对端可见。
+    #[derive(Default)]
+    struct FlushOnlyWriter {
+        pending: Vec<u8>,
+        flushed: Arc<std::sync::Mutex<Vec<u8>>>,
+    }
+
+    impl tokio::io::AsyncWrite for FlushOnlyWriter {
+        fn poll_write(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            self.pending.extend_from_slice(buf);
+            std::task::Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            let pending = std::mem::take(&mut self.pending);
+            self.flushed.lock().unwrap().extend_from_slice(&pending);
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(
+            self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            self.poll_flush(cx)
+        }
+    }
+
+    #[tokio::test]
+    async fn tcp_write_flushes_buffered_stream_once_queue_drains() {
+        let stream = FlushOnlyWriter::default();
+        let flushed = stream.flushed.clone();
+        let remote_addr: SocketAddr = "127.0.0.1:9".parse().unwrap();
+        let cancel = CancellationToken::new();
+        let writer = PacketWriter::new(None, Arc::new(RawPacketEncoder), TcpWriteMode::default());
+        let (tx, rx) = mpsc::channel(4);
+        let counters = writer.new_connection_counters();
+        let task = tokio::spawn(async move {
+            handle_tcp_write(
+                stream,
+                remote_addr,
+                cancel,
+                rx,
+                writer.clone(),
+                TcpWriterRegistration {
+                    remote_addr,
+                    writer_id: 1,
+                },
+                &counters,
+            )
+            .await
+        });
+
+        tx.send(EncodedPacket::single(Bytes::from_static(b"hello")))
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while flushed.lock().unwrap().as_slice() != b"hello" {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("packet stayed buffered without a flush");
+
+        drop(tx);
+        task.await.unwrap().unwrap();
+    }
+
     #[tokio::test]
     async fn packet_writer_close_finishes_cleanup_after_prior_cancellation() {
         let udp = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
@@ -2604,12 +4308,17 @@ mod tests {
         let managed = ManagedPacketIo {
             writer: PacketWriter::new(None, Arc::new(RawPacketEncoder), TcpWriteMode::Direct),
             cancel: CancellationToken::new(),
-            connecting_tcp_peers: std::sync::Mutex::new(std::collections::HashSet::new()),
+            connecting_tcp_peers: Arc::new(std::sync::Mutex::new(std::collections::HashSet::new())),
             active_connections: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
             completed_connection_report: Arc::new(std::sync::Mutex::new(
                 NetworkCloseReport::default(),
             )),
             next_connection_id: std::sync::atomic::AtomicU64::new(1),
+            tcp_idle: TcpIdleSupervisor::default(),
+            tcp_admission: TcpAdmission::new(TcpAdmissionOptions::default()),
+            #[cfg(unix)]
+            unix_socket_files: Vec::new(),
+            udp_memberships: std::sync::Mutex::new(None),
             close_state: Mutex::new(ManagedCloseState {
                 root_task: Some(root_task),
                 report: NetworkCloseReport::default(),
@@ -2905,252 +4614,1114 @@ mod tests {
         assert_eq!(report.completed, 17);
     }
 
-    #[tokio::test]
-    async fn managed_active_tcp_reaps_naturally_finished_handle_before_reconnect() {
-        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
-        let remote_addr = server.local_addr().unwrap();
-        let dispatcher = Arc::new(TestDispatcher::default());
-        let managed =
-            managed_rw_with_tcp_write_mode::<TestDispatcher, DrainSplitter, RawPacketEncoder>(
-                &GlobalRuntime::get_main_runtime(),
-                format!("managed-active-natural-reap-{remote_addr}"),
-                (None, None),
-                CancellationToken::new(),
-                dispatcher.clone(),
-                Arc::new(RawPacketEncoder),
-                TcpWriteMode::Direct,
+    #[tokio::test]
+    async fn managed_active_tcp_reaps_naturally_finished_handle_before_reconnect() {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote_addr = server.local_addr().unwrap();
+        let dispatcher = Arc::new(TestDispatcher::default());
+        let managed =
+            managed_rw_with_tcp_write_mode::<TestDispatcher, DrainSplitter, RawPacketEncoder>(
+                &GlobalRuntime::get_main_runtime(),
+                format!("managed-active-natural-reap-{remote_addr}"),
+                (None, None),
+                CancellationToken::new(),
+                dispatcher.clone(),
+                Arc::new(RawPacketEncoder),
+                TcpWriteMode::Direct,
+            )
+            .unwrap();
+        let options = ManagedTcpConnectOptions {
+            remote_addr,
+            local_addr: None,
+            timeout: Duration::from_secs(1),
+        };
+
+        let first = managed
+            .connect_tcp::<TestDispatcher, DrainSplitter>(
+                &GlobalRuntime::get_main_runtime(),
+                "managed-active-natural-reap-first",
+                options,
+                dispatcher.clone(),
+            )
+            .await
+            .unwrap();
+        let (first_peer, _) = server.accept().await.unwrap();
+        drop(first_peer);
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while !first.task_is_finished() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(super::lock_unpoisoned(&managed.active_connections).len(), 1);
+
+        let second = managed
+            .connect_tcp::<TestDispatcher, DrainSplitter>(
+                &GlobalRuntime::get_main_runtime(),
+                "managed-active-natural-reap-second",
+                options,
+                dispatcher,
+            )
+            .await
+            .unwrap();
+        let (second_peer, _) = server.accept().await.unwrap();
+        assert_eq!(super::lock_unpoisoned(&managed.active_connections).len(), 1);
+        assert!(first.close_and_wait().await.unwrap().is_complete());
+
+        second.close_and_wait().await.unwrap();
+        drop(second_peer);
+        assert!(super::lock_unpoisoned(&managed.active_connections).is_empty());
+        assert!(managed.close_and_wait().await.unwrap().is_complete());
+    }
+
+    #[tokio::test]
+    async fn managed_active_tcp_honors_pre_cancel_and_timeout_validation() {
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let dispatcher = Arc::new(TestDispatcher::default());
+        let managed =
+            managed_rw_with_tcp_write_mode::<TestDispatcher, DrainSplitter, RawPacketEncoder>(
+                &GlobalRuntime::get_main_runtime(),
+                "managed-active-pre-cancelled",
+                (None, None),
+                cancel,
+                dispatcher.clone(),
+                Arc::new(RawPacketEncoder),
+                TcpWriteMode::Direct,
+            )
+            .unwrap();
+        let remote_addr = "127.0.0.1:9".parse().unwrap();
+        assert!(managed
+            .connect_tcp::<TestDispatcher, DrainSplitter>(
+                &GlobalRuntime::get_main_runtime(),
+                "managed-active-pre-cancelled-peer",
+                ManagedTcpConnectOptions {
+                    remote_addr,
+                    local_addr: None,
+                    timeout: Duration::from_secs(1),
+                },
+                dispatcher.clone(),
+            )
+            .await
+            .is_err());
+        assert!(managed
+            .connect_tcp::<TestDispatcher, DrainSplitter>(
+                &GlobalRuntime::get_main_runtime(),
+                "managed-active-zero-timeout",
+                ManagedTcpConnectOptions {
+                    remote_addr,
+                    local_addr: None,
+                    timeout: Duration::ZERO,
+                },
+                dispatcher,
+            )
+            .await
+            .is_err());
+        managed.close_and_wait().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn packet_writer_waits_for_exact_peer_and_times_out_without_one() {
+        let dispatcher = Arc::new(TestDispatcher::default());
+        let managed =
+            managed_rw_with_tcp_write_mode::<TestDispatcher, DrainSplitter, RawPacketEncoder>(
+                &GlobalRuntime::get_main_runtime(),
+                "managed-wait-exact-peer",
+                (
+                    Some(std::net::TcpListener::bind("127.0.0.1:0").unwrap()),
+                    None,
+                ),
+                CancellationToken::new(),
+                dispatcher,
+                Arc::new(RawPacketEncoder),
+                TcpWriteMode::Direct,
+            )
+            .unwrap();
+        let absent = "127.0.0.1:9".parse().unwrap();
+        assert!(managed
+            .writer()
+            .wait_tcp_sink(absent, Duration::from_millis(10))
+            .await
+            .is_err());
+        managed.close_and_wait().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn managed_endpoint_close_waits_for_active_tcp_connection() {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote_addr = server.local_addr().unwrap();
+        let dispatcher = Arc::new(TestDispatcher::default());
+        let managed =
+            managed_rw_with_tcp_write_mode::<TestDispatcher, DrainSplitter, RawPacketEncoder>(
+                &GlobalRuntime::get_main_runtime(),
+                format!("managed-active-owner-{remote_addr}"),
+                (None, None),
+                CancellationToken::new(),
+                dispatcher.clone(),
+                Arc::new(RawPacketEncoder),
+                TcpWriteMode::Direct,
+            )
+            .unwrap();
+        let connection = managed
+            .connect_tcp::<TestDispatcher, DrainSplitter>(
+                &GlobalRuntime::get_main_runtime(),
+                format!("managed-active-owned-peer-{remote_addr}"),
+                ManagedTcpConnectOptions {
+                    remote_addr,
+                    local_addr: None,
+                    timeout: Duration::from_secs(1),
+                },
+                dispatcher.clone(),
+            )
+            .await
+            .unwrap();
+        let (mut peer, _) = server.accept().await.unwrap();
+
+        let report = managed.close_and_wait().await.unwrap();
+        assert!(report.is_complete());
+        assert!(report.completed >= 2, "{report:?}");
+        assert_eq!(connection.close_and_wait().await.unwrap().completed, 1);
+        assert_eq!(dispatcher.tcp_closes.load(Ordering::Relaxed), 1);
+        let mut byte = [0u8; 1];
+        assert_eq!(peer.read(&mut byte).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn managed_close_waits_for_active_tcp_read_and_writer_tasks() {
+        for (name, write_mode) in [
+            ("queued", TcpWriteMode::Queued { queue_size: 4 }),
+            ("direct", TcpWriteMode::Direct),
+        ] {
+            let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let local_addr = tcp.local_addr().unwrap();
+            let dispatcher = Arc::new(TestDispatcher::default());
+            let managed =
+                managed_rw_with_tcp_write_mode::<TestDispatcher, DrainSplitter, RawPacketEncoder>(
+                    &GlobalRuntime::get_main_runtime(),
+                    format!("managed-active-{name}-{local_addr}"),
+                    (Some(tcp), None),
+                    CancellationToken::new(),
+                    dispatcher.clone(),
+                    Arc::new(RawPacketEncoder),
+                    write_mode,
+                )
+                .unwrap();
+            let writer = managed.writer();
+            let mut client = TcpStream::connect(local_addr).await.unwrap();
+            let remote_addr = client.local_addr().unwrap();
+            tokio::time::timeout(Duration::from_secs(1), async {
+                while !writer.has_tcp_writer(&remote_addr) {
+                    tokio::task::yield_now().await;
+                }
+            })
+            .await
+            .unwrap();
+            client.write_all(b"packet").await.unwrap();
+
+            let report = managed.close_and_wait().await.unwrap();
+
+            assert!(report.is_complete(), "write_mode={name}: {report:?}");
+            assert_eq!(report.cancelled, 1, "write_mode={name}");
+            assert!(report.completed >= 1, "write_mode={name}: {report:?}");
+            assert!(!writer.has_tcp_writer(&remote_addr));
+            assert_eq!(dispatcher.tcp_closes.load(Ordering::Relaxed), 1);
+            let mut byte = [0u8; 1];
+            let read = tokio::time::timeout(Duration::from_secs(1), client.read(&mut byte))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(read, 0, "write_mode={name}");
+        }
+    }
+
+    #[tokio::test]
+    async fn managed_close_rejects_an_aborted_root_task() {
+        let udp = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let local_addr = udp.local_addr().unwrap();
+        let managed =
+            managed_rw_with_tcp_write_mode::<TestDispatcher, DrainSplitter, RawPacketEncoder>(
+                &GlobalRuntime::get_main_runtime(),
+                format!("managed-aborted-root-{local_addr}"),
+                (None, Some(udp)),
+                CancellationToken::new(),
+                Arc::new(TestDispatcher::default()),
+                Arc::new(RawPacketEncoder),
+                TcpWriteMode::Direct,
+            )
+            .unwrap();
+        {
+            let state = managed.close_state.lock().await;
+            state.root_task.as_ref().unwrap().abort();
+        }
+
+        assert!(managed.close_and_wait().await.is_err());
+        let state = managed.close_state.lock().await;
+        let report = &state.report;
+        assert_eq!(report.cancelled, 1);
+        assert_eq!(report.remaining, 1);
+        assert!(!report.is_complete());
+    }
+
+    #[tokio::test]
+    async fn managed_tls_endpoints_exchange_packets_with_mutual_auth() {
+        for (name, write_mode) in [
+            ("queued", TcpWriteMode::Queued { queue_size: 4 }),
+            ("direct", TcpWriteMode::Direct),
+        ] {
+            let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let server_addr = tcp.local_addr().unwrap();
+            let server_dispatcher = Arc::new(TestDispatcher::default());
+            let server =
+                managed_rw_with_options::<TestDispatcher, DrainSplitter, RawPacketEncoder>(
+                    &GlobalRuntime::get_main_runtime(),
+                    format!("managed-tls-server-{name}-{server_addr}"),
+                    (Some(tcp), None),
+                    CancellationToken::new(),
+                    server_dispatcher.clone(),
+                    Arc::new(RawPacketEncoder),
+                    PacketIoOptions {
+                        tcp_write_mode: write_mode,
+                        tls: Some(test_tls_acceptor(ClientAuthMode::Required)),
+                        ..PacketIoOptions::default()
+                    },
+                )
+                .unwrap();
+            let client_dispatcher = Arc::new(TestDispatcher::default());
+            let client =
+                managed_rw_with_tcp_write_mode::<TestDispatcher, DrainSplitter, RawPacketEncoder>(
+                    &GlobalRuntime::get_main_runtime(),
+                    format!("managed-tls-client-{name}-{server_addr}"),
+                    (None, None),
+                    CancellationToken::new(),
+                    client_dispatcher.clone(),
+                    Arc::new(RawPacketEncoder),
+                    write_mode,
+                )
+                .unwrap();
+
+            let connection = client
+                .connect_tcp_tls::<TestDispatcher, DrainSplitter>(
+                    &GlobalRuntime::get_main_runtime(),
+                    format!("managed-tls-peer-{name}-{server_addr}"),
+                    ManagedTcpConnectOptions {
+                        remote_addr: server_addr,
+                        local_addr: None,
+                        timeout: Duration::from_secs(1),
+                    },
+                    &test_tls_connector(),
+                    client_dispatcher.clone(),
+                )
+                .await
+                .unwrap();
+            connection
+                .write(Bytes::from_static(b"outbound"))
+                .await
+                .unwrap();
+            tokio::time::timeout(Duration::from_secs(1), async {
+                while server_dispatcher.tcp_packets.load(Ordering::Relaxed) == 0 {
+                    tokio::task::yield_now().await;
+                }
+            })
+            .await
+            .unwrap();
+
+            server
+                .writer()
+                .wait_tcp_sink(connection.local_addr(), Duration::from_secs(1))
+                .await
+                .unwrap()
+                .write(Bytes::from_static(b"inbound"))
+                .await
+                .unwrap();
+            tokio::time::timeout(Duration::from_secs(1), async {
+                while client_dispatcher.tcp_packets.load(Ordering::Relaxed) == 0 {
+                    tokio::task::yield_now().await;
+                }
+            })
+            .await
+            .unwrap();
+
+            assert!(connection.close_and_wait().await.unwrap().is_complete());
+            assert!(client.close_and_wait().await.unwrap().is_complete());
+            let report = server.close_and_wait().await.unwrap();
+            assert!(report.is_complete(), "write_mode={name}: {report:?}");
+            assert_eq!(server_dispatcher.tcp_closes.load(Ordering::Relaxed), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn managed_tls_listener_drops_plaintext_peer_without_failing_endpoint() {
+        let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let local_addr = tcp.local_addr().unwrap();
+        let dispatcher = Arc::new(TestDispatcher::default());
+        let managed = managed_rw_with_options::<TestDispatcher, DrainSplitter, RawPacketEncoder>(
+            &GlobalRuntime::get_main_runtime(),
+            format!("managed-tls-plaintext-{local_addr}"),
+            (Some(tcp), None),
+            CancellationToken::new(),
+            dispatcher.clone(),
+            Arc::new(RawPacketEncoder),
+            PacketIoOptions {
+                tls: Some(test_tls_acceptor(ClientAuthMode::Disabled)),
+                ..PacketIoOptions::default()
+            },
+        )
+        .unwrap();
+
+        let mut client = TcpStream::connect(local_addr).await.unwrap();
+        let remote_addr = client.local_addr().unwrap();
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        let mut response = Vec::new();
+        tokio::time::timeout(Duration::from_secs(1), client.read_to_end(&mut response))
+            .await
+            .unwrap()
+            .ok();
+
+        assert_eq!(dispatcher.tcp_packets.load(Ordering::Relaxed), 0);
+        assert!(!managed.writer().has_tcp_writer(&remote_addr));
+        assert!(managed.close_and_wait().await.unwrap().is_complete());
+    }
+
+    #[tokio::test]
+    async fn managed_tcp_read_idle_reaps_silent_peer_and_notifies_dispatcher() {
+        for (name, write_mode) in [
+            ("queued", TcpWriteMode::Queued { queue_size: 4 }),
+            ("direct", TcpWriteMode::Direct),
+        ] {
+            let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let local_addr = tcp.local_addr().unwrap();
+            let dispatcher = Arc::new(TestDispatcher::default());
+            let managed =
+                managed_rw_with_options::<TestDispatcher, DrainSplitter, RawPacketEncoder>(
+                    &GlobalRuntime::get_main_runtime(),
+                    format!("managed-read-idle-{name}-{local_addr}"),
+                    (Some(tcp), None),
+                    CancellationToken::new(),
+                    dispatcher.clone(),
+                    Arc::new(RawPacketEncoder),
+                    PacketIoOptions {
+                        tcp_write_mode: write_mode,
+                        tcp_idle: TcpIdleOptions {
+                            read_timeout: Some(Duration::from_millis(100)),
+                            ..TcpIdleOptions::default()
+                        },
+                        ..PacketIoOptions::default()
+                    },
+                )
+                .unwrap();
+
+            let mut client = TcpStream::connect(local_addr).await.unwrap();
+            let remote_addr = client.local_addr().unwrap();
+            client.write_all(b"hello").await.unwrap();
+            let mut byte = [0u8; 1];
+            let read = tokio::time::timeout(Duration::from_secs(2), client.read(&mut byte))
+                .await
+                .unwrap();
+            assert!(matches!(read, Ok(0) | Err(_)), "write_mode={name}");
+            tokio::time::timeout(Duration::from_secs(1), async {
+                while dispatcher.tcp_closes.load(Ordering::Relaxed) == 0 {
+                    tokio::task::yield_now().await;
+                }
+            })
+            .await
+            .unwrap();
+
+            assert_eq!(dispatcher.tcp_packets.load(Ordering::Relaxed), 1);
+            assert!(!managed.writer().has_tcp_writer(&remote_addr));
+            assert_eq!(managed.stats().tcp_idle_reaped, 1, "write_mode={name}");
+            let report = managed.close_and_wait().await.unwrap();
+            assert_eq!(report.idle_reaped, 1, "write_mode={name}: {report:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn unmanaged_tcp_read_idle_reaps_silent_peer_and_counts_it() {
+        let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let local_addr = tcp.local_addr().unwrap();
+        let cancel = CancellationToken::new();
+        let dispatcher = Arc::new(TestDispatcher::default());
+        let writer = rw_with_options::<TestDispatcher, DrainSplitter, RawPacketEncoder>(
+            (Some(tcp), None),
+            cancel.clone(),
+            dispatcher.clone(),
+            Arc::new(RawPacketEncoder),
+            PacketIoOptions {
+                tcp_idle: TcpIdleOptions {
+                    read_timeout: Some(Duration::from_millis(100)),
+                    ..TcpIdleOptions::default()
+                },
+                ..PacketIoOptions::default()
+            },
+        )
+        .unwrap();
+
+        let mut client = TcpStream::connect(local_addr).await.unwrap();
+        let mut byte = [0u8; 1];
+        let read = tokio::time::timeout(Duration::from_secs(2), client.read(&mut byte))
+            .await
+            .unwrap();
+        assert!(matches!(read, Ok(0) | Err(_)));
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while dispatcher.tcp_closes.load(Ordering::Relaxed) == 0 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(writer.stats().tcp_idle_reaped, 1);
+        cancel.cancel();
+    }
+
+    #[tokio::test]
+    async fn managed_tcp_heartbeat_keeps_write_side_alive() {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote_addr = server.local_addr().unwrap();
+        let dispatcher = Arc::new(TestDispatcher::default());
+        let managed = managed_rw_with_options::<TestDispatcher, DrainSplitter, RawPacketEncoder>(
+            &GlobalRuntime::get_main_runtime(),
+            format!("managed-heartbeat-{remote_addr}"),
+            (None, None),
+            CancellationToken::new(),
+            dispatcher.clone(),
+            Arc::new(RawPacketEncoder),
+            PacketIoOptions {
+                tcp_idle: TcpIdleOptions {
+                    write_timeout: Some(Duration::from_millis(150)),
+                    heartbeat: Some(TcpHeartbeat::new(Duration::from_millis(30), |_| {
+                        Bytes::from_static(b"ping")
+                    })),
+                    ..TcpIdleOptions::default()
+                },
+                ..PacketIoOptions::default()
+            },
+        )
+        .unwrap();
+        let connection = managed
+            .connect_tcp::<TestDispatcher, DrainSplitter>(
+                &GlobalRuntime::get_main_runtime(),
+                format!("managed-heartbeat-peer-{remote_addr}"),
+                ManagedTcpConnectOptions {
+                    remote_addr,
+                    local_addr: None,
+                    timeout: Duration::from_secs(1),
+                },
+                dispatcher.clone(),
+            )
+            .await
+            .unwrap();
+        let (mut peer, _) = server.accept().await.unwrap();
+
+        let mut pings = [0u8; 12];
+        tokio::time::timeout(Duration::from_secs(2), peer.read_exact(&mut pings))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&pings, b"pingpingping");
+        assert!(managed.writer().has_tcp_writer(&connection.remote_addr()));
+
+        let report = managed.close_and_wait().await.unwrap();
+        assert_eq!(report.idle_reaped, 0, "{report:?}");
+        assert_eq!(dispatcher.tcp_closes.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn managed_tcp_admission_rejects_over_limit_peer_and_releases_on_close() {
+        let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let local_addr = tcp.local_addr().unwrap();
+        let dispatcher = Arc::new(TestDispatcher::default());
+        let managed = managed_rw_with_options::<TestDispatcher, DrainSplitter, RawPacketEncoder>(
+            &GlobalRuntime::get_main_runtime(),
+            format!("managed-admission-{local_addr}"),
+            (Some(tcp), None),
+            CancellationToken::new(),
+            dispatcher.clone(),
+            Arc::new(RawPacketEncoder),
+            PacketIoOptions {
+                tcp_admission: TcpAdmissionOptions {
+                    max_connections_per_ip: Some(1),
+                    ..TcpAdmissionOptions::default()
+                },
+                ..PacketIoOptions::default()
+            },
+        )
+        .unwrap();
+
+        let first = TcpStream::connect(local_addr).await.unwrap();
+        managed
+            .writer()
+            .wait_tcp_sink(first.local_addr().unwrap(), Duration::from_secs(1))
+            .await
+            .unwrap();
+        let mut second = TcpStream::connect(local_addr).await.unwrap();
+        let mut byte = [0u8; 1];
+        let read = tokio::time::timeout(Duration::from_secs(1), second.read(&mut byte))
+            .await
+            .unwrap();
+        assert!(matches!(read, Ok(0) | Err(_)));
+        assert_eq!(managed.tcp_admission_rejections().max_connections_per_ip, 1);
+
+        drop(first);
+        // The dispatcher hears of the close before the connection task drops its permit.
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while managed.tcp_admission.active() != 0 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(dispatcher.tcp_closes.load(Ordering::Relaxed), 1);
+        let third = TcpStream::connect(local_addr).await.unwrap();
+        managed
+            .writer()
+            .wait_tcp_sink(third.local_addr().unwrap(), Duration::from_secs(1))
+            .await
+            .unwrap();
+
+        let report = managed.close_and_wait().await.unwrap();
+        assert!(report.is_complete(), "{report:?}");
+        assert_eq!(report.rejected, 1, "{report:?}");
+    }
+
+    #[tokio::test]
+    async fn managed_stats_count_tcp_and_udp_traffic() {
+        let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let udp = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let tcp_addr = tcp.local_addr().unwrap();
+        let udp_addr = udp.local_addr().unwrap();
+        let dispatcher = Arc::new(TestDispatcher::default());
+        let managed =
+            managed_rw_with_tcp_write_mode::<TestDispatcher, DrainSplitter, RawPacketEncoder>(
+                &GlobalRuntime::get_main_runtime(),
+                format!("managed-stats-{tcp_addr}"),
+                (Some(tcp), Some(udp)),
+                CancellationToken::new(),
+                dispatcher.clone(),
+                Arc::new(RawPacketEncoder),
+                TcpWriteMode::Direct,
+            )
+            .unwrap();
+
+        let mut peer = TcpStream::connect(tcp_addr).await.unwrap();
+        let peer_addr = peer.local_addr().unwrap();
+        managed
+            .writer()
+            .wait_tcp_sink(peer_addr, Duration::from_secs(1))
+            .await
+            .unwrap();
+        peer.write_all(b"inbound").await.unwrap();
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while dispatcher.tcp_packets.load(Ordering::Relaxed) == 0 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+        managed
+            .writer()
+            .write_to(Bytes::from_static(b"out"), peer_addr, Protocol::TCP)
+            .await
+            .unwrap();
+        let mut outbound = [0u8; 3];
+        peer.read_exact(&mut outbound).await.unwrap();
+
+        let sender = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        sender.send_to(b"datagram", udp_addr).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while dispatcher.udp_packets.load(Ordering::Relaxed) == 0 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+        managed
+            .writer()
+            .write_to(
+                Bytes::from_static(b"reply"),
+                sender.local_addr().unwrap(),
+                Protocol::UDP,
+            )
+            .await
+            .unwrap();
+
+        let connection = managed.writer().tcp_connection_stats(&peer_addr).unwrap();
+        assert_eq!(connection.rx_bytes, 7);
+        assert_eq!(connection.rx_packets, 1);
+        assert_eq!(connection.tx_bytes, 3);
+        assert_eq!(connection.tx_packets, 1);
+
+        let stats = managed.stats();
+        assert_eq!(stats.tcp_accepted, 1);
+        assert_eq!(stats.tcp_active, 1);
+        assert_eq!(stats.tcp_rx_bytes, 7);
+        assert_eq!(stats.tcp_tx_bytes, 3);
+        assert_eq!(stats.udp_rx_packets, 1);
+        assert_eq!(stats.udp_rx_bytes, 8);
+        assert_eq!(stats.udp_tx_packets, 1);
+        assert_eq!(stats.udp_tx_bytes, 5);
+        assert_eq!(stats.udp_truncated, 0);
+        let json = serde_json::to_string(&stats).unwrap();
+        assert_eq!(
+            serde_json::from_str::<super::PacketIoStats>(&json).unwrap(),
+            stats
+        );
+
+        drop(peer);
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while managed.stats().tcp_closed == 0 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(managed.stats().tcp_active, 0);
+        assert!(managed.writer().tcp_connection_stats(&peer_addr).is_none());
+        assert!(managed.close_and_wait().await.unwrap().is_complete());
+    }
+
+    #[derive(Default)]
+    struct RecordingDispatcher {
+        packets: std::sync::Mutex<Vec<(Bytes, SocketAddr, Protocol)>>,
+        closes: std::sync::Mutex<Vec<(SocketAddr, Protocol)>>,
+    }
+
+    impl RecordingDispatcher {
+        async fn wait_packet(&self, protocol: Protocol) -> (Bytes, SocketAddr) {
+            tokio::time::timeout(Duration::from_secs(1), async {
+                loop {
+                    let packet = super::lock_unpoisoned(&self.packets)
+                        .iter()
+                        .find(|(_, _, packet_protocol)| *packet_protocol == protocol)
+                        .map(|(data, remote_addr, _)| (data.clone(), *remote_addr));
+                    if let Some(packet) = packet {
+                        return packet;
+                    }
+                    tokio::task::yield_now().await;
+                }
+            })
+            .await
+            .unwrap()
+        }
+    }
+
+    impl PacketDispatcher for RecordingDispatcher {
+        fn dispatch_owned(
+            &self,
+            data: Bytes,
+            remote_addr: SocketAddr,
+            protocol: Protocol,
+        ) -> GlobalResult<()> {
+            super::lock_unpoisoned(&self.packets).push((data, remote_addr, protocol));
+            Ok(())
+        }
+
+        fn close(&self, remote_addr: SocketAddr, protocol: Protocol) -> GlobalResult<()> {
+            super::lock_unpoisoned(&self.closes).push((remote_addr, protocol));
+            Ok(())
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn managed_unix_sockets_exchange_packets_and_unlink_on_close() {
+        use crate::net::listen_unix;
+        use crate::net::unix::is_unix_peer_addr;
+        use tokio::net::{UnixDatagram, UnixStream};
+
+        let dir = std::env::temp_dir().join(format!("base-managed-unix-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let stream_path = dir.join("stream.sock");
+        let datagram_path = dir.join("datagram.sock");
+        let client_path = dir.join("client.sock");
+        for path in [&stream_path, &datagram_path, &client_path] {
+            let _ = std::fs::remove_file(path);
+        }
+        drop(std::os::unix::net::UnixListener::bind(&stream_path).unwrap());
+
+        let (stream_listener, _) = listen_unix(Protocol::UnixStream, &stream_path).unwrap();
+        let (_, datagram_socket) = listen_unix(Protocol::UnixDatagram, &datagram_path).unwrap();
+        assert!(listen_unix(Protocol::TCP, &stream_path).is_err());
+        let dispatcher = Arc::new(RecordingDispatcher::default());
+        let managed = super::managed_unix_rw_with_options::<
+            RecordingDispatcher,
+            DrainSplitter,
+            RawPacketEncoder,
+        >(
+            &GlobalRuntime::get_main_runtime(),
+            format!("managed-unix-{}", std::process::id()),
+            (stream_listener, datagram_socket),
+            CancellationToken::new(),
+            dispatcher.clone(),
+            Arc::new(RawPacketEncoder),
+            PacketIoOptions::default(),
+        )
+        .unwrap();
+
+        let mut stream = UnixStream::connect(&stream_path).await.unwrap();
+        stream.write_all(b"ping").await.unwrap();
+        let (data, stream_peer) = dispatcher.wait_packet(Protocol::UnixStream).await;
+        assert_eq!(data.as_ref(), b"ping");
+        assert!(is_unix_peer_addr(&stream_peer));
+        managed
+            .writer()
+            .write_to(
+                Bytes::from_static(b"pong"),
+                stream_peer,
+                Protocol::UnixStream,
             )
+            .await
             .unwrap();
-        let options = ManagedTcpConnectOptions {
-            remote_addr,
-            local_addr: None,
-            timeout: Duration::from_secs(1),
-        };
-
-        let first = managed
-            .connect_tcp::<TestDispatcher, DrainSplitter>(
-                &GlobalRuntime::get_main_runtime(),
-                "managed-active-natural-reap-first",
-                options,
-                dispatcher.clone(),
+        let mut reply = [0u8; 4];
+        stream.read_exact(&mut reply).await.unwrap();
+        assert_eq!(&reply, b"pong");
+
+        let client = UnixDatagram::bind(&client_path).unwrap();
+        client.send_to(b"hello", &datagram_path).await.unwrap();
+        let (data, datagram_peer) = dispatcher.wait_packet(Protocol::UnixDatagram).await;
+        assert_eq!(data.as_ref(), b"hello");
+        assert_ne!(datagram_peer, stream_peer);
+        assert_eq!(
+            managed.writer().unix_peer_path(&datagram_peer),
+            Some(client_path.clone())
+        );
+        managed
+            .writer()
+            .write_to(
+                Bytes::from_static(b"reply"),
+                datagram_peer,
+                Protocol::UnixDatagram,
             )
             .await
             .unwrap();
-        let (first_peer, _) = server.accept().await.unwrap();
-        drop(first_peer);
+        let mut reply = [0u8; 16];
+        let (size, _) = client.recv_from(&mut reply).await.unwrap();
+        assert_eq!(&reply[..size], b"reply");
+
+        drop(stream);
         tokio::time::timeout(Duration::from_secs(1), async {
-            while !first.task_is_finished() {
+            while super::lock_unpoisoned(&dispatcher.closes).is_empty() {
                 tokio::task::yield_now().await;
             }
         })
         .await
         .unwrap();
-        assert_eq!(super::lock_unpoisoned(&managed.active_connections).len(), 1);
+        assert_eq!(
+            super::lock_unpoisoned(&dispatcher.closes).as_slice(),
+            &[(stream_peer, Protocol::UnixStream)]
+        );
+        assert!(managed.close_and_wait().await.unwrap().is_complete());
+        assert!(!stream_path.exists());
+        assert!(!datagram_path.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 
-        let second = managed
-            .connect_tcp::<TestDispatcher, DrainSplitter>(
-                &GlobalRuntime::get_main_runtime(),
-                "managed-active-natural-reap-second",
-                options,
-                dispatcher,
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn unmanaged_unix_datagrams_reply_and_unlink_on_cancel() {
+        use crate::net::listen_unix;
+        use tokio::net::UnixDatagram;
+
+        let dir = std::env::temp_dir().join(format!("base-unix-rw-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let datagram_path = dir.join("datagram.sock");
+        let client_path = dir.join("client.sock");
+        let _ = std::fs::remove_file(&client_path);
+        let (_, datagram_socket) = listen_unix(Protocol::UnixDatagram, &datagram_path).unwrap();
+        let dispatcher = Arc::new(RecordingDispatcher::default());
+        let cancel = CancellationToken::new();
+        let writer =
+            super::unix_rw_with_options::<RecordingDispatcher, DrainSplitter, RawPacketEncoder>(
+                (None, datagram_socket),
+                cancel.clone(),
+                dispatcher.clone(),
+                Arc::new(RawPacketEncoder),
+                PacketIoOptions::default(),
             )
+            .unwrap();
+
+        let client = UnixDatagram::bind(&client_path).unwrap();
+        client.send_to(b"hello", &datagram_path).await.unwrap();
+        let (data, peer) = dispatcher.wait_packet(Protocol::UnixDatagram).await;
+        assert_eq!(data.as_ref(), b"hello");
+        writer
+            .write_to(Bytes::from_static(b"reply"), peer, Protocol::UnixDatagram)
             .await
             .unwrap();
-        let (second_peer, _) = server.accept().await.unwrap();
-        assert_eq!(super::lock_unpoisoned(&managed.active_connections).len(), 1);
-        assert!(first.close_and_wait().await.unwrap().is_complete());
+        let mut reply = [0u8; 16];
+        let (size, _) = client.recv_from(&mut reply).await.unwrap();
+        assert_eq!(&reply[..size], b"reply");
 
-        second.close_and_wait().await.unwrap();
-        drop(second_peer);
-        assert!(super::lock_unpoisoned(&managed.active_connections).is_empty());
-        assert!(managed.close_and_wait().await.unwrap().is_complete());
+        cancel.cancel();
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while datagram_path.exists() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
     #[tokio::test]
-    async fn managed_active_tcp_honors_pre_cancel_and_timeout_validation() {
-        let cancel = CancellationToken::new();
-        cancel.cancel();
+    async fn managed_reconnecting_tcp_client_buffers_and_redials() {
+        let reservation = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let remote_addr = reservation.local_addr().unwrap();
+        drop(reservation);
         let dispatcher = Arc::new(TestDispatcher::default());
         let managed =
             managed_rw_with_tcp_write_mode::<TestDispatcher, DrainSplitter, RawPacketEncoder>(
                 &GlobalRuntime::get_main_runtime(),
-                "managed-active-pre-cancelled",
+                format!("managed-reconnect-{remote_addr}"),
                 (None, None),
-                cancel,
+                CancellationToken::new(),
                 dispatcher.clone(),
                 Arc::new(RawPacketEncoder),
-                TcpWriteMode::Direct,
+                TcpWriteMode::default(),
             )
             .unwrap();
-        let remote_addr = "127.0.0.1:9".parse().unwrap();
-        assert!(managed
-            .connect_tcp::<TestDispatcher, DrainSplitter>(
+        let connect = ManagedTcpConnectOptions {
+            remote_addr,
+            local_addr: None,
+            timeout: Duration::from_secs(1),
+        };
+        let client = managed
+            .connect_tcp_reconnecting::<TestDispatcher, DrainSplitter>(
                 &GlobalRuntime::get_main_runtime(),
-                "managed-active-pre-cancelled-peer",
-                ManagedTcpConnectOptions {
-                    remote_addr,
-                    local_addr: None,
-                    timeout: Duration::from_secs(1),
+                format!("managed-reconnect-client-{remote_addr}"),
+                ManagedTcpReconnectOptions {
+                    retry: ReconnectPolicy {
+                        initial_delay: Duration::from_millis(10),
+                        max_delay: Duration::from_millis(40),
+                        jitter_ratio: 0.0,
+                        ..ReconnectPolicy::default()
+                    },
+                    disconnected_writes: DisconnectedWritePolicy::Buffer { capacity: 2 },
+                    ..ManagedTcpReconnectOptions::new(connect)
                 },
                 dispatcher.clone(),
             )
             .await
-            .is_err());
+            .unwrap();
         assert!(managed
             .connect_tcp::<TestDispatcher, DrainSplitter>(
                 &GlobalRuntime::get_main_runtime(),
-                "managed-active-zero-timeout",
-                ManagedTcpConnectOptions {
-                    remote_addr,
-                    local_addr: None,
-                    timeout: Duration::ZERO,
-                },
-                dispatcher,
+                "managed-reconnect-duplicate",
+                connect,
+                dispatcher.clone(),
             )
             .await
             .is_err());
-        managed.close_and_wait().await.unwrap();
+        let mut state = client.state();
+        tokio::time::timeout(
+            Duration::from_secs(2),
+            state.wait_for(|state| matches!(state, ConnectionState::Disconnected { .. })),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        client.write(Bytes::from_static(b"a")).await.unwrap();
+        client.write(Bytes::from_static(b"b")).await.unwrap();
+        assert!(client.write(Bytes::from_static(b"x")).await.is_err());
+
+        let server = TcpListener::bind(remote_addr).await.unwrap();
+        let (mut peer, _) = server.accept().await.unwrap();
+        let mut buffered = [0u8; 2];
+        peer.read_exact(&mut buffered).await.unwrap();
+        assert_eq!(&buffered, b"ab");
+        let generation = match *tokio::time::timeout(
+            Duration::from_secs(2),
+            state.wait_for(|state| matches!(state, ConnectionState::Connected { .. })),
+        )
+        .await
+        .unwrap()
+        .unwrap()
+        {
+            ConnectionState::Connected { generation } => generation,
+            _ => unreachable!(),
+        };
+        client.write(Bytes::from_static(b"c")).await.unwrap();
+        let mut direct = [0u8; 1];
+        peer.read_exact(&mut direct).await.unwrap();
+        assert_eq!(&direct, b"c");
+
+        drop(peer);
+        let (mut peer, _) = tokio::time::timeout(Duration::from_secs(2), server.accept())
+            .await
+            .unwrap()
+            .unwrap();
+        tokio::time::timeout(
+            Duration::from_secs(2),
+            state.wait_for(|state| {
+                matches!(state, ConnectionState::Connected { generation: next } if *next > generation)
+            }),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        client.write(Bytes::from_static(b"d")).await.unwrap();
+        peer.read_exact(&mut direct).await.unwrap();
+        assert_eq!(&direct, b"d");
+        assert!(managed.stats().tcp_connected >= 2);
+
+        let report = managed.close_and_wait().await.unwrap();
+        assert!(report.is_complete(), "{report:?}");
+        assert_eq!(*client.state().borrow(), ConnectionState::Stopped);
+        assert!(client.write(Bytes::from_static(b"e")).await.is_err());
     }
 
     #[tokio::test]
-    async fn packet_writer_waits_for_exact_peer_and_times_out_without_one() {
-        let dispatcher = Arc::new(TestDispatcher::default());
+    async fn managed_proxy_protocol_substitutes_client_addr_and_rejects_bad_headers() {
+        let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let local_addr = tcp.local_addr().unwrap();
+        let dispatcher = Arc::new(RecordingDispatcher::default());
         let managed =
-            managed_rw_with_tcp_write_mode::<TestDispatcher, DrainSplitter, RawPacketEncoder>(
+            managed_rw_with_options::<RecordingDispatcher, DrainSplitter, RawPacketEncoder>(
                 &GlobalRuntime::get_main_runtime(),
-                "managed-wait-exact-peer",
-                (
-                    Some(std::net::TcpListener::bind("127.0.0.1:0").unwrap()),
-                    None,
-                ),
+                format!("managed-proxy-{local_addr}"),
+                (Some(tcp), None),
                 CancellationToken::new(),
-                dispatcher,
+                dispatcher.clone(),
                 Arc::new(RawPacketEncoder),
-                TcpWriteMode::Direct,
+                PacketIoOptions {
+                    proxy_protocol: Some(ProxyProtocolOptions {
+                        header_timeout: Duration::from_millis(100),
+                        ..ProxyProtocolOptions::default()
+                    }),
+                    ..PacketIoOptions::default()
+                },
             )
             .unwrap();
-        let absent = "127.0.0.1:9".parse().unwrap();
-        assert!(managed
+
+        let mut client = TcpStream::connect(local_addr).await.unwrap();
+        client
+            .write_all(b"PROXY TCP4 192.0.2.7 127.0.0.1 5000 443\r\nhello")
+            .await
+            .unwrap();
+        let client_addr: SocketAddr = "192.0.2.7:5000".parse().unwrap();
+        let (data, remote_addr) = dispatcher.wait_packet(Protocol::TCP).await;
+        assert_eq!((&data[..], remote_addr), (&b"hello"[..], client_addr));
+        assert!(managed.writer().tcp_sink_by_ip(client_addr.ip()).is_some());
+        managed
             .writer()
-            .wait_tcp_sink(absent, Duration::from_millis(10))
+            .write_to(Bytes::from_static(b"reply"), client_addr, Protocol::TCP)
             .await
-            .is_err());
-        managed.close_and_wait().await.unwrap();
+            .unwrap();
+        let mut reply = [0u8; 5];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(&reply, b"reply");
+
+        for header in [&b"GET / HTTP/1.1\r\n"[..], &b"PROXY TCP4 192.0.2.8"[..]] {
+            let mut rejected = TcpStream::connect(local_addr).await.unwrap();
+            rejected.write_all(header).await.unwrap();
+            let mut byte = [0u8; 1];
+            let read = tokio::time::timeout(Duration::from_secs(1), rejected.read(&mut byte))
+                .await
+                .unwrap();
+            assert!(matches!(read, Ok(0) | Err(_)));
+        }
+        let stats = managed.stats();
+        assert_eq!((stats.tcp_accepted, stats.tcp_rejected), (1, 2));
+
+        drop(client);
+        assert!(managed.close_and_wait().await.unwrap().is_complete());
+        let closes = super::lock_unpoisoned(&dispatcher.closes);
+        assert_eq!(closes.as_slice(), &[(client_addr, Protocol::TCP)]);
     }
 
     #[tokio::test]
-    async fn managed_endpoint_close_waits_for_active_tcp_connection() {
-        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
-        let remote_addr = server.local_addr().unwrap();
-        let dispatcher = Arc::new(TestDispatcher::default());
+    async fn managed_proxy_protocol_counts_pending_headers_against_max_connections() {
+        let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let local_addr = tcp.local_addr().unwrap();
+        let dispatcher = Arc::new(RecordingDispatcher::default());
         let managed =
-            managed_rw_with_tcp_write_mode::<TestDispatcher, DrainSplitter, RawPacketEncoder>(
+            managed_rw_with_options::<RecordingDispatcher, DrainSplitter, RawPacketEncoder>(
                 &GlobalRuntime::get_main_runtime(),
-                format!("managed-active-owner-{remote_addr}"),
-                (None, None),
+                format!("managed-proxy-pending-{local_addr}"),
+                (Some(tcp), None),
                 CancellationToken::new(),
                 dispatcher.clone(),
                 Arc::new(RawPacketEncoder),
-                TcpWriteMode::Direct,
-            )
-            .unwrap();
-        let connection = managed
-            .connect_tcp::<TestDispatcher, DrainSplitter>(
-                &GlobalRuntime::get_main_runtime(),
-                format!("managed-active-owned-peer-{remote_addr}"),
-                ManagedTcpConnectOptions {
-                    remote_addr,
-                    local_addr: None,
-                    timeout: Duration::from_secs(1),
+                PacketIoOptions {
+                    tcp_admission: TcpAdmissionOptions {
+                        max_connections: Some(1),
+                        ..TcpAdmissionOptions::default()
+                    },
+                    proxy_protocol: Some(ProxyProtocolOptions {
+                        header_timeout: Duration::from_secs(5),
+                        ..ProxyProtocolOptions::default()
+                    }),
+                    ..PacketIoOptions::default()
                 },
-                dispatcher.clone(),
             )
-            .await
             .unwrap();
-        let (mut peer, _) = server.accept().await.unwrap();
 
-        let report = managed.close_and_wait().await.unwrap();
-        assert!(report.is_complete());
-        assert!(report.completed >= 2, "{report:?}");
-        assert_eq!(connection.close_and_wait().await.unwrap().completed, 1);
-        assert_eq!(dispatcher.tcp_closes.load(Ordering::Relaxed), 1);
+        // The first stream never sends its header but still holds the only slot.
+        let _stalled = TcpStream::connect(local_addr).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let mut rejected = TcpStream::connect(local_addr).await.unwrap();
         let mut byte = [0u8; 1];
-        assert_eq!(peer.read(&mut byte).await.unwrap(), 0);
-    }
-
-    #[tokio::test]
-    async fn managed_close_waits_for_active_tcp_read_and_writer_tasks() {
-        for (name, write_mode) in [
-            ("queued", TcpWriteMode::Queued { queue_size: 4 }),
-            ("direct", TcpWriteMode::Direct),
-        ] {
-            let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
-            let local_addr = tcp.local_addr().unwrap();
-            let dispatcher = Arc::new(TestDispatcher::default());
-            let managed =
-                managed_rw_with_tcp_write_mode::<TestDispatcher, DrainSplitter, RawPacketEncoder>(
-                    &GlobalRuntime::get_main_runtime(),
-                    format!("managed-active-{name}-{local_addr}"),
-                    (Some(tcp), None),
-                    CancellationToken::new(),
-                    dispatcher.clone(),
-                    Arc::new(RawPacketEncoder),
-                    write_mode,
-                )
-                .unwrap();
-            let writer = managed.writer();
-            let mut client = TcpStream::connect(local_addr).await.unwrap();
-            let remote_addr = client.local_addr().unwrap();
-            tokio::time::timeout(Duration::from_secs(1), async {
-                while !writer.has_tcp_writer(&remote_addr) {
-                    tokio::task::yield_now().await;
-                }
-            })
+        let read = tokio::time::timeout(Duration::from_secs(1), rejected.read(&mut byte))
             .await
             .unwrap();
-            client.write_all(b"packet").await.unwrap();
-
-            let report = managed.close_and_wait().await.unwrap();
-
-            assert!(report.is_complete(), "write_mode={name}: {report:?}");
-            assert_eq!(report.cancelled, 1, "write_mode={name}");
-            assert!(report.completed >= 1, "write_mode={name}: {report:?}");
-            assert!(!writer.has_tcp_writer(&remote_addr));
-            assert_eq!(dispatcher.tcp_closes.load(Ordering::Relaxed), 1);
-            let mut byte = [0u8; 1];
-            let read = tokio::time::timeout(Duration::from_secs(1), client.read(&mut byte))
-                .await
-                .unwrap()
-                .unwrap();
-            assert_eq!(read, 0, "write_mode={name}");
-        }
+        assert!(matches!(read, Ok(0) | Err(_)));
+        assert_eq!(managed.tcp_admission_rejections().max_connections, 1);
+        assert_eq!(managed.stats().tcp_rejected, 1);
+        assert!(managed.close_and_wait().await.unwrap().is_complete());
     }
 
     #[tokio::test]
-    async fn managed_close_rejects_an_aborted_root_task() {
-        let udp = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
-        let local_addr = udp.local_addr().unwrap();
+    async fn managed_udp_multicast_joins_groups_writes_to_them_and_leaves_on_close() {
+        let group_ip = std::net::Ipv4Addr::new(239, 255, 77, 1);
+        let group = MulticastGroup::V4 {
+            group: group_ip,
+            interface: std::net::Ipv4Addr::LOCALHOST,
+        };
+        let udp = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+        let group_addr = SocketAddr::from((group_ip, udp.local_addr().unwrap().port()));
+        let dispatcher = Arc::new(RecordingDispatcher::default());
         let managed =
-            managed_rw_with_tcp_write_mode::<TestDispatcher, DrainSplitter, RawPacketEncoder>(
+            managed_rw_with_options::<RecordingDispatcher, DrainSplitter, RawPacketEncoder>(
                 &GlobalRuntime::get_main_runtime(),
-                format!("managed-aborted-root-{local_addr}"),
+                format!("managed-multicast-{group_addr}"),
                 (None, Some(udp)),
                 CancellationToken::new(),
-                Arc::new(TestDispatcher::default()),
+                dispatcher.clone(),
                 Arc::new(RawPacketEncoder),
-                TcpWriteMode::Direct,
+                PacketIoOptions {
+                    udp: UdpSocketOptions {
+                        multicast_groups: vec![group],
+                        multicast_loopback: Some(true),
+                        multicast_interface: Some(MulticastInterface::V4(
+                            std::net::Ipv4Addr::LOCALHOST,
+                        )),
+                        ..UdpSocketOptions::default()
+                    },
+                    ..PacketIoOptions::default()
+                },
             )
             .unwrap();
-        {
-            let state = managed.close_state.lock().await;
-            state.root_task.as_ref().unwrap().abort();
-        }
+        assert_eq!(managed.multicast_groups(), vec![group]);
 
-        assert!(managed.close_and_wait().await.is_err());
-        let state = managed.close_state.lock().await;
-        let report = &state.report;
-        assert_eq!(report.cancelled, 1);
-        assert_eq!(report.remaining, 1);
-        assert!(!report.is_complete());
+        managed
+            .writer()
+            .write_to(Bytes::from_static(b"discover"), group_addr, Protocol::UDP)
+            .await
+            .unwrap();
+        let (data, _) = dispatcher.wait_packet(Protocol::UDP).await;
+        assert_eq!(&data[..], b"discover");
+
+        let other = MulticastGroup::V4 {
+            group: std::net::Ipv4Addr::new(239, 255, 77, 2),
+            interface: std::net::Ipv4Addr::LOCALHOST,
+        };
+        managed.join_multicast(other).unwrap();
+        managed.leave_multicast(other).unwrap();
+        assert!(managed.leave_multicast(other).is_err());
+
+        assert!(managed.close_and_wait().await.unwrap().is_complete());
+        assert!(managed.multicast_groups().is_empty());
+        assert!(managed.join_multicast(group).is_err());
     }
 }
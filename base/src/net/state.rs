@@ -7,6 +7,8 @@ pub const CHANNEL_BUFFER_SIZE: usize = 10000;
 pub const UDP: &str = "UDP";
 pub const TCP: &str = "TCP";
 pub const ALL: &str = "ALL";
+pub const UNIX_STREAM: &str = "UNIX_STREAM";
+pub const UNIX_DATAGRAM: &str = "UNIX_DATAGRAM";
 
 #[derive(Debug)]
 pub enum IoEventType {
@@ -39,6 +41,10 @@ pub enum Protocol {
     UDP,
     TCP,
     ALL,
+    /// Unix domain stream socket; peers are named by synthetic addresses, see [`crate::net::unix`].
+    UnixStream,
+    /// Unix domain datagram socket; peers are named by synthetic addresses, see [`crate::net::unix`].
+    UnixDatagram,
 }
 
 impl Display for Protocol {
@@ -47,6 +53,8 @@ impl Display for Protocol {
             Protocol::UDP => write!(f, "UDP"),
             Protocol::TCP => write!(f, "TCP"),
             Protocol::ALL => write!(f, "ALL"),
+            Protocol::UnixStream => write!(f, "UNIX_STREAM"),
+            Protocol::UnixDatagram => write!(f, "UNIX_DATAGRAM"),
         }
     }
 }
@@ -57,6 +65,8 @@ impl Protocol {
             Protocol::UDP => UDP,
             Protocol::TCP => TCP,
             Protocol::ALL => ALL,
+            Protocol::UnixStream => UNIX_STREAM,
+            Protocol::UnixDatagram => UNIX_DATAGRAM,
         }
     }
 }
@@ -0,0 +1,179 @@
+//! Lock-free traffic counters for packet I/O endpoints.
+//!
+//! Counters are plain relaxed atomics updated on the I/O paths; snapshots are not taken
+//! atomically across fields, so derived values such as `tcp_active` are best-effort. Unix stream
+//! and datagram traffic is counted under the `tcp_*` and `udp_*` fields respectively.
+
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Point-in-time counters of one packet I/O endpoint.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PacketIoStats {
+    /// Inbound TCP streams that passed admission.
+    pub tcp_accepted: u64,
+    /// Outbound TCP connections opened through the endpoint.
+    pub tcp_connected: u64,
+    pub tcp_closed: u64,
+    pub tcp_active: u64,
+    pub tcp_rx_bytes: u64,
+    pub tcp_rx_packets: u64,
+    pub tcp_tx_bytes: u64,
+    pub tcp_tx_packets: u64,
+    /// Packets dropped by `try_write` because the queued TCP writer was full.
+    pub tcp_queue_full_drops: u64,
+    /// TCP connections closed by idle supervision, on managed and unmanaged endpoints alike.
+    pub tcp_idle_reaped: u64,
+    /// Inbound TCP streams dropped by admission or for a missing or malformed PROXY header.
+    pub tcp_rejected: u64,
+    /// Splitter failures; each one closes its connection.
+    pub splitter_errors: u64,
+    pub udp_rx_bytes: u64,
+    pub udp_rx_packets: u64,
+    pub udp_tx_bytes: u64,
+    pub udp_tx_packets: u64,
+    /// Datagrams that filled the whole receive buffer and may have been cut short.
+    pub udp_truncated: u64,
+}
+
+/// Point-in-time counters of one TCP connection.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TcpConnectionStats {
+    pub remote_addr: SocketAddr,
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+    pub queue_full_drops: u64,
+}
+
+#[derive(Default)]
+pub(crate) struct PacketIoCounters {
+    pub(crate) tcp_accepted: AtomicU64,
+    pub(crate) tcp_connected: AtomicU64,
+    pub(crate) tcp_closed: AtomicU64,
+    tcp_rx_bytes: AtomicU64,
+    tcp_rx_packets: AtomicU64,
+    tcp_tx_bytes: AtomicU64,
+    tcp_tx_packets: AtomicU64,
+    tcp_queue_full_drops: AtomicU64,
+    pub(crate) tcp_idle_reaped: AtomicU64,
+    pub(crate) tcp_rejected: AtomicU64,
+    splitter_errors: AtomicU64,
+    udp_rx_bytes: AtomicU64,
+    udp_rx_packets: AtomicU64,
+    udp_tx_bytes: AtomicU64,
+    udp_tx_packets: AtomicU64,
+    udp_truncated: AtomicU64,
+}
+
+impl PacketIoCounters {
+    pub(crate) fn incr(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_udp_rx(&self, bytes: usize, truncated: bool) {
+        Self::incr(&self.udp_rx_packets);
+        self.udp_rx_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        if truncated {
+            Self::incr(&self.udp_truncated);
+        }
+    }
+
+    pub(crate) fn record_udp_tx(&self, bytes: usize) {
+        Self::incr(&self.udp_tx_packets);
+        self.udp_tx_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> PacketIoStats {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let tcp_closed = load(&self.tcp_closed);
+        PacketIoStats {
+            tcp_accepted: load(&self.tcp_accepted),
+            tcp_connected: load(&self.tcp_connected),
+            tcp_closed,
+            tcp_active: (load(&self.tcp_accepted) + load(&self.tcp_connected))
+                .saturating_sub(tcp_closed),
+            tcp_rx_bytes: load(&self.tcp_rx_bytes),
+            tcp_rx_packets: load(&self.tcp_rx_packets),
+            tcp_tx_bytes: load(&self.tcp_tx_bytes),
+            tcp_tx_packets: load(&self.tcp_tx_packets),
+            tcp_queue_full_drops: load(&self.tcp_queue_full_drops),
+            tcp_idle_reaped: load(&self.tcp_idle_reaped),
+            tcp_rejected: load(&self.tcp_rejected),
+            splitter_errors: load(&self.splitter_errors),
+            udp_rx_bytes: load(&self.udp_rx_bytes),
+            udp_rx_packets: load(&self.udp_rx_packets),
+            udp_tx_bytes: load(&self.udp_tx_bytes),
+            udp_tx_packets: load(&self.udp_tx_packets),
+            udp_truncated: load(&self.udp_truncated),
+        }
+    }
+}
+
+/// Per-connection counters that also feed the owning endpoint totals.
+pub(crate) struct TcpConnectionCounters {
+    endpoint: Arc<PacketIoCounters>,
+    rx_bytes: AtomicU64,
+    rx_packets: AtomicU64,
+    tx_bytes: AtomicU64,
+    tx_packets: AtomicU64,
+    queue_full_drops: AtomicU64,
+}
+
+impl TcpConnectionCounters {
+    pub(crate) fn new(endpoint: Arc<PacketIoCounters>) -> Arc<Self> {
+        Arc::new(Self {
+            endpoint,
+            rx_bytes: AtomicU64::new(0),
+            rx_packets: AtomicU64::new(0),
+            tx_bytes: AtomicU64::new(0),
+            tx_packets: AtomicU64::new(0),
+            queue_full_drops: AtomicU64::new(0),
+        })
+    }
+
+    pub(crate) fn record_rx_bytes(&self, bytes: usize) {
+        self.rx_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.endpoint
+            .tcp_rx_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_rx_packet(&self) {
+        PacketIoCounters::incr(&self.rx_packets);
+        PacketIoCounters::incr(&self.endpoint.tcp_rx_packets);
+    }
+
+    pub(crate) fn record_tx(&self, bytes: usize) {
+        PacketIoCounters::incr(&self.tx_packets);
+        PacketIoCounters::incr(&self.endpoint.tcp_tx_packets);
+        self.tx_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.endpoint
+            .tcp_tx_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_queue_full(&self) {
+        PacketIoCounters::incr(&self.queue_full_drops);
+        PacketIoCounters::incr(&self.endpoint.tcp_queue_full_drops);
+    }
+
+    pub(crate) fn record_splitter_error(&self) {
+        PacketIoCounters::incr(&self.endpoint.splitter_errors);
+    }
+
+    pub(crate) fn snapshot(&self, remote_addr: SocketAddr) -> TcpConnectionStats {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        TcpConnectionStats {
+            remote_addr,
+            rx_bytes: load(&self.rx_bytes),
+            rx_packets: load(&self.rx_packets),
+            tx_bytes: load(&self.tx_bytes),
+            tx_packets: load(&self.tx_packets),
+            queue_full_drops: load(&self.queue_full_drops),
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use exception::{GlobalError, GlobalResult, GlobalResultExt};
+use log::{debug, error};
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::select;
+use tokio_rustls::rustls::crypto::{ring, CryptoProvider};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore, ServerConfig};
+use tokio_rustls::{client, server, TlsAcceptor, TlsConnector};
+use tokio_util::sync::CancellationToken;
+
+pub use crate::utils::tls::{ClientAuthMode, TlsFileConfig};
+pub use tokio_rustls::rustls;
+
+#[derive(Debug, Clone)]
+pub struct TcpTlsServerConfig {
+    pub certificate_pem: Vec<u8>,
+    pub private_key_pem: Vec<u8>,
+    pub client_ca_pem: Option<Vec<u8>>,
+    pub client_auth: ClientAuthMode,
+    pub handshake_timeout: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct TcpTlsClientConfig {
+    /// Name verified against the server certificate; the remote IP is used when absent.
+    pub domain_name: Option<String>,
+    pub ca_certificate_pem: Option<Vec<u8>>,
+    pub client_certificate_pem: Option<Vec<u8>>,
+    pub client_private_key_pem: Option<Vec<u8>>,
+    /// Also trusts the platform root certificates.
+    pub use_native_roots: bool,
+    pub handshake_timeout: Duration,
+}
+
+pub fn load_server_tls_from_files(config: &TlsFileConfig) -> GlobalResult<TcpTlsServerConfig> {
+    let certificate_path = required_path(&config.certificate_path, "certificate_path")?;
+    let private_key_path = required_path(&config.private_key_path, "private_key_path")?;
+    Ok(TcpTlsServerConfig {
+        certificate_pem: read_file(certificate_path)?,
+        private_key_pem: read_file(private_key_path)?,
+        client_ca_pem: config
+            .ca_certificate_path
+            .as_ref()
+            .map(read_file)
+            .transpose()?,
+        client_auth: config.client_auth,
+        handshake_timeout: config.handshake_timeout,
+    })
+}
+
+pub fn load_client_tls_from_files(config: &TlsFileConfig) -> GlobalResult<TcpTlsClientConfig> {
+    // Without native roots the CA file is the only trust anchor.
+    if !config.use_native_roots {
+        required_path(&config.ca_certificate_path, "ca_certificate_path")?;
+    }
+    Ok(TcpTlsClientConfig {
+        domain_name: config.domain_name.clone(),
+        ca_certificate_pem: config
+            .ca_certificate_path
+            .as_ref()
+            .map(read_file)
+            .transpose()?,
+        client_certificate_pem: config
+            .client_certificate_path
+            .as_ref()
+            .map(read_file)
+            .transpose()?,
+        client_private_key_pem: config
+            .client_private_key_path
+            .as_ref()
+            .map(read_file)
+            .transpose()?,
+        use_native_roots: config.use_native_roots,
+        handshake_timeout: config.handshake_timeout,
+    })
+}
+
+fn required_path<'a>(path: &'a Option<PathBuf>, field: &str) -> GlobalResult<&'a PathBuf> {
+    path.as_ref().ok_or_else(|| {
+        GlobalError::new_sys_error("tls file path is required", |msg| {
+            error!("{msg}: field={field}")
+        })
+    })
+}
+
+fn read_file(path: &PathBuf) -> GlobalResult<Vec<u8>> {
+    fs::read(path).hand_log(|msg| error!("{msg}: path={}", path.display()))
+}
+
+impl TcpTlsServerConfig {
+    pub fn build(&self) -> GlobalResult<TcpTlsAcceptor> {
+        let provider = crypto_provider();
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .hand_log(|msg| error!("{msg}"))?;
+        let builder = match (self.client_auth, &self.client_ca_pem) {
+            (ClientAuthMode::Disabled, _) => builder.with_no_client_auth(),
+            (_, None) => {
+                return Err(GlobalError::new_sys_error(
+                    "tls client auth requires client_ca_pem",
+                    |msg| error!("{msg}: client_auth={:?}", self.client_auth),
+                ));
+            }
+            (client_auth, Some(client_ca_pem)) => {
+                let roots = Arc::new(root_store(client_ca_pem)?);
+                let verifier = WebPkiClientVerifier::builder_with_provider(roots, provider);
+                let verifier = if client_auth == ClientAuthMode::Optional {
+                    verifier.allow_unauthenticated()
+                } else {
+                    verifier
+                };
+                builder.with_client_cert_verifier(verifier.build().hand_log(|msg| error!("{msg}"))?)
+            }
+        };
+        let config = builder
+            .with_single_cert(
+                certificates(&self.certificate_pem)?,
+                private_key(&self.private_key_pem)?,
+            )
+            .hand_log(|msg| error!("{msg}"))?;
+        Ok(TcpTlsAcceptor::new(
+            Arc::new(config),
+            self.handshake_timeout,
+        ))
+    }
+}
+
+impl TcpTlsClientConfig {
+    pub fn build(&self) -> GlobalResult<TcpTlsConnector> {
+        let builder = ClientConfig::builder_with_provider(crypto_provider())
+            .with_safe_default_protocol_versions()
+            .hand_log(|msg| error!("{msg}"))?
+            .with_root_certificates(self.root_store()?);
+        let config = match (&self.client_certificate_pem, &self.client_private_key_pem) {
+            (Some(certificate), Some(private_key)) => builder
+                .with_client_auth_cert(certificates(certificate)?, self::private_key(private_key)?)
+                .hand_log(|msg| error!("{msg}"))?,
+            (None, None) => builder.with_no_client_auth(),
+            _ => {
+                return Err(GlobalError::new_sys_error(
+                    "tls client certificate and private key must be set together",
+                    |msg| error!("{msg}"),
+                ));
+            }
+        };
+        let server_name = self
+            .domain_name
+            .clone()
+            .map(ServerName::try_from)
+            .transpose()
+            .hand_log(|msg| error!("{msg}: domain_name={:?}", self.domain_name))?;
+        Ok(TcpTlsConnector::new(
+            Arc::new(config),
+            server_name,
+            self.handshake_timeout,
+        ))
+    }
+
+    fn root_store(&self) -> GlobalResult<RootCertStore> {
+        let mut roots = match &self.ca_certificate_pem {
+            Some(pem) => root_store(pem)?,
+            None => RootCertStore::empty(),
+        };
+        if self.use_native_roots {
+            let native = rustls_native_certs::load_native_certs();
+            for error in &native.errors {
+                debug!("skipped native root certificates: {error}");
+            }
+            roots.add_parsable_certificates(native.certs);
+        }
+        if roots.is_empty() {
+            return Err(GlobalError::new_sys_error(
+                "tls client has no trusted root certificate",
+                |msg| error!("{msg}: use_native_roots={}", self.use_native_roots),
+            ));
+        }
+        Ok(roots)
+    }
+}
+
+fn crypto_provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+fn certificates(pem: &[u8]) -> GlobalResult<Vec<CertificateDer<'static>>> {
+    let certificates = CertificateDer::pem_slice_iter(pem)
+        .collect::<Result<Vec<_>, _>>()
+        .hand_log(|msg| error!("{msg}"))?;
+    if certificates.is_empty() {
+        return Err(GlobalError::new_sys_error(
+            "tls pem contains no certificate",
+            |msg| error!("{msg}"),
+        ));
+    }
+    Ok(certificates)
+}
+
+fn private_key(pem: &[u8]) -> GlobalResult<PrivateKeyDer<'static>> {
+    PrivateKeyDer::from_pem_slice(pem).hand_log(|msg| error!("{msg}"))
+}
+
+fn root_store(pem: &[u8]) -> GlobalResult<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for certificate in certificates(pem)? {
+        roots.add(certificate).hand_log(|msg| error!("{msg}"))?;
+    }
+    Ok(roots)
+}
+
+/// Server side TLS termination for accepted TCP connections.
+#[derive(Clone)]
+pub struct TcpTlsAcceptor {
+    acceptor: TlsAcceptor,
+    handshake_timeout: Duration,
+}
+
+impl TcpTlsAcceptor {
+    pub fn new(config: Arc<ServerConfig>, handshake_timeout: Duration) -> Self {
+        Self {
+            acceptor: TlsAcceptor::from(config),
+            handshake_timeout,
+        }
+    }
+
+    pub(crate) async fn accept<IO>(
+        &self,
+        stream: IO,
+        remote_addr: SocketAddr,
+        cancel: &CancellationToken,
+    ) -> GlobalResult<server::TlsStream<IO>>
+    where
+        IO: AsyncRead + AsyncWrite + Unpin,
+    {
+        select! {
+            biased;
+            _ = cancel.cancelled() => Err(tls_handshake_cancelled(remote_addr)),
+            result = tokio::time::timeout(self.handshake_timeout, self.acceptor.accept(stream)) => {
+                match result {
+                    Ok(result) => result.hand_log(|msg| debug!("{msg}: remote_addr={remote_addr}")),
+                    Err(_) => Err(tls_handshake_timed_out(remote_addr)),
+                }
+            }
+        }
+    }
+}
+
+/// Client side TLS for actively opened TCP connections.
+#[derive(Clone)]
+pub struct TcpTlsConnector {
+    connector: TlsConnector,
+    server_name: Option<ServerName<'static>>,
+    handshake_timeout: Duration,
+}
+
+impl TcpTlsConnector {
+    pub fn new(
+        config: Arc<ClientConfig>,
+        server_name: Option<ServerName<'static>>,
+        handshake_timeout: Duration,
+    ) -> Self {
+        Self {
+            connector: TlsConnector::from(config),
+            server_name,
+            handshake_timeout,
+        }
+    }
+
+    pub(crate) async fn connect<IO>(
+        &self,
+        stream: IO,
+        remote_addr: SocketAddr,
+        cancel: &CancellationToken,
+    ) -> GlobalResult<client::TlsStream<IO>>
+    where
+        IO: AsyncRead + AsyncWrite + Unpin,
+    {
+        let server_name = self
+            .server_name
+            .clone()
+            .unwrap_or_else(|| ServerName::from(remote_addr.ip()));
+        select! {
+            biased;
+            _ = cancel.cancelled() => Err(tls_handshake_cancelled(remote_addr)),
+            result = tokio::time::timeout(
+                self.handshake_timeout,
+                self.connector.connect(server_name, stream),
+            ) => {
+                match result {
+                    Ok(result) => result.hand_log(|msg| debug!("{msg}: remote_addr={remote_addr}")),
+                    Err(_) => Err(tls_handshake_timed_out(remote_addr)),
+                }
+            }
+        }
+    }
+}
+
+fn tls_handshake_cancelled(remote_addr: SocketAddr) -> GlobalError {
+    GlobalError::new_sys_error("tls handshake cancelled", |msg| {
+        debug!("{msg}: remote_addr={remote_addr}")
+    })
+}
+
+fn tls_handshake_timed_out(remote_addr: SocketAddr) -> GlobalError {
+    GlobalError::new_sys_error("tls handshake timed out", |msg| {
+        debug!("{msg}: remote_addr={remote_addr}")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::tls::DEFAULT_HANDSHAKE_TIMEOUT;
+
+    #[test]
+    fn rejects_client_auth_without_ca_and_half_client_identity() {
+        let server = TcpTlsServerConfig {
+            certificate_pem: include_bytes!("../../tests/fixtures/tls/server.pem").to_vec(),
+            private_key_pem: include_bytes!("../../tests/fixtures/tls/server-key.pem").to_vec(),
+            client_ca_pem: None,
+            client_auth: ClientAuthMode::Required,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+        };
+        assert!(server.build().is_err());
+        assert!(TcpTlsServerConfig {
+            client_auth: ClientAuthMode::Disabled,
+            ..server
+        }
+        .build()
+        .is_ok());
+
+        let client = TcpTlsClientConfig {
+            domain_name: None,
+            ca_certificate_pem: Some(include_bytes!("../../tests/fixtures/tls/ca.pem").to_vec()),
+            client_certificate_pem: Some(
+                include_bytes!("../../tests/fixtures/tls/client.pem").to_vec(),
+            ),
+            client_private_key_pem: None,
+            use_native_roots: false,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+        };
+        assert!(client.build().is_err());
+        assert!(TcpTlsClientConfig {
+            client_certificate_pem: None,
+            ..client.clone()
+        }
+        .build()
+        .is_ok());
+        assert!(TcpTlsClientConfig {
+            ca_certificate_pem: None,
+            client_certificate_pem: None,
+            ..client
+        }
+        .build()
+        .is_err());
+    }
+
+    #[test]
+    fn loads_server_and_client_material_from_files() {
+        let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls");
+        let config = TlsFileConfig {
+            certificate_path: Some(fixtures.join("server.pem")),
+            private_key_path: Some(fixtures.join("server-key.pem")),
+            ca_certificate_path: Some(fixtures.join("ca.pem")),
+            client_certificate_path: Some(fixtures.join("client.pem")),
+            client_private_key_path: Some(fixtures.join("client-key.pem")),
+            domain_name: Some("localhost".to_string()),
+            client_auth: ClientAuthMode::Optional,
+            ..TlsFileConfig::default()
+        };
+        load_server_tls_from_files(&config)
+            .unwrap()
+            .build()
+            .unwrap();
+        load_client_tls_from_files(&config)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(load_client_tls_from_files(&TlsFileConfig::default()).is_err());
+    }
+}
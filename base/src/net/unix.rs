@@ -0,0 +1,368 @@
+//! Unix domain socket transport for packet I/O.
+//!
+//! Unix peers have no IP address, so packet I/O names each one with a synthetic [`SocketAddr`]
+//! in the IPv6 discard-only prefix `100::/64` (RFC 6666), which never belongs to a real peer.
+//! Dispatchers receive that address and hand it back to
+//! [`PacketWriter`](crate::net::rw::PacketWriter) exactly like a TCP or UDP peer address.
+
+use crate::net::state::Protocol;
+use std::net::{Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const UNIX_PEER_PREFIX: u16 = 0x0100;
+
+static NEXT_UNIX_PEER_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Returns true when `addr` is a synthetic Unix socket peer address.
+pub fn is_unix_peer_addr(addr: &SocketAddr) -> bool {
+    match addr {
+        SocketAddr::V6(addr) => addr.ip().segments()[..4] == [UNIX_PEER_PREFIX, 0, 0, 0],
+        SocketAddr::V4(_) => false,
+    }
+}
+
+/// Allocates a peer address that is unique for the lifetime of the process.
+pub(crate) fn next_unix_peer_addr() -> SocketAddr {
+    let id = NEXT_UNIX_PEER_ID.fetch_add(1, Ordering::Relaxed);
+    let [a, b, c, d] = [id >> 48, id >> 32, id >> 16, id].map(|part| part as u16);
+    SocketAddr::from((Ipv6Addr::new(UNIX_PEER_PREFIX, 0, 0, 0, a, b, c, d), 0))
+}
+
+/// Protocol reported to the dispatcher for a stream peer.
+pub(crate) fn stream_protocol(remote_addr: &SocketAddr) -> Protocol {
+    if is_unix_peer_addr(remote_addr) {
+        Protocol::UnixStream
+    } else {
+        Protocol::TCP
+    }
+}
+
+#[cfg(unix)]
+pub use socket::{bind_unix_datagram, bind_unix_listener};
+#[cfg(unix)]
+pub(crate) use socket::{UnixDatagramSocket, UnixSocketFile};
+
+#[cfg(unix)]
+mod socket {
+    use super::next_unix_peer_addr;
+    use crate::net::state::Protocol;
+    use dashmap::DashMap;
+    use exception::{GlobalError, GlobalResult, GlobalResultExt};
+    use log::{debug, error};
+    use std::fs;
+    use std::io::{self, ErrorKind};
+    use std::net::SocketAddr;
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+    use std::os::unix::net::{UnixDatagram as StdUnixDatagram, UnixListener, UnixStream};
+    use std::path::{Path, PathBuf};
+    use std::task::{ready, Context, Poll};
+    use std::time::{Duration, Instant};
+    use tokio::io::ReadBuf;
+    use tokio::net::UnixDatagram;
+
+    const MAX_DATAGRAM_PEERS: usize = 4096;
+    const DATAGRAM_PEER_IDLE: Duration = Duration::from_secs(600);
+
+    /// Binds a stream socket at `path`, first removing a socket file no process listens on.
+    pub fn bind_unix_listener(path: impl AsRef<Path>) -> GlobalResult<UnixListener> {
+        let path = path.as_ref();
+        remove_stale_socket(path, Protocol::UnixStream)?;
+        UnixListener::bind(path).hand_log(|msg| error!("{msg}: path={}", path.display()))
+    }
+
+    /// Binds a datagram socket at `path`, first removing a socket file no process receives on.
+    pub fn bind_unix_datagram(path: impl AsRef<Path>) -> GlobalResult<StdUnixDatagram> {
+        let path = path.as_ref();
+        remove_stale_socket(path, Protocol::UnixDatagram)?;
+        StdUnixDatagram::bind(path).hand_log(|msg| error!("{msg}: path={}", path.display()))
+    }
+
+    /// Leaves missing paths alone, refuses non-socket files and sockets that still accept
+    /// connections, and unlinks sockets whose owner is gone.
+    fn remove_stale_socket(path: &Path, protocol: Protocol) -> GlobalResult<()> {
+        let metadata = match fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(error) => {
+                return Err(error).hand_log(|msg| error!("{msg}: path={}", path.display()))
+            }
+        };
+        if !metadata.file_type().is_socket() {
+            return Err(GlobalError::new_sys_error(
+                "unix socket path exists and is not a socket",
+                |msg| error!("{msg}: path={}", path.display()),
+            ));
+        }
+        let probe = match protocol {
+            Protocol::UnixDatagram => {
+                StdUnixDatagram::unbound().and_then(|socket| socket.connect(path))
+            }
+            _ => UnixStream::connect(path).map(drop),
+        };
+        match probe {
+            Ok(()) => Err(GlobalError::new_sys_error(
+                "unix socket path is in use",
+                |msg| error!("{msg}: path={}", path.display()),
+            )),
+            Err(error) if error.kind() == ErrorKind::ConnectionRefused => {
+                debug!("remove stale unix socket: path={}", path.display());
+                fs::remove_file(path).hand_log(|msg| error!("{msg}: path={}", path.display()))
+            }
+            Err(error) => Err(error).hand_log(|msg| error!("{msg}: path={}", path.display())),
+        }
+    }
+
+    /// Socket file created by a managed endpoint bind.
+    ///
+    /// The inode is recorded so that close never unlinks a file another process bound later.
+    pub(crate) struct UnixSocketFile {
+        path: PathBuf,
+        dev: u64,
+        ino: u64,
+    }
+
+    impl UnixSocketFile {
+        pub(crate) fn bound(path: Option<&Path>) -> Option<Self> {
+            let path = path?;
+            let metadata = fs::symlink_metadata(path).ok()?;
+            Some(Self {
+                path: path.to_path_buf(),
+                dev: metadata.dev(),
+                ino: metadata.ino(),
+            })
+        }
+
+        pub(crate) fn remove(&self) {
+            let owned = fs::symlink_metadata(&self.path)
+                .is_ok_and(|metadata| metadata.dev() == self.dev && metadata.ino() == self.ino);
+            if !owned {
+                return;
+            }
+            if let Err(error) = fs::remove_file(&self.path) {
+                debug!(
+                    "remove unix socket failed: path={}, error={error}",
+                    self.path.display()
+                );
+            }
+        }
+    }
+
+    struct DatagramPeer {
+        path: PathBuf,
+        last_seen: Instant,
+    }
+
+    /// Datagram socket plus the mapping between sender paths and synthetic peer addresses.
+    ///
+    /// A named sender keeps its address while it keeps sending. Once the table is full, a new
+    /// sender first drops every peer silent for the idle period and, failing that, the one
+    /// heard from least recently; a dropped peer cannot be replied to and gets a new address
+    /// with its next datagram. Unnamed senders get a fresh address per datagram and cannot be
+    /// replied to.
+    pub(crate) struct UnixDatagramSocket {
+        socket: UnixDatagram,
+        peers_by_path: DashMap<PathBuf, SocketAddr>,
+        paths_by_peer: DashMap<SocketAddr, DatagramPeer>,
+        max_peers: usize,
+        peer_idle: Duration,
+    }
+
+    impl UnixDatagramSocket {
+        pub(crate) fn new(socket: UnixDatagram) -> Self {
+            Self::with_peer_limits(socket, MAX_DATAGRAM_PEERS, DATAGRAM_PEER_IDLE)
+        }
+
+        pub(super) fn with_peer_limits(
+            socket: UnixDatagram,
+            max_peers: usize,
+            peer_idle: Duration,
+        ) -> Self {
+            Self {
+                socket,
+                peers_by_path: DashMap::new(),
+                paths_by_peer: DashMap::new(),
+                max_peers,
+                peer_idle,
+            }
+        }
+
+        pub(crate) fn local_path(&self) -> Option<PathBuf> {
+            let addr = self.socket.local_addr().ok()?;
+            addr.as_pathname().map(Path::to_path_buf)
+        }
+
+        pub(crate) fn peer_path(&self, remote_addr: &SocketAddr) -> Option<PathBuf> {
+            self.paths_by_peer
+                .get(remote_addr)
+                .map(|peer| peer.path.clone())
+        }
+
+        pub(crate) fn poll_recv_from(
+            &self,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<SocketAddr>> {
+            let addr = ready!(self.socket.poll_recv_from(cx, buf))?;
+            let Some(path) = addr.as_pathname() else {
+                return Poll::Ready(Ok(next_unix_peer_addr()));
+            };
+            let now = Instant::now();
+            let known = self.peers_by_path.get(path).map(|peer| *peer);
+            if let Some(peer) = known {
+                if let Some(mut seen) = self.paths_by_peer.get_mut(&peer) {
+                    seen.last_seen = now;
+                    return Poll::Ready(Ok(peer));
+                }
+            }
+            if self.paths_by_peer.len() >= self.max_peers {
+                self.evict_peers(now);
+            }
+            let peer = next_unix_peer_addr();
+            self.paths_by_peer.insert(
+                peer,
+                DatagramPeer {
+                    path: path.to_path_buf(),
+                    last_seen: now,
+                },
+            );
+            self.peers_by_path.insert(path.to_path_buf(), peer);
+            Poll::Ready(Ok(peer))
+        }
+
+        /// Drops idle peers, or the least recently seen one when none is idle.
+        fn evict_peers(&self, now: Instant) {
+            self.paths_by_peer
+                .retain(|_, peer| now.duration_since(peer.last_seen) < self.peer_idle);
+            if self.paths_by_peer.len() >= self.max_peers {
+                let oldest = self
+                    .paths_by_peer
+                    .iter()
+                    .min_by_key(|peer| peer.last_seen)
+                    .map(|peer| *peer.key());
+                if let Some(oldest) = oldest {
+                    self.paths_by_peer.remove(&oldest);
+                }
+            }
+            self.peers_by_path
+                .retain(|_, peer| self.paths_by_peer.contains_key(peer));
+            debug!(
+                "unix datagram peers evicted: remaining={}",
+                self.paths_by_peer.len()
+            );
+        }
+
+        pub(crate) async fn send_to(
+            &self,
+            buf: &[u8],
+            remote_addr: SocketAddr,
+        ) -> GlobalResult<usize> {
+            let path = self.peer_path_or_err(remote_addr)?;
+            self.socket
+                .send_to(buf, &path)
+                .await
+                .hand_log(|msg| error!("{msg}: remote_addr={remote_addr}, path={}", path.display()))
+        }
+
+        pub(crate) fn try_send_to(
+            &self,
+            buf: &[u8],
+            remote_addr: SocketAddr,
+        ) -> GlobalResult<usize> {
+            let path = self.peer_path_or_err(remote_addr)?;
+            self.socket
+                .try_send_to(buf, &path)
+                .hand_log(|msg| error!("{msg}: remote_addr={remote_addr}, path={}", path.display()))
+        }
+
+        fn peer_path_or_err(&self, remote_addr: SocketAddr) -> GlobalResult<PathBuf> {
+            self.peer_path(&remote_addr).ok_or_else(|| {
+                GlobalError::new_sys_error("unix datagram peer has no reply path", |msg| {
+                    error!("{msg}: remote_addr={remote_addr}")
+                })
+            })
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::{bind_unix_datagram, bind_unix_listener, is_unix_peer_addr, next_unix_peer_addr};
+    use std::os::unix::net::UnixListener;
+    use std::time::Duration;
+
+    #[test]
+    fn synthetic_peer_addresses_are_unique_and_recognizable() {
+        let first = next_unix_peer_addr();
+        let second = next_unix_peer_addr();
+        assert_ne!(first, second);
+        assert!(is_unix_peer_addr(&first));
+        assert!(!is_unix_peer_addr(&"127.0.0.1:1".parse().unwrap()));
+        assert!(!is_unix_peer_addr(&"[::1]:1".parse().unwrap()));
+    }
+
+    #[test]
+    fn bind_replaces_stale_socket_but_not_live_socket_or_regular_file() {
+        let dir = std::env::temp_dir().join(format!("base-unix-bind-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("stream.sock");
+        let _ = std::fs::remove_file(&path);
+
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+        let live = bind_unix_listener(&path).unwrap();
+        assert!(bind_unix_listener(&path).is_err());
+        assert!(bind_unix_datagram(&path).is_err());
+        drop(live);
+        std::fs::remove_file(&path).unwrap();
+
+        std::fs::write(&path, b"not a socket").unwrap();
+        assert!(bind_unix_listener(&path).is_err());
+        assert!(path.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn datagram_peer_table_evicts_idle_then_least_recent_peers() {
+        use super::socket::UnixDatagramSocket;
+        use tokio::io::ReadBuf;
+        use tokio::net::UnixDatagram;
+
+        let dir = std::env::temp_dir().join(format!("base-unix-peers-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let server_path = dir.join("server.sock");
+        let server = UnixDatagramSocket::with_peer_limits(
+            UnixDatagram::bind(&server_path).unwrap(),
+            2,
+            Duration::from_millis(100),
+        );
+        let clients: Vec<_> = (0..3)
+            .map(|i| UnixDatagram::bind(dir.join(format!("client-{i}.sock"))).unwrap())
+            .collect();
+        let (server, server_path, clients) = (&server, &server_path, &clients);
+        let recv = |client: usize| async move {
+            clients[client].send_to(b"x", server_path).await.unwrap();
+            std::future::poll_fn(|cx| {
+                let mut buf = [0u8; 8];
+                server.poll_recv_from(cx, &mut ReadBuf::new(&mut buf))
+            })
+            .await
+            .unwrap()
+        };
+
+        let first = recv(0).await;
+        let second = recv(1).await;
+        assert_eq!(recv(0).await, first);
+        let third = recv(2).await;
+        assert!(server.peer_path(&second).is_none());
+        assert!(server.peer_path(&first).is_some());
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let again = recv(1).await;
+        assert_ne!(again, second);
+        assert!(server.peer_path(&first).is_none());
+        assert!(server.peer_path(&third).is_none());
+        assert_eq!(server.peer_path(&again).unwrap(), dir.join("client-1.sock"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -4,5 +4,7 @@ pub mod crypto;
 pub mod dig62;
 pub mod macros;
 pub mod rt;
+pub mod schedule;
+pub mod tls;
 #[allow(dead_code)]
 pub mod token;
@@ -2,22 +2,28 @@ use crate::daemon::signal::{ExitSignal, Signal};
 use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
 use exception::{GlobalError, GlobalResult};
+use futures::future::BoxFuture;
 use futures::FutureExt;
 use log::{error, info, warn};
 use once_cell::sync::Lazy;
+use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::future::Future;
 use std::panic::{resume_unwind, AssertUnwindSafe};
 use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::runtime::{Handle, Runtime};
 use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
 use tokio_util::task::TaskTracker;
 
+/// Default total budget of [`ShutdownPlan`].
 pub const APPLICATION_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(8);
+/// How long the Unix daemon waits after SIGTERM when the service recorded no shutdown budget.
 pub const DAEMON_STOP_TIMEOUT_SECS: u64 = 10;
+/// Extra time the Unix daemon waits beyond the recorded shutdown budget before SIGKILL.
+pub const DAEMON_STOP_GRACE: Duration = Duration::from_secs(2);
 
 /// | 协议类型 | 推荐线程数 | 运行时类型 | 理由 |
 /// | --- | --- | --- | --- |
@@ -105,6 +111,8 @@ pub struct RuntimeShutdownReport {
     pub cancelled_tasks: usize,
     pub panicked_tasks: usize,
     pub remaining_tasks: Vec<String>,
+    /// `(task name, restart count)` of supervised tasks that were restarted.
+    pub restarted_tasks: Vec<(String, usize)>,
 }
 
 #[derive(Debug, Clone)]
@@ -115,15 +123,205 @@ pub struct ShutdownReport {
     pub runtimes: Vec<RuntimeShutdownReport>,
 }
 
+pub type PreStopHook = Box<dyn FnOnce() -> BoxFuture<'static, ()> + Send>;
+
+struct ShutdownStage {
+    runtime_type: RuntimeType,
+    timeout: Option<Duration>,
+    pre_stop: Vec<PreStopHook>,
+}
+
+impl ShutdownStage {
+    fn new(runtime_type: RuntimeType) -> Self {
+        Self {
+            runtime_type,
+            timeout: None,
+            pre_stop: Vec::new(),
+        }
+    }
+}
+
+/// Stages of [`GlobalRuntime::order_shutdown`] and the total time they may take.
+///
+/// Runtimes stop in the order they were first named; registered runtimes that were not named
+/// stop afterwards, and the main runtime always stops last. A stage without its own timeout gets
+/// an equal share of what the total budget has left after the timeouts of later stages. Pre-stop
+/// hooks run in order before a stage's runtime is cancelled, and their time counts against the
+/// stage.
+pub struct ShutdownPlan {
+    stages: Vec<ShutdownStage>,
+    total_timeout: Duration,
+}
+
+impl Default for ShutdownPlan {
+    fn default() -> Self {
+        Self {
+            stages: Vec::new(),
+            total_timeout: APPLICATION_SHUTDOWN_TIMEOUT,
+        }
+    }
+}
+
+impl ShutdownPlan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stage(mut self, runtime_type: RuntimeType) -> Self {
+        self.stage_mut(runtime_type);
+        self
+    }
+
+    /// Gives the stage of `runtime_type` a dedicated slice, capped by what the total budget has left.
+    pub fn stage_timeout(mut self, runtime_type: RuntimeType, timeout: Duration) -> Self {
+        self.stage_mut(runtime_type).timeout = Some(timeout);
+        self
+    }
+
+    pub fn pre_stop<F, Fut>(mut self, runtime_type: RuntimeType, hook: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.stage_mut(runtime_type)
+            .pre_stop
+            .push(Box::new(move || hook().boxed()));
+        self
+    }
+
+    pub fn total_timeout(mut self, timeout: Duration) -> Self {
+        self.total_timeout = timeout;
+        self
+    }
+
+    pub fn budget(&self) -> Duration {
+        self.total_timeout
+    }
+
+    fn stage_mut(&mut self, runtime_type: RuntimeType) -> &mut ShutdownStage {
+        let index = match self
+            .stages
+            .iter()
+            .position(|stage| stage.runtime_type == runtime_type)
+        {
+            Some(index) => index,
+            None => {
+                self.stages.push(ShutdownStage::new(runtime_type));
+                self.stages.len() - 1
+            }
+        };
+        &mut self.stages[index]
+    }
+}
+
+impl From<&[RuntimeType]> for ShutdownPlan {
+    fn from(orders: &[RuntimeType]) -> Self {
+        orders.iter().cloned().fold(Self::new(), Self::stage)
+    }
+}
+
+impl<const N: usize> From<&[RuntimeType; N]> for ShutdownPlan {
+    fn from(orders: &[RuntimeType; N]) -> Self {
+        Self::from(&orders[..])
+    }
+}
+
+/// Restart budget of a task spawned with [`GlobalRuntime::spawn_supervised`].
+///
+/// At most `max_restarts` restarts are allowed within any `window`; the delay before a restart
+/// doubles from `initial_backoff` up to `max_backoff` with each restart still inside the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartPolicy {
+    pub max_restarts: usize,
+    pub window: Duration,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: 5,
+            window: Duration::from_secs(60),
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RestartPolicy {
+    pub fn new(max_restarts: usize, window: Duration) -> Self {
+        Self {
+            max_restarts,
+            window,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    fn backoff(&self, recent_restarts: usize) -> Duration {
+        let factor = 1u32.checked_shl(recent_restarts as u32).unwrap_or(u32::MAX);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskKind {
+    Async,
+    Blocking,
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskSnapshot {
+    pub name: String,
+    pub kind: TaskKind,
+    pub started_at: SystemTime,
+    pub age: Duration,
+}
+
+/// Live view of one registered runtime; active tasks are listed oldest first.
+#[derive(Debug, Clone)]
+pub struct RuntimeSnapshot {
+    pub runtime_type: RuntimeType,
+    pub accepting_tasks: bool,
+    pub active_tasks: Vec<TaskSnapshot>,
+    pub completed_tasks: usize,
+    pub cancelled_tasks: usize,
+    pub panicked_tasks: usize,
+    pub restarted_tasks: Vec<(String, usize)>,
+}
+
+impl RuntimeSnapshot {
+    /// Active tasks running for longer than `age`, candidates for being stuck.
+    pub fn tasks_older_than(&self, age: Duration) -> impl Iterator<Item = &TaskSnapshot> {
+        self.active_tasks.iter().filter(move |task| task.age > age)
+    }
+}
+
+struct ActiveTask {
+    name: String,
+    kind: TaskKind,
+    started: Instant,
+    started_at: SystemTime,
+}
+
 #[derive(Default)]
 struct TaskState {
     gate: Mutex<()>,
     accepting: AtomicBool,
     next_id: AtomicU64,
-    active: DashMap<u64, String>,
+    active: DashMap<u64, ActiveTask>,
     completed: AtomicUsize,
     cancelled: AtomicUsize,
     panicked: AtomicUsize,
+    restarts: DashMap<String, usize>,
 }
 
 impl TaskState {
@@ -134,9 +332,17 @@ impl TaskState {
         }
     }
 
-    fn start_locked(self: &Arc<Self>, name: String) -> TaskGuard {
+    fn start_locked(self: &Arc<Self>, name: String, kind: TaskKind) -> TaskGuard {
         let id = self.next_id.fetch_add(1, Ordering::Relaxed);
-        self.active.insert(id, name);
+        self.active.insert(
+            id,
+            ActiveTask {
+                name,
+                kind,
+                started: Instant::now(),
+                started_at: SystemTime::now(),
+            },
+        );
         TaskGuard {
             id,
             state: self.clone(),
@@ -152,11 +358,48 @@ impl TaskState {
         let mut names = self
             .active
             .iter()
-            .map(|entry| entry.value().clone())
+            .map(|entry| entry.value().name.clone())
             .collect::<Vec<_>>();
         names.sort();
         names
     }
+
+    fn record_restart(&self, name: &str) {
+        *self.restarts.entry(name.to_string()).or_default() += 1;
+    }
+
+    fn restarted_tasks(&self) -> Vec<(String, usize)> {
+        let mut restarts = self
+            .restarts
+            .iter()
+            .map(|entry| (entry.key().clone(), *entry.value()))
+            .collect::<Vec<_>>();
+        restarts.sort();
+        restarts
+    }
+
+    fn snapshot(&self, runtime_type: RuntimeType) -> RuntimeSnapshot {
+        let mut active_tasks = self
+            .active
+            .iter()
+            .map(|entry| TaskSnapshot {
+                name: entry.name.clone(),
+                kind: entry.kind,
+                started_at: entry.started_at,
+                age: entry.started.elapsed(),
+            })
+            .collect::<Vec<_>>();
+        active_tasks.sort_by(|a, b| b.age.cmp(&a.age).then_with(|| a.name.cmp(&b.name)));
+        RuntimeSnapshot {
+            runtime_type,
+            accepting_tasks: self.accepting.load(Ordering::Acquire),
+            active_tasks,
+            completed_tasks: self.completed.load(Ordering::Relaxed),
+            cancelled_tasks: self.cancelled.load(Ordering::Relaxed),
+            panicked_tasks: self.panicked.load(Ordering::Relaxed),
+            restarted_tasks: self.restarted_tasks(),
+        }
+    }
 }
 
 struct TaskGuard {
@@ -293,6 +536,21 @@ impl RuntimeRegistry {
         })
     }
 
+    fn snapshot(&self) -> Vec<RuntimeSnapshot> {
+        let mut snapshots = self
+            .runtimes
+            .iter()
+            .map(|entry| entry.tasks.snapshot(entry.key().clone()))
+            .collect::<Vec<_>>();
+        snapshots.sort_by_key(|snapshot| {
+            (
+                snapshot.runtime_type != RuntimeType::Main,
+                snapshot.runtime_type.as_thread_name(),
+            )
+        });
+        snapshots
+    }
+
     fn runtime_types(&self) -> Vec<RuntimeType> {
         self.runtimes
             .iter()
@@ -300,11 +558,17 @@ impl RuntimeRegistry {
             .collect()
     }
 
+    #[cfg(test)]
     async fn shutdown(
         &self,
         orders: &[RuntimeType],
         total_timeout: Duration,
     ) -> Vec<RuntimeShutdownReport> {
+        self.execute(ShutdownPlan::from(orders).total_timeout(total_timeout))
+            .await
+    }
+
+    async fn execute(&self, plan: ShutdownPlan) -> Vec<RuntimeShutdownReport> {
         {
             let _gate = self
                 .gate
@@ -312,44 +576,95 @@ impl RuntimeRegistry {
                 .unwrap_or_else(std::sync::PoisonError::into_inner);
             self.shutting_down.store(true, Ordering::Release);
         }
-        let deadline = Instant::now() + total_timeout;
-        let mut ordered = Vec::new();
-        for runtime_type in orders {
-            if runtime_type != &RuntimeType::Main && !ordered.contains(runtime_type) {
-                ordered.push(runtime_type.clone());
-            }
-        }
+        let deadline = Instant::now() + plan.total_timeout;
+        let mut stages = plan.stages;
+        let main_stage = match stages
+            .iter()
+            .position(|stage| stage.runtime_type == RuntimeType::Main)
+        {
+            Some(index) => stages.remove(index),
+            None => ShutdownStage::new(RuntimeType::Main),
+        };
         let mut remaining = self.runtime_types();
         remaining.sort_by_key(RuntimeType::as_thread_name);
         for runtime_type in remaining {
-            if !ordered.contains(&runtime_type) {
+            if !stages
+                .iter()
+                .any(|stage| stage.runtime_type == runtime_type)
+            {
                 warn!(
                     "runtime omitted from shutdown order; appending final stage: runtime_type={}",
                     runtime_type.as_thread_name()
                 );
-                ordered.push(runtime_type);
+                stages.push(ShutdownStage::new(runtime_type));
             }
         }
-
-        let mut reports = Vec::with_capacity(ordered.len() + 1);
-        for (index, runtime_type) in ordered.iter().enumerate() {
-            let stages_left = (ordered.len() - index + 1) as u32;
-            let stage_timeout = deadline
-                .saturating_duration_since(Instant::now())
-                .checked_div(stages_left)
-                .unwrap_or_default();
-            if let Some(report) = self.shutdown_runtime(runtime_type, stage_timeout).await {
+        stages.push(main_stage);
+
+        let timeouts = stages.iter().map(|stage| stage.timeout).collect::<Vec<_>>();
+        let last = stages.len() - 1;
+        let mut reports = Vec::with_capacity(stages.len());
+        for (index, stage) in stages.into_iter().enumerate() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let later = &timeouts[index + 1..];
+            let stage_timeout = match stage.timeout {
+                Some(timeout) => timeout.min(remaining),
+                None if index == last => remaining,
+                None => {
+                    let reserved = later.iter().flatten().sum::<Duration>();
+                    let unfixed = 1 + later.iter().filter(|timeout| timeout.is_none()).count();
+                    remaining.saturating_sub(reserved) / unfixed as u32
+                }
+            };
+            let started = Instant::now();
+            self.run_pre_stop(&stage.runtime_type, stage.pre_stop, stage_timeout)
+                .await;
+            let timeout = stage_timeout.saturating_sub(started.elapsed());
+            let report = if index == last {
+                self.prepare_main_shutdown(timeout).await
+            } else {
+                self.shutdown_runtime(&stage.runtime_type, timeout).await
+            };
+            if let Some(report) = report {
                 log_runtime_report(&report);
                 reports.push(report);
             }
         }
+        reports
+    }
 
-        let main_timeout = deadline.saturating_duration_since(Instant::now());
-        if let Some(report) = self.prepare_main_shutdown(main_timeout).await {
-            log_runtime_report(&report);
-            reports.push(report);
+    async fn run_pre_stop(
+        &self,
+        runtime_type: &RuntimeType,
+        hooks: Vec<PreStopHook>,
+        timeout: Duration,
+    ) {
+        if hooks.is_empty() {
+            return;
+        }
+        let count = hooks.len();
+        let run = async {
+            for hook in hooks {
+                if AssertUnwindSafe(async { hook().await })
+                    .catch_unwind()
+                    .await
+                    .is_err()
+                {
+                    error!(
+                        "pre-stop hook panicked: runtime_type={}",
+                        runtime_type.as_thread_name()
+                    );
+                    self.fail();
+                }
+            }
+        };
+        if tokio::time::timeout(timeout, run).await.is_err() {
+            warn!(
+                "pre-stop hooks timed out: runtime_type={}, hooks={count}, timeout_ms={}",
+                runtime_type.as_thread_name(),
+                timeout.as_millis()
+            );
         }
-        reports
     }
 
     async fn shutdown_runtime(
@@ -401,6 +716,7 @@ impl RuntimeRegistry {
             } else {
                 remaining_tasks
             },
+            restarted_tasks: handle.tasks.restarted_tasks(),
         })
     }
 
@@ -435,6 +751,7 @@ impl RuntimeRegistry {
             cancelled_tasks: handle.tasks.cancelled.load(Ordering::Relaxed),
             panicked_tasks,
             remaining_tasks,
+            restarted_tasks: handle.tasks.restarted_tasks(),
         })
     }
 
@@ -502,6 +819,16 @@ impl GlobalRuntime {
         self.shutting_down.load(Ordering::Acquire)
     }
 
+    /// Active managed tasks and task counters of every registered runtime, main first.
+    pub fn snapshot() -> Vec<RuntimeSnapshot> {
+        GLOBAL_RUNTIMES.snapshot()
+    }
+
+    /// Active managed tasks and task counters of this runtime.
+    pub fn tasks_snapshot(&self) -> RuntimeSnapshot {
+        self.tasks.snapshot(self.runtime_type.clone())
+    }
+
     pub fn spawn<F>(
         &self,
         name: impl Into<String>,
@@ -523,7 +850,7 @@ impl GlobalRuntime {
                 self.runtime_type.as_thread_name()
             )));
         }
-        let mut guard = self.tasks.start_locked(name.clone());
+        let mut guard = self.tasks.start_locked(name.clone(), TaskKind::Async);
         let cancel = self.cancel.clone();
         let failed = self.failed.clone();
         let shutdown_requested = self.shutdown_requested.clone();
@@ -571,7 +898,7 @@ impl GlobalRuntime {
                 self.runtime_type.as_thread_name()
             )));
         }
-        let mut guard = self.tasks.start_locked(name.clone());
+        let mut guard = self.tasks.start_locked(name.clone(), TaskKind::Blocking);
         let cancel = self.cancel.clone();
         let failed = self.failed.clone();
         let shutdown_requested = self.shutdown_requested.clone();
@@ -595,6 +922,75 @@ impl GlobalRuntime {
         Ok(handle)
     }
 
+    /// Runs the future built by `factory` as a managed task and builds a fresh one whenever it
+    /// panics or returns an error, within the budget of `policy`.
+    ///
+    /// Returning `Ok(())` ends the task, and no restart happens once the runtime is cancelled.
+    /// An exhausted budget fails the application like a panicking managed task does.
+    pub fn spawn_supervised<F, Fut>(
+        &self,
+        name: impl Into<String>,
+        policy: RestartPolicy,
+        mut factory: F,
+    ) -> GlobalResult<JoinHandle<()>>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = GlobalResult<()>> + Send + 'static,
+    {
+        let name = name.into();
+        let task_name = name.clone();
+        let cancel = self.cancel.clone();
+        let tasks = self.tasks.clone();
+        let failed = self.failed.clone();
+        let shutdown_requested = self.shutdown_requested.clone();
+        self.spawn(name, async move {
+            let mut restarts = VecDeque::new();
+            loop {
+                let reason = match AssertUnwindSafe(async { factory().await })
+                    .catch_unwind()
+                    .await
+                {
+                    Ok(Ok(())) => return,
+                    Ok(Err(err)) => err.to_string(),
+                    Err(_) => "panic".to_string(),
+                };
+                if cancel.is_cancelled() {
+                    warn!("supervised task stopped during shutdown: task={task_name}, reason={reason}");
+                    return;
+                }
+                let now = Instant::now();
+                while restarts
+                    .front()
+                    .is_some_and(|at| now.duration_since(*at) > policy.window)
+                {
+                    restarts.pop_front();
+                }
+                if restarts.len() >= policy.max_restarts {
+                    error!(
+                        "supervised task exhausted restart budget: task={task_name}, max_restarts={}, window_ms={}, reason={reason}",
+                        policy.max_restarts,
+                        policy.window.as_millis()
+                    );
+                    failed.store(true, Ordering::Release);
+                    shutdown_requested.cancel();
+                    return;
+                }
+                let backoff = policy.backoff(restarts.len());
+                warn!(
+                    "supervised task failed; restarting: task={task_name}, reason={reason}, recent_restarts={}, backoff_ms={}",
+                    restarts.len(),
+                    backoff.as_millis()
+                );
+                tokio::select! {
+                    _ = cancel.cancelled() => return,
+                    _ = tokio::time::sleep(backoff) => {}
+                }
+                restarts.push_back(Instant::now());
+                tasks.record_restart(&task_name);
+            }
+        })
+    }
+
     pub fn request_shutdown() {
         Signal::request_shutdown();
     }
@@ -604,7 +1000,14 @@ impl GlobalRuntime {
         Signal::request_shutdown();
     }
 
-    pub fn order_shutdown(orders: &[RuntimeType]) -> ShutdownReport {
+    /// Waits for an exit signal, then stops the runtimes as `plan` describes.
+    ///
+    /// A slice of runtime types is a plan with those stages and the default budget. The budget
+    /// is recorded for the Unix daemon so that `stop` waits for it before escalating to SIGKILL.
+    pub fn order_shutdown(plan: impl Into<ShutdownPlan>) -> ShutdownReport {
+        let plan = plan.into();
+        let budget = plan.budget();
+        crate::daemon::record_shutdown_budget(budget);
         let main = Self::get_main_runtime();
         let shutdown_requested = main.shutdown_requested.clone();
         let signal = main
@@ -614,16 +1017,13 @@ impl GlobalRuntime {
         info!(
             "application shutdown requested: signal={}, stage_count={}, total_timeout_ms={}",
             signal.as_str(),
-            orders.len(),
-            APPLICATION_SHUTDOWN_TIMEOUT.as_millis()
+            plan.stages.len(),
+            budget.as_millis()
         );
-        let runtimes = main
-            .rt_handle
-            .block_on(GLOBAL_RUNTIMES.shutdown(orders, APPLICATION_SHUTDOWN_TIMEOUT));
+        let runtimes = main.rt_handle.block_on(GLOBAL_RUNTIMES.execute(plan));
         let main_runtime = GLOBAL_RUNTIMES.take_main_runtime();
         if let Some(runtime) = main_runtime {
-            runtime
-                .shutdown_timeout(APPLICATION_SHUTDOWN_TIMEOUT.saturating_sub(started.elapsed()));
+            runtime.shutdown_timeout(budget.saturating_sub(started.elapsed()));
         }
         let outcome = if GLOBAL_RUNTIMES.has_failed() {
             ShutdownOutcome::Incomplete
@@ -705,28 +1105,31 @@ fn log_runtime_report(report: &RuntimeShutdownReport) {
     let runtime_type = report.runtime_type.as_thread_name();
     match report.outcome {
         ShutdownOutcome::Graceful => info!(
-            "runtime shutdown completed: runtime_type={runtime_type}, outcome=graceful, elapsed_ms={}, completed_tasks={}, cancelled_tasks={}, panicked_tasks=0, remaining_tasks=0",
+            "runtime shutdown completed: runtime_type={runtime_type}, outcome=graceful, elapsed_ms={}, completed_tasks={}, cancelled_tasks={}, panicked_tasks=0, remaining_tasks=0, restarted_tasks={:?}",
             report.elapsed.as_millis(),
             report.completed_tasks,
-            report.cancelled_tasks
+            report.cancelled_tasks,
+            report.restarted_tasks
         ),
         ShutdownOutcome::TimedOut => warn!(
-            "runtime shutdown completed: runtime_type={runtime_type}, outcome=timeout, elapsed_ms={}, completed_tasks={}, cancelled_tasks={}, panicked_tasks={}, remaining_tasks={}, task_names={:?}",
+            "runtime shutdown completed: runtime_type={runtime_type}, outcome=timeout, elapsed_ms={}, completed_tasks={}, cancelled_tasks={}, panicked_tasks={}, remaining_tasks={}, task_names={:?}, restarted_tasks={:?}",
             report.elapsed.as_millis(),
             report.completed_tasks,
             report.cancelled_tasks,
             report.panicked_tasks,
             report.remaining_tasks.len(),
-            report.remaining_tasks
+            report.remaining_tasks,
+            report.restarted_tasks
         ),
         ShutdownOutcome::Incomplete => error!(
-            "runtime shutdown completed: runtime_type={runtime_type}, outcome=incomplete, elapsed_ms={}, completed_tasks={}, cancelled_tasks={}, panicked_tasks={}, remaining_tasks={}, task_names={:?}",
+            "runtime shutdown completed: runtime_type={runtime_type}, outcome=incomplete, elapsed_ms={}, completed_tasks={}, cancelled_tasks={}, panicked_tasks={}, remaining_tasks={}, task_names={:?}, restarted_tasks={:?}",
             report.elapsed.as_millis(),
             report.completed_tasks,
             report.cancelled_tasks,
             report.panicked_tasks,
             report.remaining_tasks.len(),
-            report.remaining_tasks
+            report.remaining_tasks,
+            report.restarted_tasks
         ),
     }
 }
@@ -966,4 +1369,215 @@ mod tests {
         network.tasks.close();
         assert!(network.spawn("late", async {}).is_err());
     }
+
+    #[test]
+    fn snapshots_active_tasks_and_counters() {
+        let registry = RuntimeRegistry::new();
+        let network = registry
+            .register(
+                RuntimeType::CommonNetwork,
+                test_runtime(RuntimeType::CommonNetwork),
+            )
+            .expect("network runtime");
+        let main = registry.get(&RuntimeType::Main).expect("main runtime");
+        let done = network.spawn("done", async {}).expect("done task");
+        main.rt_handle.block_on(done).expect("done task result");
+        network
+            .spawn("listener", std::future::pending::<()>())
+            .expect("pending task");
+        std::thread::sleep(Duration::from_millis(20));
+        let cancel = network.cancel.clone();
+        network
+            .spawn_blocking("flush", move || {
+                while !cancel.is_cancelled() {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            })
+            .expect("blocking task");
+
+        let snapshots = registry.snapshot();
+        assert_eq!(
+            snapshots
+                .iter()
+                .map(|snapshot| snapshot.runtime_type.clone())
+                .collect::<Vec<_>>(),
+            [RuntimeType::Main, RuntimeType::CommonNetwork]
+        );
+        let snapshot = network.tasks_snapshot();
+        assert!(snapshot.accepting_tasks);
+        assert_eq!(snapshot.completed_tasks, 1);
+        assert_eq!(
+            snapshot
+                .active_tasks
+                .iter()
+                .map(|task| (task.name.as_str(), task.kind))
+                .collect::<Vec<_>>(),
+            [("listener", TaskKind::Async), ("flush", TaskKind::Blocking)]
+        );
+        assert_eq!(
+            snapshot
+                .tasks_older_than(Duration::from_millis(10))
+                .map(|task| task.name.as_str())
+                .collect::<Vec<_>>(),
+            ["listener"]
+        );
+
+        main.rt_handle
+            .block_on(registry.shutdown(&[RuntimeType::CommonNetwork], Duration::from_millis(200)));
+        let main_runtime = registry.take_main_runtime().expect("take main runtime");
+        main_runtime.shutdown_timeout(Duration::from_secs(1));
+        assert!(!network.tasks_snapshot().accepting_tasks);
+    }
+
+    #[test]
+    fn restarts_supervised_task_until_it_succeeds() {
+        let registry = RuntimeRegistry::new();
+        let network = registry
+            .register(
+                RuntimeType::CommonNetwork,
+                test_runtime(RuntimeType::CommonNetwork),
+            )
+            .expect("network runtime");
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let task_attempts = attempts.clone();
+        let policy = RestartPolicy::new(3, Duration::from_secs(60))
+            .with_backoff(Duration::from_millis(1), Duration::from_millis(5));
+        let task = network
+            .spawn_supervised("flaky", policy, move || {
+                let attempts = task_attempts.clone();
+                async move {
+                    match attempts.fetch_add(1, Ordering::SeqCst) {
+                        0 => panic!("supervised task panic"),
+                        1 => Err(global_runtime_error("supervised task error")),
+                        _ => Ok(()),
+                    }
+                }
+            })
+            .expect("supervised task");
+        let main = registry.get(&RuntimeType::Main).expect("main runtime");
+        main.rt_handle
+            .block_on(task)
+            .expect("supervised task result");
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert!(!registry.has_failed());
+
+        let reports = main
+            .rt_handle
+            .block_on(registry.shutdown(&[RuntimeType::CommonNetwork], Duration::from_secs(1)));
+        let main_runtime = registry.take_main_runtime().expect("take main runtime");
+        main_runtime.shutdown_timeout(Duration::from_secs(1));
+        let report = reports
+            .iter()
+            .find(|report| report.runtime_type == RuntimeType::CommonNetwork)
+            .expect("network report");
+        assert_eq!(report.outcome, ShutdownOutcome::Graceful);
+        assert_eq!(report.panicked_tasks, 0);
+        assert_eq!(report.restarted_tasks, [("flaky".to_string(), 2)]);
+    }
+
+    #[test]
+    fn fails_application_when_restart_budget_is_exhausted() {
+        let registry = RuntimeRegistry::new();
+        let network = registry
+            .register(
+                RuntimeType::CommonNetwork,
+                test_runtime(RuntimeType::CommonNetwork),
+            )
+            .expect("network runtime");
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let task_attempts = attempts.clone();
+        let policy = RestartPolicy::new(2, Duration::from_secs(60))
+            .with_backoff(Duration::from_millis(1), Duration::from_millis(1));
+        let task = network
+            .spawn_supervised("broken", policy, move || {
+                task_attempts.fetch_add(1, Ordering::SeqCst);
+                async { Err(global_runtime_error("supervised task error")) }
+            })
+            .expect("supervised task");
+        let main = registry.get(&RuntimeType::Main).expect("main runtime");
+        main.rt_handle
+            .block_on(task)
+            .expect("supervised task result");
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert!(registry.has_failed());
+        assert_eq!(
+            network.tasks_snapshot().restarted_tasks,
+            [("broken".to_string(), 2)]
+        );
+
+        main.rt_handle
+            .block_on(registry.shutdown(&[RuntimeType::CommonNetwork], Duration::from_millis(200)));
+        let main_runtime = registry.take_main_runtime().expect("take main runtime");
+        main_runtime.shutdown_timeout(Duration::from_secs(1));
+    }
+
+    #[test]
+    fn restart_backoff_doubles_up_to_limit() {
+        let policy = RestartPolicy::new(10, Duration::from_secs(60))
+            .with_backoff(Duration::from_millis(100), Duration::from_millis(500));
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(400));
+        assert_eq!(policy.backoff(3), Duration::from_millis(500));
+        assert_eq!(policy.backoff(64), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn runs_plan_with_pre_stop_hooks_and_stage_timeouts() {
+        let registry = RuntimeRegistry::new();
+        let network = registry
+            .register(
+                RuntimeType::CommonNetwork,
+                test_runtime(RuntimeType::CommonNetwork),
+            )
+            .expect("network runtime");
+        let compute = registry
+            .register(
+                RuntimeType::CommonCompute,
+                test_runtime(RuntimeType::CommonCompute),
+            )
+            .expect("compute runtime");
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let compute_events = events.clone();
+        let compute_cancel = compute.cancel.clone();
+        compute
+            .spawn("compute", async move {
+                compute_cancel.cancelled().await;
+                compute_events.lock().unwrap().push("compute");
+            })
+            .expect("compute task");
+        network
+            .spawn("device-sessions", std::future::pending::<()>())
+            .expect("network task");
+
+        let hook_events = events.clone();
+        let network_cancel = network.cancel.clone();
+        let plan = ShutdownPlan::new()
+            .stage(RuntimeType::CommonCompute)
+            .stage_timeout(RuntimeType::CommonNetwork, Duration::from_millis(100))
+            .pre_stop(RuntimeType::CommonNetwork, move || async move {
+                assert!(!network_cancel.is_cancelled());
+                hook_events.lock().unwrap().push("flush");
+            })
+            .total_timeout(Duration::from_secs(5));
+        assert_eq!(plan.budget(), Duration::from_secs(5));
+        let main = registry.get(&RuntimeType::Main).expect("main runtime");
+        let reports = main.rt_handle.block_on(registry.execute(plan));
+        let main_runtime = registry.take_main_runtime().expect("take main runtime");
+        main_runtime.shutdown_timeout(Duration::from_secs(1));
+
+        assert_eq!(*events.lock().unwrap(), ["compute", "flush"]);
+        assert_eq!(
+            reports
+                .iter()
+                .map(|report| (report.runtime_type.clone(), report.outcome))
+                .collect::<Vec<_>>(),
+            [
+                (RuntimeType::CommonCompute, ShutdownOutcome::Graceful),
+                (RuntimeType::CommonNetwork, ShutdownOutcome::TimedOut),
+                (RuntimeType::Main, ShutdownOutcome::Graceful),
+            ]
+        );
+        assert!(reports[1].elapsed < Duration::from_secs(1));
+        assert!(!registry.has_failed());
+    }
 }
@@ -0,0 +1,682 @@
+use crate::utils::rt::GlobalRuntime;
+use chrono::{
+    DateTime, Datelike, Duration as ChronoDuration, Local, LocalResult, NaiveDate, NaiveDateTime,
+    TimeZone, Timelike,
+};
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use exception::{GlobalError, GlobalResult};
+use futures::FutureExt;
+use log::{error, warn};
+use rand::Rng;
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// When a scheduled job runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    /// Every period measured from the previous scheduled start, so slow runs do not drift.
+    FixedRate(Duration),
+    /// The given delay after the previous run finished.
+    FixedDelay(Duration),
+    /// Matching times of a cron expression in local time.
+    Cron(CronExpr),
+}
+
+impl Schedule {
+    pub fn cron(expr: &str) -> GlobalResult<Self> {
+        CronExpr::parse(expr).map(Self::Cron)
+    }
+}
+
+impl Display for Schedule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FixedRate(period) => write!(f, "fixed-rate {}ms", period.as_millis()),
+            Self::FixedDelay(delay) => write!(f, "fixed-delay {}ms", delay.as_millis()),
+            Self::Cron(expr) => write!(f, "cron {}", expr.source),
+        }
+    }
+}
+
+/// What happens to ticks that come due while the previous run is still going.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverlapPolicy {
+    /// Missed ticks are counted as skipped and the job waits for the next future tick.
+    #[default]
+    Skip,
+    /// Up to this many missed ticks run back to back afterwards; the rest are skipped.
+    Queue(usize),
+}
+
+/// Name, schedule and run options of a job for [`Scheduler::schedule`].
+#[derive(Debug, Clone)]
+pub struct Job {
+    name: String,
+    schedule: Schedule,
+    overlap: OverlapPolicy,
+    jitter: Duration,
+}
+
+impl Job {
+    pub fn new(name: impl Into<String>, schedule: Schedule) -> Self {
+        Self {
+            name: name.into(),
+            schedule,
+            overlap: OverlapPolicy::default(),
+            jitter: Duration::ZERO,
+        }
+    }
+
+    pub fn overlap(mut self, overlap: OverlapPolicy) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// Delays every run by a random amount up to `jitter`, without moving later ticks.
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JobStatus {
+    pub name: String,
+    pub schedule: String,
+    pub active: bool,
+    pub running: bool,
+    pub runs: u64,
+    pub failures: u64,
+    pub skipped: u64,
+    pub last_run: Option<SystemTime>,
+    pub last_duration: Option<Duration>,
+    pub next_run: Option<SystemTime>,
+    pub last_error: Option<String>,
+}
+
+struct JobState {
+    cancel: CancellationToken,
+    status: Mutex<JobStatus>,
+}
+
+impl JobState {
+    fn update(&self, f: impl FnOnce(&mut JobStatus)) {
+        f(&mut self.status.lock().unwrap_or_else(PoisonError::into_inner));
+    }
+
+    fn status(&self) -> JobStatus {
+        self.status
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+/// Periodic and cron jobs running as managed tasks of one runtime.
+///
+/// Every job is a single managed task, so runs of one job never overlap. Cancelling the
+/// runtime during `order_shutdown` lets a run in progress finish and starts no new ones.
+#[derive(Clone)]
+pub struct Scheduler {
+    runtime: GlobalRuntime,
+    cancel: CancellationToken,
+    jobs: Arc<DashMap<String, Arc<JobState>>>,
+}
+
+impl Scheduler {
+    pub fn new(runtime: &GlobalRuntime) -> Self {
+        Self {
+            runtime: runtime.clone(),
+            cancel: runtime.cancel.child_token(),
+            jobs: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// Starts `job`, building the future of each run with `task`.
+    ///
+    /// An error or panic of a run is recorded as the job's last error and the job keeps its
+    /// schedule. A name can be reused once the previous job with that name has stopped.
+    pub fn schedule<F, Fut>(&self, job: Job, task: F) -> GlobalResult<JoinHandle<()>>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = GlobalResult<()>> + Send + 'static,
+    {
+        if let Schedule::FixedRate(period) | Schedule::FixedDelay(period) = &job.schedule {
+            if period.is_zero() {
+                return Err(GlobalError::new_sys_error(
+                    &format!("scheduled job period must be positive: job={}", job.name),
+                    |msg| error!("{msg}"),
+                ));
+            }
+        }
+        let state = Arc::new(JobState {
+            cancel: self.cancel.child_token(),
+            status: Mutex::new(JobStatus {
+                name: job.name.clone(),
+                schedule: job.schedule.to_string(),
+                active: true,
+                running: false,
+                runs: 0,
+                failures: 0,
+                skipped: 0,
+                last_run: None,
+                last_duration: None,
+                next_run: None,
+                last_error: None,
+            }),
+        });
+        match self.jobs.entry(job.name.clone()) {
+            Entry::Occupied(mut entry) if !entry.get().status().active => {
+                entry.insert(state.clone());
+            }
+            Entry::Occupied(_) => {
+                return Err(GlobalError::new_sys_error(
+                    &format!("scheduled job already exists: job={}", job.name),
+                    |msg| error!("{msg}"),
+                ));
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(state.clone());
+            }
+        }
+        let result = self.runtime.spawn(
+            format!("schedule:{}", job.name),
+            run_job(job, state.clone(), task),
+        );
+        if result.is_err() {
+            state.update(|status| status.active = false);
+        }
+        result
+    }
+
+    /// Stops the named job after its current run, if any.
+    pub fn unschedule(&self, name: &str) -> bool {
+        match self.jobs.get(name) {
+            Some(state) => {
+                state.cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stops every job of this scheduler; jobs also stop when the runtime is cancelled.
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    pub fn status(&self, name: &str) -> Option<JobStatus> {
+        self.jobs.get(name).map(|state| state.status())
+    }
+
+    pub fn statuses(&self) -> Vec<JobStatus> {
+        let mut statuses = self
+            .jobs
+            .iter()
+            .map(|entry| entry.status())
+            .collect::<Vec<_>>();
+        statuses.sort_by(|a, b| a.name.cmp(&b.name));
+        statuses
+    }
+}
+
+async fn run_job<F, Fut>(job: Job, state: Arc<JobState>, mut task: F)
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = GlobalResult<()>> + Send + 'static,
+{
+    let mut cursor = Cursor::new(&job.schedule);
+    let mut pending = 0usize;
+    while !cursor.is_exhausted() {
+        if pending == 0 {
+            let fire = cursor.due() + sample_jitter(job.jitter);
+            state.update(|status| status.next_run = Some(system_time_of(fire)));
+            tokio::select! {
+                _ = state.cancel.cancelled() => break,
+                _ = tokio::time::sleep_until(fire) => {}
+            }
+            cursor.step();
+        } else {
+            pending -= 1;
+        }
+        if state.cancel.is_cancelled() {
+            break;
+        }
+
+        let started = Instant::now();
+        state.update(|status| {
+            status.running = true;
+            status.next_run = None;
+            status.last_run = Some(SystemTime::now());
+        });
+        let result = AssertUnwindSafe(async { task().await })
+            .catch_unwind()
+            .await;
+        let error = match result {
+            Ok(Ok(())) => None,
+            Ok(Err(err)) => Some(err.to_string()),
+            Err(_) => Some("panic".to_string()),
+        };
+        if let Some(error) = &error {
+            warn!("scheduled job failed: job={}, reason={error}", job.name);
+        }
+        let missed = cursor.catch_up();
+        let skipped = match job.overlap {
+            OverlapPolicy::Skip => missed,
+            OverlapPolicy::Queue(max) => {
+                let queued = (pending + missed).min(max);
+                let skipped = pending + missed - queued;
+                pending = queued;
+                skipped
+            }
+        };
+        state.update(|status| {
+            status.running = false;
+            status.runs += 1;
+            status.last_duration = Some(started.elapsed());
+            status.skipped += skipped as u64;
+            if let Some(error) = error {
+                status.failures += 1;
+                status.last_error = Some(error);
+            }
+        });
+    }
+    if cursor.is_exhausted() {
+        warn!("scheduled job has no further run time: job={}", job.name);
+    }
+    state.update(|status| {
+        status.active = false;
+        status.next_run = None;
+    });
+}
+
+/// Next scheduled tick of a job.
+enum Cursor {
+    Rate {
+        next: Instant,
+        period: Duration,
+    },
+    Delay {
+        next: Instant,
+        delay: Duration,
+    },
+    Cron {
+        next: Option<DateTime<Local>>,
+        expr: CronExpr,
+    },
+}
+
+impl Cursor {
+    fn new(schedule: &Schedule) -> Self {
+        let now = Instant::now();
+        match schedule {
+            Schedule::FixedRate(period) => Self::Rate {
+                next: now + *period,
+                period: *period,
+            },
+            Schedule::FixedDelay(delay) => Self::Delay {
+                next: now + *delay,
+                delay: *delay,
+            },
+            Schedule::Cron(expr) => Self::Cron {
+                next: expr.next_after(Local::now()),
+                expr: expr.clone(),
+            },
+        }
+    }
+
+    fn due(&self) -> Instant {
+        match self {
+            Self::Rate { next, .. } | Self::Delay { next, .. } => *next,
+            Self::Cron { next, .. } => {
+                let wait = next
+                    .and_then(|next| (next - Local::now()).to_std().ok())
+                    .unwrap_or_default();
+                Instant::now() + wait
+            }
+        }
+    }
+
+    fn is_exhausted(&self) -> bool {
+        matches!(self, Self::Cron { next: None, .. })
+    }
+
+    /// Moves past the tick that is about to run.
+    fn step(&mut self) {
+        match self {
+            Self::Rate { next, period } => *next += *period,
+            Self::Delay { .. } => {}
+            Self::Cron { next, expr } => *next = next.and_then(|tick| expr.next_after(tick)),
+        }
+    }
+
+    /// Moves past the ticks that came due during a run and returns how many there were.
+    fn catch_up(&mut self) -> usize {
+        let mut missed = 0;
+        match self {
+            Self::Rate { next, period } => {
+                let now = Instant::now();
+                while *next <= now {
+                    missed += 1;
+                    *next += *period;
+                }
+            }
+            Self::Delay { next, delay } => *next = Instant::now() + *delay,
+            Self::Cron { next, expr } => {
+                let now = Local::now();
+                while let Some(tick) = next.filter(|tick| *tick <= now) {
+                    missed += 1;
+                    *next = expr.next_after(tick);
+                }
+            }
+        }
+        missed
+    }
+}
+
+fn sample_jitter(jitter: Duration) -> Duration {
+    if jitter.is_zero() {
+        return Duration::ZERO;
+    }
+    rand::thread_rng().gen_range(Duration::ZERO..=jitter)
+}
+
+fn system_time_of(instant: Instant) -> SystemTime {
+    SystemTime::now() + instant.saturating_duration_since(Instant::now())
+}
+
+/// Cron expression with five fields (minute hour day-of-month month day-of-week) or six with a
+/// leading seconds field. Fields take `*`, `?`, values, ranges `a-b`, steps `*/n` or `a-b/n`,
+/// comma lists, and English month and weekday abbreviations; `@hourly`, `@daily`, `@weekly`,
+/// `@monthly` and `@yearly` are accepted as well. When both day fields are restricted, a day
+/// matching either of them matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpr {
+    source: String,
+    seconds: u64,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+const WEEKDAY_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+/// How far ahead `next_after` looks before giving up, e.g. for `0 0 30 2 *`.
+const CRON_SEARCH_YEARS: i32 = 5;
+
+impl CronExpr {
+    pub fn parse(expr: &str) -> GlobalResult<Self> {
+        let source = expr.trim();
+        let expanded = match source {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+        let fields = expanded.split_whitespace().collect::<Vec<_>>();
+        let (seconds, rest) = match fields.len() {
+            5 => ("0", &fields[..]),
+            6 => (fields[0], &fields[1..]),
+            count => {
+                return Err(invalid_cron(
+                    source,
+                    &format!("expected 5 or 6 fields, found {count}"),
+                ))
+            }
+        };
+        let field = |value: &str, min: u32, max: u32, names: &[&str]| {
+            parse_field(value, min, max, names).map_err(|reason| invalid_cron(source, &reason))
+        };
+        let mut weekdays = field(rest[4], 0, 7, &WEEKDAY_NAMES)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays & !(1 << 7)) | 1;
+        }
+        Ok(Self {
+            source: source.to_string(),
+            seconds: field(seconds, 0, 59, &[])?,
+            minutes: field(rest[0], 0, 59, &[])?,
+            hours: field(rest[1], 0, 23, &[])?,
+            days: field(rest[2], 1, 31, &[])?,
+            months: field(rest[3], 1, 12, &MONTH_NAMES)?,
+            weekdays,
+            any_day: is_wildcard(rest[2]),
+            any_weekday: is_wildcard(rest[4]),
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// First matching time strictly after `after`; skipped local times of a DST change are passed over.
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let mut from = after.naive_local();
+        loop {
+            let next = self.next_naive_after(from)?;
+            match Local.from_local_datetime(&next) {
+                LocalResult::Single(time) => return Some(time),
+                LocalResult::Ambiguous(earliest, _) => return Some(earliest),
+                LocalResult::None => from = next,
+            }
+        }
+    }
+
+    fn next_naive_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut time = after.with_nanosecond(0)? + ChronoDuration::seconds(1);
+        let last_year = after.year() + CRON_SEARCH_YEARS;
+        while time.year() <= last_year {
+            let date = time.date();
+            if !has(self.months, time.month()) {
+                let (year, month) = if time.month() == 12 {
+                    (time.year() + 1, 1)
+                } else {
+                    (time.year(), time.month() + 1)
+                };
+                time = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+            } else if !self.matches_day(date) {
+                time = date.succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if !has(self.hours, time.hour()) {
+                time = date.and_hms_opt(time.hour(), 0, 0)? + ChronoDuration::hours(1);
+            } else if !has(self.minutes, time.minute()) {
+                time =
+                    date.and_hms_opt(time.hour(), time.minute(), 0)? + ChronoDuration::minutes(1);
+            } else if !has(self.seconds, time.second()) {
+                time += ChronoDuration::seconds(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day = has(self.days, date.day());
+        let weekday = has(self.weekdays, date.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+impl Display for CronExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn has(mask: u64, value: u32) -> bool {
+    mask & (1 << value) != 0
+}
+
+fn is_wildcard(field: &str) -> bool {
+    field == "*" || field == "?"
+}
+
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid step `{part}`"))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = if is_wildcard(range) {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (
+                parse_value(start, min, names)?,
+                parse_value(end, min, names)?,
+            )
+        } else {
+            let start = parse_value(range, min, names)?;
+            (start, if step > 1 { max } else { start })
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("`{part}` is outside {min}-{max}"));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+fn parse_value(value: &str, min: u32, names: &[&str]) -> Result<u32, String> {
+    if let Some(index) = names
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(value))
+    {
+        return Ok(index as u32 + min);
+    }
+    value
+        .parse::<u32>()
+        .map_err(|_| format!("invalid value `{value}`"))
+}
+
+fn invalid_cron(expr: &str, reason: &str) -> GlobalError {
+    GlobalError::new_sys_error(
+        &format!("invalid cron expression: expr={expr}, reason={reason}"),
+        |msg| error!("{msg}"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn at(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn next(expr: &str, after: &str) -> Option<NaiveDateTime> {
+        CronExpr::parse(expr).unwrap().next_naive_after(at(after))
+    }
+
+    #[test]
+    fn cron_finds_next_matching_time() {
+        assert_eq!(
+            next("*/15 * * * *", "2024-01-01 10:07:30"),
+            Some(at("2024-01-01 10:15:00"))
+        );
+        assert_eq!(
+            next("30 0 9 * * MON-FRI", "2024-01-05 09:00:30"),
+            Some(at("2024-01-08 09:00:30"))
+        );
+        assert_eq!(
+            next("0 0 1 * SUN", "2024-01-01 00:00:00"),
+            Some(at("2024-01-07 00:00:00"))
+        );
+        assert_eq!(
+            next("@monthly", "2024-01-31 12:00:00"),
+            Some(at("2024-02-01 00:00:00"))
+        );
+        assert_eq!(
+            next("0 12 29 FEB *", "2024-03-01 00:00:00"),
+            Some(at("2028-02-29 12:00:00"))
+        );
+        assert_eq!(next("0 0 30 2 *", "2024-01-01 00:00:00"), None);
+        for invalid in [
+            "* * * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "x * * * *",
+        ] {
+            assert!(CronExpr::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn skips_overlapping_ticks_and_records_errors() {
+        let runtime = GlobalRuntime::get_main_runtime();
+        let scheduler = Scheduler::new(&runtime);
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let (task_running, task_max) = (running.clone(), max_running.clone());
+        let job = Job::new("slow-sync", Schedule::FixedRate(Duration::from_millis(10)));
+        let handle = scheduler
+            .schedule(job, move || {
+                let (running, max_running) = (task_running.clone(), task_max.clone());
+                async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(35)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    Err(GlobalError::new_sys_error("sync failed", |_| {}))
+                }
+            })
+            .expect("schedule job");
+        assert!(scheduler
+            .schedule(
+                Job::new("slow-sync", Schedule::FixedDelay(Duration::from_secs(1))),
+                || async { Ok(()) },
+            )
+            .is_err());
+        for schedule in [
+            Schedule::FixedRate(Duration::ZERO),
+            Schedule::FixedDelay(Duration::ZERO),
+        ] {
+            assert!(scheduler
+                .schedule(Job::new("busy", schedule), || async { Ok(()) })
+                .is_err());
+        }
+        assert!(scheduler.status("busy").is_none());
+
+        runtime.rt_handle.block_on(async {
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            assert!(scheduler.unschedule("slow-sync"));
+            handle.await.expect("job task");
+        });
+        let status = scheduler.status("slow-sync").expect("job status");
+        assert!(!status.active);
+        assert!(status.runs >= 2);
+        assert_eq!(status.failures, status.runs);
+        assert!(status.skipped >= status.runs);
+        assert_eq!(status.last_error.as_deref(), Some("sync failed"));
+        assert_eq!(status.schedule, "fixed-rate 10ms");
+        assert_eq!(max_running.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,48 @@
+//! TLS file settings shared by `base::net` TCP endpoints and `base_rpc` channels/servers.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub(crate) const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientAuthMode {
+    Disabled,
+    Required,
+    Optional,
+}
+
+/// TLS material locations.
+///
+/// Servers use `certificate_path`/`private_key_path` and verify peers against
+/// `ca_certificate_path` when `client_auth` is enabled. Clients trust `ca_certificate_path`
+/// and, with `use_native_roots`, the platform roots; they present
+/// `client_certificate_path`/`client_private_key_path` for mTLS.
+#[derive(Debug, Clone)]
+pub struct TlsFileConfig {
+    pub certificate_path: Option<PathBuf>,
+    pub private_key_path: Option<PathBuf>,
+    pub ca_certificate_path: Option<PathBuf>,
+    pub client_certificate_path: Option<PathBuf>,
+    pub client_private_key_path: Option<PathBuf>,
+    pub domain_name: Option<String>,
+    pub use_native_roots: bool,
+    pub client_auth: ClientAuthMode,
+    pub handshake_timeout: Duration,
+}
+
+impl Default for TlsFileConfig {
+    fn default() -> Self {
+        Self {
+            certificate_path: None,
+            private_key_path: None,
+            ca_certificate_path: None,
+            client_certificate_path: None,
+            client_private_key_path: None,
+            domain_name: None,
+            use_native_roots: false,
+            client_auth: ClientAuthMode::Disabled,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use base::cache::ttl::TtlCache;
+
+use crate::sqlx::{Database, Pool};
+use crate::DatabaseError;
+
+/// Reads the version column of a cached row.
+pub type VersionOf<V> = fn(&V) -> i64;
+
+/// Fills in flight for one key, and how many writes landed since the oldest of them began.
+#[derive(Default)]
+struct Fence {
+    readers: usize,
+    generation: u64,
+}
+
+/// Read-through cache over a sqlx pool.
+///
+/// Reads go through [`fetch`](Self::fetch), which caches `Some` results under a caller-chosen
+/// key. Writes go through [`write`](Self::write), which drops the affected keys afterwards and
+/// discards any fill of them whose query was already running. With
+/// [`with_version`](Self::with_version) a fill is also discarded when the cache already holds a
+/// newer version of the row, which covers writers that bypass this helper.
+pub struct CacheAside<DB: Database, V> {
+    pool: Pool<DB>,
+    cache: TtlCache<String, V>,
+    version: Option<VersionOf<V>>,
+    fences: Mutex<HashMap<String, Fence>>,
+}
+
+impl<DB, V> CacheAside<DB, V>
+where
+    DB: Database,
+    V: Clone + Send + Sync + 'static,
+{
+    /// Caches rows for `ttl`; must be called inside a tokio runtime.
+    pub fn new(pool: Pool<DB>, ttl: Duration) -> Self {
+        Self::with_cache(pool, TtlCache::new(ttl))
+    }
+
+    pub fn with_cache(pool: Pool<DB>, cache: TtlCache<String, V>) -> Self {
+        Self {
+            pool,
+            cache,
+            version: None,
+            fences: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_version(mut self, version: VersionOf<V>) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    pub fn pool(&self) -> &Pool<DB> {
+        &self.pool
+    }
+
+    pub fn cache(&self) -> &TtlCache<String, V> {
+        &self.cache
+    }
+
+    /// Returns the cached row for `key`, or runs `query` and caches what it found.
+    pub async fn fetch<F, Fut>(&self, key: &str, query: F) -> Result<Option<V>, DatabaseError>
+    where
+        F: FnOnce(Pool<DB>) -> Fut,
+        Fut: Future<Output = Result<Option<V>, crate::sqlx::Error>>,
+    {
+        let key = key.to_string();
+        if let Some(value) = self.cache.get(&key) {
+            return Ok(Some(value));
+        }
+        let reader = self.enter(&key);
+        let value = query(self.pool.clone()).await?;
+        if let Some(value) = &value {
+            let fences = self.lock();
+            if fences
+                .get(&key)
+                .is_some_and(|fence| fence.generation == reader.generation)
+            {
+                self.fill(&key, value.clone())?;
+            }
+        }
+        Ok(value)
+    }
+
+    /// Runs `exec`, then drops `keys` from the cache whether or not it succeeded.
+    pub async fn write<T, F, Fut>(&self, keys: &[&str], exec: F) -> Result<T, DatabaseError>
+    where
+        F: FnOnce(Pool<DB>) -> Fut,
+        Fut: Future<Output = Result<T, crate::sqlx::Error>>,
+    {
+        let result = exec(self.pool.clone()).await;
+        self.invalidate(keys);
+        Ok(result?)
+    }
+
+    /// Caches `value` as the current row for `key`, for example one returned by an update.
+    ///
+    /// Fills that are still running for `key` are discarded.
+    pub fn put(&self, key: &str, value: V) -> Result<(), DatabaseError> {
+        let mut fences = self.lock();
+        if let Some(fence) = fences.get_mut(key) {
+            fence.generation += 1;
+        }
+        self.fill(key, value)
+    }
+
+    /// Drops `keys` from the cache and discards fills of them that are still running.
+    pub fn invalidate(&self, keys: &[&str]) {
+        let mut fences = self.lock();
+        for key in keys {
+            if let Some(fence) = fences.get_mut(*key) {
+                fence.generation += 1;
+            }
+            self.cache.remove(&key.to_string());
+        }
+    }
+
+    /// Stores `value` unless the cache holds a newer version; callers hold the fence lock.
+    fn fill(&self, key: &str, value: V) -> Result<(), DatabaseError> {
+        let key = key.to_string();
+        if let Some(version) = self.version {
+            if let Some(cached) = self.cache.get(&key) {
+                if version(&cached) > version(&value) {
+                    return Ok(());
+                }
+            }
+        }
+        self.cache.insert(key, value)?;
+        Ok(())
+    }
+
+    fn enter(&self, key: &str) -> Reader<'_> {
+        let mut fences = self.lock();
+        let fence = fences.entry(key.to_string()).or_default();
+        fence.readers += 1;
+        Reader {
+            fences: &self.fences,
+            key: key.to_string(),
+            generation: fence.generation,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Fence>> {
+        self.fences.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Registration of a running fill; the fence is dropped with its last reader.
+struct Reader<'a> {
+    fences: &'a Mutex<HashMap<String, Fence>>,
+    key: String,
+    generation: u64,
+}
+
+impl Drop for Reader<'_> {
+    fn drop(&mut self) {
+        let mut fences = self.fences.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(fence) = fences.get_mut(&self.key) {
+            fence.readers -= 1;
+            if fence.readers == 0 {
+                fences.remove(&self.key);
+            }
+        }
+    }
+}
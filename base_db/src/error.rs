@@ -13,6 +13,9 @@ pub enum DatabaseError {
     #[cfg(feature = "sqlite")]
     #[error("sqlite integrity check failed: {0}")]
     Integrity(String),
+    #[cfg(feature = "cache")]
+    #[error("cache error: {0}")]
+    Cache(#[from] base::exception::GlobalError),
     #[error("migration conflict at version {version}: expected {expected}, found {actual}")]
     MigrationConflict {
         version: i64,
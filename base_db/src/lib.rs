@@ -1,5 +1,7 @@
 #[cfg(feature = "sqlite")]
 pub mod backup;
+#[cfg(all(feature = "cache", any(feature = "mysql", feature = "sqlite")))]
+pub mod cache_aside;
 pub mod dbx;
 pub mod error;
 pub mod health;
@@ -0,0 +1,193 @@
+#![cfg(all(feature = "sqlite", feature = "cache"))]
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base_db::cache_aside::CacheAside;
+use base_db::dbx::sqlitex::{build_sqlite_pool, SqliteConnectionConfig};
+use base_db::dbx::DatabasePoolConfig;
+use base_db::sqlx::{Sqlite, SqlitePool};
+use tokio::sync::oneshot;
+
+#[derive(Debug, Clone, PartialEq, Eq, base_db::sqlx::FromRow)]
+struct Item {
+    id: i64,
+    name: String,
+    version: i64,
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    let unique = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    std::env::temp_dir().join(format!("base-db-{name}-{}-{unique}.db", std::process::id()))
+}
+
+async fn items(path: &Path) -> SqlitePool {
+    let pool = build_sqlite_pool(
+        SqliteConnectionConfig::new(path),
+        DatabasePoolConfig {
+            max_size: 4,
+            ..DatabasePoolConfig::default()
+        },
+    )
+    .unwrap();
+    base_db::sqlx::raw_sql(
+        "CREATE TABLE items(id INTEGER PRIMARY KEY, name TEXT NOT NULL, version INTEGER NOT NULL);
+         INSERT INTO items VALUES (1, 'first', 1);",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    pool
+}
+
+async fn cleanup(pool: SqlitePool, path: &Path) {
+    pool.close().await;
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+}
+
+async fn select_item(pool: SqlitePool, id: i64) -> Result<Option<Item>, base_db::sqlx::Error> {
+    base_db::sqlx::query_as("SELECT id, name, version FROM items WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&pool)
+        .await
+}
+
+async fn rename(pool: SqlitePool, id: i64, name: &str) -> Result<u64, base_db::sqlx::Error> {
+    base_db::sqlx::query("UPDATE items SET name = ?, version = version + 1 WHERE id = ?")
+        .bind(name)
+        .bind(id)
+        .execute(&pool)
+        .await
+        .map(|done| done.rows_affected())
+}
+
+#[tokio::test]
+async fn reads_through_and_writes_invalidate() {
+    let path = temp_path("cache-aside");
+    let pool = items(&path).await;
+    let repo = CacheAside::<Sqlite, Item>::new(pool.clone(), Duration::from_secs(60));
+    let queries = AtomicUsize::new(0);
+    let fetch = || {
+        repo.fetch("item:1", |pool| {
+            queries.fetch_add(1, Ordering::SeqCst);
+            select_item(pool, 1)
+        })
+    };
+
+    assert_eq!(fetch().await.unwrap().unwrap().name, "first");
+    assert_eq!(fetch().await.unwrap().unwrap().name, "first");
+    assert_eq!(queries.load(Ordering::SeqCst), 1);
+
+    let updated = repo
+        .write(&["item:1"], |pool| rename(pool, 1, "second"))
+        .await
+        .unwrap();
+    assert_eq!(updated, 1);
+    assert_eq!(fetch().await.unwrap().unwrap().name, "second");
+    assert_eq!(queries.load(Ordering::SeqCst), 2);
+
+    assert_eq!(
+        repo.fetch("item:9", |pool| select_item(pool, 9))
+            .await
+            .unwrap(),
+        None
+    );
+    assert!(repo.cache().get(&"item:9".to_string()).is_none());
+    cleanup(pool, &path).await;
+}
+
+#[tokio::test]
+async fn write_discards_fill_started_before_it() {
+    let path = temp_path("cache-aside-fence");
+    let pool = items(&path).await;
+    let repo = Arc::new(CacheAside::<Sqlite, Item>::new(
+        pool.clone(),
+        Duration::from_secs(60),
+    ));
+    let (read_tx, read_rx) = oneshot::channel();
+    let (release_tx, release_rx) = oneshot::channel::<()>();
+    let slow = tokio::spawn({
+        let repo = repo.clone();
+        async move {
+            repo.fetch("item:1", |pool| async move {
+                let item = select_item(pool, 1).await;
+                read_tx.send(()).unwrap();
+                release_rx.await.unwrap();
+                item
+            })
+            .await
+        }
+    });
+
+    read_rx.await.unwrap();
+    repo.write(&["item:1"], |pool| rename(pool, 1, "second"))
+        .await
+        .unwrap();
+    release_tx.send(()).unwrap();
+    assert_eq!(slow.await.unwrap().unwrap().unwrap().name, "first");
+    assert!(repo.cache().get(&"item:1".to_string()).is_none());
+    let fresh = repo
+        .fetch("item:1", |pool| select_item(pool, 1))
+        .await
+        .unwrap();
+    assert_eq!(fresh.unwrap().name, "second");
+    cleanup(pool, &path).await;
+}
+
+#[tokio::test]
+async fn version_column_rejects_older_fill() {
+    let path = temp_path("cache-aside-version");
+    let pool = items(&path).await;
+    let repo = Arc::new(
+        CacheAside::<Sqlite, Item>::new(pool.clone(), Duration::from_secs(60))
+            .with_version(|item| item.version),
+    );
+    let (read_tx, read_rx) = oneshot::channel();
+    let (release_tx, release_rx) = oneshot::channel::<()>();
+    let slow = tokio::spawn({
+        let repo = repo.clone();
+        async move {
+            repo.fetch("item:1", |pool| async move {
+                let item = select_item(pool, 1).await;
+                read_tx.send(()).unwrap();
+                release_rx.await.unwrap();
+                item
+            })
+            .await
+        }
+    });
+
+    read_rx.await.unwrap();
+    // Another process updates the row without going through this cache.
+    rename(pool.clone(), 1, "second").await.unwrap();
+    let fresh = repo
+        .fetch("item:1", |pool| select_item(pool, 1))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(fresh.version, 2);
+    release_tx.send(()).unwrap();
+    assert_eq!(slow.await.unwrap().unwrap().unwrap().version, 1);
+    assert_eq!(repo.cache().get(&"item:1".to_string()), Some(fresh));
+
+    let newer = Item {
+        id: 1,
+        name: "third".to_string(),
+        version: 3,
+    };
+    repo.put("item:1", newer.clone()).unwrap();
+    assert_eq!(
+        repo.fetch("item:1", |pool| select_item(pool, 1))
+            .await
+            .unwrap(),
+        Some(newer)
+    );
+    cleanup(pool, &path).await;
+}
@@ -18,4 +18,8 @@ pub enum MessageBusError {
     NotFound,
     #[error("通道已存在")]
     AlreadyExists,
+    #[error("主题无效: {0}")]
+    InvalidTopic(String),
+    #[error("容量无效: {0}")]
+    InvalidCapacity(usize),
 }